@@ -1,20 +1,36 @@
 //! Command line argument parsing
 
-use std::{num::ParseIntError, ops::Range, process, sync::Arc};
+use std::{path::PathBuf, process, sync::Arc};
 
-use anyhow::Context;
-use clap::{value_parser, Arg, ArgMatches, Command};
+use clap::{error::ErrorKind, value_parser, Arg, ArgAction, ArgMatches, Command};
 use cpal::{
     traits::{DeviceTrait, HostTrait},
     SupportedStreamConfig,
 };
 
 use crate::{
-    audio::windows::{self, Window},
+    audio::{
+        algorithms::ChannelSelect,
+        passthrough::{ChannelMap, ResampleQuality},
+        sequence::Sequence,
+        tone::{Tone, Waveform},
+        windows,
+    },
+    coding::{ctcss, dtmf::DtmfConfig, format::DataFormat},
+    misc::SampleRate,
     modules::{
+        afsk::{afsk_receive, afsk_send},
+        ctcss::{ctcss_receive, ctcss_send},
+        dataset::{dataset_receive, dataset_send},
         dtmf::{dtmf_receive, dtmf_send},
-        morse::{morse_receive, morse_send},
-        range_test, spectrum_analyzer, true_random, InitContext, Module,
+        frequency_counter, meter,
+        morse::{morse_key, morse_receive, morse_send},
+        range_test,
+        rtty::{rtty_receive, rtty_send},
+        scope, sequence, spectrum_analyzer, sweep,
+        tones::tones_detect,
+        true_random::{self, Debias},
+        tune, InitContext, Module,
     },
 };
 
@@ -23,6 +39,17 @@ type BoxedModule = Box<Arc<dyn Module + Send + Sync + 'static>>;
 
 /// Parse command line args
 pub fn parse_args() -> ArgMatches {
+    let matches = command().get_matches();
+    if let Some(("spectrum", m)) = matches.subcommand() {
+        if let Err(e) = spectrum_analyzer::check_args(m) {
+            command().error(ErrorKind::ArgumentConflict, e).exit();
+        }
+    }
+    matches
+}
+
+/// Builds the command line interface, with every subcommand and its args
+pub fn command() -> Command {
     Command::new("radio-data")
         .author("Connor Slade")
         .version(env!("CARGO_PKG_VERSION"))
@@ -30,13 +57,13 @@ pub fn parse_args() -> ArgMatches {
         .arg(
             Arg::new("input-device")
                 .short('i')
-                .help("The input device to use.")
+                .help("The input device to use, either its name or index.")
                 .default_value("default"),
         )
         .arg(
             Arg::new("output-device")
                 .short('o')
-                .help("The output device to use.")
+                .help("The output device to use, either its name or index.")
                 .default_value("default"),
         )
         .arg(
@@ -53,13 +80,232 @@ pub fn parse_args() -> ArgMatches {
                 .default_value("1.0")
                 .value_parser(value_parser!(f32)),
         )
+        .arg(
+            Arg::new("dc-block")
+                .long("dc-block")
+                .help("Remove any DC offset from the input with a high-pass filter.")
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("agc")
+                .long("agc")
+                .help("Automatically adjust the input gain to keep the level near a target.")
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("agc-target")
+                .long("agc-target")
+                .help("The peak level the AGC keeps the input at, from 0 to 1.")
+                .value_parser(unit_interval)
+                .default_value("0.5"),
+        )
+        .arg(
+            Arg::new("agc-min-gain")
+                .long("agc-min-gain")
+                .help("The lowest gain the AGC will apply.")
+                .value_parser(value_parser!(f32))
+                .default_value("0.1"),
+        )
+        .arg(
+            Arg::new("agc-max-gain")
+                .long("agc-max-gain")
+                .help(
+                    "The highest gain the AGC will apply, so silence isn't raised into loud noise.",
+                )
+                .value_parser(value_parser!(f32))
+                .default_value("100"),
+        )
+        .arg(
+            Arg::new("input-rate")
+                .long("input-rate")
+                .help("The sample rate to use for the input device. Uses the device's default if not set.")
+                .value_parser(value_parser!(u32)),
+        )
+        .arg(
+            Arg::new("output-rate")
+                .long("output-rate")
+                .help("The sample rate to use for the output device. Uses the device's default if not set.")
+                .value_parser(value_parser!(u32)),
+        )
+        .arg(
+            Arg::new("wav-in")
+                .long("wav-in")
+                .help("Read the input from a WAV file instead of the input device.")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("wav-loop")
+                .long("wav-loop")
+                .help("Loop the input WAV file instead of exiting when it ends.")
+                .requires("wav-in")
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("wav-out")
+                .long("wav-out")
+                .help("Also write everything sent to the output device into a WAV file.")
+                .value_parser(value_parser!(PathBuf)),
+        )
         .subcommands([
             Command::new("device")
                 .alias("dev")
                 .about("Lists the available audio devices."),
+            Command::new("windows")
+                .about("Lists the window functions that can be used with `--window`."),
             Command::new("range")
                 .alias("r")
-                .about("Lets you test the range of your radio system."),
+                .about("Lets you test the range of your radio system.")
+                .arg(
+                    Arg::new("waveform")
+                        .short('w')
+                        .help("The waveform of the tone played when the code is received.")
+                        .value_parser(value_parser!(Waveform))
+                        .default_value("sine"),
+                )
+                .arg(
+                    Arg::new("code")
+                        .short('c')
+                        .long("code")
+                        .help("The DTMF code that triggers the response tone.")
+                        .value_parser(|x: &str| {
+                            let code = x.to_ascii_uppercase();
+                            let values = DtmfConfig::standard().values;
+                            anyhow::ensure!(!code.is_empty(), "The code can't be empty");
+                            anyhow::ensure!(
+                                code.bytes().all(|x| values.contains(&x)),
+                                "The code can only contain DTMF characters (0-9, A-D, *, #)"
+                            );
+                            Ok::<String, anyhow::Error>(code)
+                        })
+                        .default_value("ABCD"),
+                )
+                .arg(
+                    Arg::new("response-freq")
+                        .long("response-freq")
+                        .help("The frequency of the response tone in Hz.")
+                        .value_parser(value_parser!(f32))
+                        .default_value("440"),
+                )
+                .arg(
+                    Arg::new("response-duration")
+                        .long("response-duration")
+                        .help("The duration of the response tone in seconds.")
+                        .value_parser(value_parser!(f32))
+                        .default_value("5"),
+                )
+                .arg(
+                    Arg::new("response")
+                        .long("response")
+                        .help(
+                            "Tones to respond with in the `sequence` file format, split by commas.",
+                        )
+                        .conflicts_with_all(["response-freq", "response-duration"])
+                        .value_parser(|x: &str| {
+                            // The sample rate doesn't matter when only checking the format
+                            let seq = x.replace(',', "\n");
+                            Sequence::<Tone>::from_seq(&seq, SampleRate::from_hz(48000))
+                                .map_err(|e| anyhow::anyhow!("{e:#}"))?;
+                            Ok::<String, anyhow::Error>(seq)
+                        }),
+                )
+                .arg(
+                    Arg::new("log")
+                        .long("log")
+                        .help("Append each successful exchange to this CSV file.")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(decode_channel()),
+            Command::new("afsk")
+                .alias("a")
+                .about("Sends and receives data with Bell 202 AFSK.")
+                .subcommand_required(true)
+                .subcommands([
+                    Command::new("send")
+                        .alias("s")
+                        .about("Sends data as AFSK tones.")
+                        .arg(
+                            Arg::new("data")
+                                .help("The data to send.")
+                                .required(true)
+                                .index(1),
+                        ),
+                    Command::new("receive")
+                        .alias("r")
+                        .about("Receives AFSK tones and prints the decoded data."),
+                ]),
+            Command::new("dataset")
+                .about("Sends and receives data like a Commodore Datasette.")
+                .subcommand_required(true)
+                .subcommands([
+                    Command::new("send")
+                        .alias("s")
+                        .about("Sends data as one cycle of a tone per bit.")
+                        .arg(
+                            Arg::new("data")
+                                .help("The data to send.")
+                                .required(true)
+                                .index(1),
+                        )
+                        .arg(data_format()),
+                    Command::new("receive")
+                        .alias("r")
+                        .about("Receives dataset tones and prints the decoded data.")
+                        .arg(data_format()),
+                ]),
+            Command::new("ctcss")
+                .about("Sends and detects CTCSS sub-audible squelch tones.")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("send")
+                        .about("Sends a continuous tone, mixed under any passed through audio.")
+                        .arg(
+                            Arg::new("tone")
+                                .help("The tone to send, as a frequency or a code from 1 to 38.")
+                                .value_parser(ctcss::parse_tone)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("amplitude")
+                                .short('a')
+                                .long("amplitude")
+                                .help("The peak value of the tone, from 0 to 1.")
+                                .value_parser(unit_interval)
+                                .default_value("0.15"),
+                        )
+                        .arg(
+                            Arg::new("passthrough")
+                                .short('p')
+                                .help("Pass the input audio through, with the tone mixed under it.")
+                                .num_args(0),
+                        )
+                        .arg(passthrough_resample_quality())
+                        .arg(passthrough_channel_map()),
+                )
+                .subcommand(
+                    Command::new("receive")
+                        .about("Prints the tones heard, and can pass audio through as a squelch.")
+                        .arg(
+                            Arg::new("tone")
+                                .help("The tone that opens the squelch, any tone does if not set.")
+                                .value_parser(ctcss::parse_tone),
+                        )
+                        .arg(
+                            Arg::new("threshold")
+                                .short('t')
+                                .long("threshold")
+                                .help("The magnitude a tone has to reach to be detected.")
+                                .value_parser(value_parser!(f32))
+                                .default_value("0.02"),
+                        )
+                        .arg(
+                            Arg::new("passthrough")
+                                .short('p')
+                                .help("Pass the audio through while the squelch is open.")
+                                .num_args(0),
+                        )
+                        .arg(passthrough_resample_quality())
+                        .arg(passthrough_channel_map()),
+                ),
             Command::new("dtmf")
                 .alias("d")
                 .subcommand_required(true)
@@ -72,10 +318,33 @@ pub fn parse_args() -> ArgMatches {
                                 .help("The data to send.")
                                 .required(true)
                                 .index(1),
-                        ),
+                        )
+                        .arg(
+                            Arg::new("repeat")
+                                .long("repeat")
+                                .help("The number of times to send the data before exiting.")
+                                .value_parser(value_parser!(u32).range(1..))
+                                .default_value("1"),
+                        )
+                        .arg(dtmf_freq_table())
+                        .arg(dtmf_crc())
+                        .arg(data_format()),
                     Command::new("receive")
                         .alias("r")
-                        .about("Receives DTMF tones from the radio."),
+                        .about("Receives DTMF tones from the radio.")
+                        .arg(
+                            Arg::new("min-confidence")
+                                .long("min-confidence")
+                                .help("Ignore codes detected with a confidence (0-1) below this value.")
+                                .value_parser(value_parser!(f32))
+                                .default_value("0.0"),
+                        )
+                        .arg(dtmf_freq_table())
+                        .arg(dtmf_crc())
+                        .arg(data_format())
+                        .arg(receive_window())
+                        .arg(decode_channel())
+                        .arg(notch()),
                 ]),
             Command::new("spectrum")
                 .alias("s")
@@ -84,33 +353,25 @@ pub fn parse_args() -> ArgMatches {
                     Arg::new("fft-size")
                         .short('f')
                         .help("The sample size of the FFT. Should be a power of 2.")
-                        .value_parser(value_parser!(usize))
+                        .value_parser(|x: &str| {
+                            let size = x.parse::<usize>()?;
+                            anyhow::ensure!(size > 0, "Must be greater than zero");
+                            Ok::<usize, anyhow::Error>(size)
+                        })
                         .default_value("2048"),
                 )
                 .arg(
                     Arg::new("display-range")
                         .short('d')
                         .help("The range of frequencies to display. In the format of `low..high`.")
-                        .value_parser(|x: &str| {
-                            let mut x = x.split("..");
-                            let start = x.next().unwrap().parse::<usize>()?;
-                            let end = x.next().unwrap().parse::<usize>()?;
-                            Ok::<Range<usize>, ParseIntError>(start..end)
-                        })
+                        .value_parser(spectrum_analyzer::parse_display_range)
                         .default_value("15..14000"),
                 )
                 .arg(
                     Arg::new("window")
                         .short('w')
-                        .help("The window function to use on the samples")
-                        .value_parser(|x: &str| {
-                            let window = windows::get_window(x).with_context(|| {
-                                format!("Must be: {}", windows::WINDOWS.join(", "))
-                            })?;
-                            Ok::<Arc<Box<dyn Window + Send + Sync + 'static>>, anyhow::Error>(
-                                Arc::new(window),
-                            )
-                        })
+                        .help("The window function to use, see the `windows` command.")
+                        .value_parser(|x: &str| windows::parse(x).map(Arc::new))
                         .default_value("hann"),
                 )
                 .arg(
@@ -119,6 +380,8 @@ pub fn parse_args() -> ArgMatches {
                         .help("Pass the audio through to the output device.")
                         .num_args(0),
                 )
+                .arg(passthrough_resample_quality())
+                .arg(passthrough_channel_map())
                 .arg(
                     Arg::new("gain")
                         .short('g')
@@ -126,11 +389,335 @@ pub fn parse_args() -> ArgMatches {
                         .value_parser(value_parser!(f32))
                         .default_value("1.0"),
                 )
+                .arg(
+                    Arg::new("average")
+                        .short('a')
+                        .long("average")
+                        .help("The number of FFT frames to exponentially average together.")
+                        .value_parser(value_parser!(u32).range(1..))
+                        .default_value("1"),
+                )
+                .arg(
+                    Arg::new("db")
+                        .long("db")
+                        .help("Use a decibel scale for the magnitudes.")
+                        .num_args(0),
+                )
+                .arg(
+                    Arg::new("db-floor")
+                        .long("db-floor")
+                        .help("The lowest decibel value shown when using the decibel scale.")
+                        .allow_negative_numbers(true)
+                        .value_parser(|x: &str| {
+                            let floor = x.parse::<f32>()?;
+                            anyhow::ensure!(floor < 0.0, "Must be negative");
+                            Ok::<f32, anyhow::Error>(floor)
+                        })
+                        .default_value("-90"),
+                )
+                .arg(
+                    Arg::new("floor")
+                        .long("floor")
+                        .help("The magnitude shown as the lowest color, in dB with `--db`.")
+                        .allow_negative_numbers(true)
+                        .value_parser(value_parser!(f32))
+                        .conflicts_with("db-floor"),
+                )
+                .arg(
+                    Arg::new("ceiling")
+                        .long("ceiling")
+                        .help("The magnitude shown as the highest color, in dB with `--db`.")
+                        .allow_negative_numbers(true)
+                        .value_parser(value_parser!(f32)),
+                )
+                .arg(
+                    Arg::new("color-scheme")
+                        .short('c')
+                        .long("color-scheme")
+                        .help("The color gradient to draw the spectrum with.")
+                        .value_parser(value_parser!(spectrum_analyzer::ColorScheme))
+                        .default_value("magma"),
+                )
+                .arg(
+                    Arg::new("channel")
+                        .long("channel")
+                        .help("Only analyze this input channel, starting at 0, instead of the mix.")
+                        .value_parser(value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("split")
+                        .long("split")
+                        .help("Analyze every input channel, drawing them side by side.")
+                        .conflicts_with("channel")
+                        .num_args(0),
+                )
+                .arg(notch())
+                .arg(
+                    Arg::new("label-interval")
+                        .long("label-interval")
+                        .help("The spacing of the console frequency labels, like `20` or `500hz`.")
+                        .value_parser(spectrum_analyzer::parse_label_interval),
+                )
+                .arg(
+                    Arg::new("data-out")
+                        .long("data-out")
+                        .help("Append the magnitudes of every frame to a CSV file.")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("history")
+                        .long("history")
+                        .help("The number of waterfall rows kept to scroll back through or save.")
+                        .value_parser(value_parser!(u32).range(1..))
+                        .default_value("2000"),
+                )
                 .arg(
                     Arg::new("display-type")
                         .short('t')
                         .help("The method to use to display the spectrum.")
                         .value_parser(value_parser!(spectrum_analyzer::DisplayType)),
+                )
+                .arg(
+                    Arg::new("interval")
+                        .long("interval")
+                        .help("How often the `file` display type writes a PNG, in seconds.")
+                        .value_parser(value_parser!(u64).range(1..))
+                        .default_value("60"),
+                )
+                .arg(
+                    Arg::new("config")
+                        .long("config")
+                        .help("Load settings from a TOML file, any flags given override it.")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("save-config")
+                        .long("save-config")
+                        .help("Save the settings in use to a TOML file.")
+                        .value_parser(value_parser!(PathBuf)),
+                ),
+            Command::new("freq")
+                .about("Prints the dominant frequency of the input a few times per second.")
+                .arg(
+                    Arg::new("rate")
+                        .short('r')
+                        .long("rate")
+                        .help("Measurements per second. Lower rates are more precise.")
+                        .value_parser(|x: &str| {
+                            let rate = x.parse::<f32>()?;
+                            anyhow::ensure!(rate > 0.0, "Must be positive");
+                            Ok::<f32, anyhow::Error>(rate)
+                        })
+                        .default_value("4"),
+                )
+                .arg(
+                    Arg::new("threshold")
+                        .short('t')
+                        .long("threshold")
+                        .help("The amplitude below which no signal is reported.")
+                        .value_parser(value_parser!(f32))
+                        .default_value("0.001"),
+                ),
+            Command::new("tune")
+                .about("Shows the strength of a single frequency, for aligning a transmitter.")
+                .arg(
+                    Arg::new("freq")
+                        .help("The frequency to measure, in Hz.")
+                        .value_parser(positive_freq)
+                        .required_unless_present("freq-option")
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("freq-option")
+                        .short('f')
+                        .long("freq")
+                        .help("The frequency to measure, in Hz, instead of the positional one.")
+                        .value_parser(positive_freq)
+                        .conflicts_with("freq"),
+                )
+                .arg(
+                    Arg::new("bandwidth")
+                        .short('b')
+                        .long("bandwidth")
+                        .help("The width of the measured band in Hz. Narrower is slower to react.")
+                        .value_parser(positive_freq)
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::new("sonify")
+                        .short('s')
+                        .long("sonify")
+                        .help("Play a tone that rises in pitch as the signal gets stronger.")
+                        .num_args(0),
+                ),
+            Command::new("tones")
+                .about("Tools for working with arbitrary sets of tones.")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("detect")
+                        .about("Shows the live magnitude of each tone as a labeled bar.")
+                        .arg(
+                            Arg::new("freqs")
+                                .long("freqs")
+                                .help("The tones to watch, like `88.5,100:0.05` with a threshold.")
+                                .value_parser(tones_detect::parse_tones)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("threshold")
+                                .short('t')
+                                .long("threshold")
+                                .help("The magnitude a tone counts as present at by default.")
+                                .value_parser(value_parser!(f32))
+                                .default_value("0.01"),
+                        )
+                        .arg(
+                            Arg::new("bandwidth")
+                                .short('b')
+                                .long("bandwidth")
+                                .help("The width of the band measured for each tone in Hz.")
+                                .value_parser(positive_freq)
+                                .default_value("5"),
+                        ),
+                ),
+            Command::new("meter")
+                .about("Shows the input level in the terminal, for setting the input gain.")
+                .arg(
+                    Arg::new("peak-hold-ms")
+                        .long("peak-hold-ms")
+                        .help("How long the peak marker is held for, in milliseconds.")
+                        .value_parser(value_parser!(u64))
+                        .default_value("1500"),
+                )
+                .arg(
+                    Arg::new("passthrough")
+                        .short('p')
+                        .help("Pass the audio through to the output device, and meter it too.")
+                        .num_args(0),
+                )
+                .arg(passthrough_resample_quality())
+                .arg(passthrough_channel_map()),
+            Command::new("scope")
+                .about("Shows the input waveform in the terminal, like an oscilloscope.")
+                .arg(
+                    Arg::new("level")
+                        .short('l')
+                        .long("level")
+                        .help("The sample value the signal has to cross to trigger a frame.")
+                        .allow_negative_numbers(true)
+                        .value_parser(value_parser!(f32))
+                        .default_value("0.0"),
+                )
+                .arg(
+                    Arg::new("edge")
+                        .short('e')
+                        .long("edge")
+                        .help("The direction the signal has to cross the trigger level in.")
+                        .value_parser(value_parser!(scope::Edge))
+                        .default_value("rising"),
+                )
+                .arg(
+                    Arg::new("window")
+                        .short('w')
+                        .long("window")
+                        .help("The length of time shown on the screen, in milliseconds.")
+                        .value_parser(|x: &str| {
+                            let window = x.parse::<f32>()?;
+                            anyhow::ensure!(window > 0.0, "Must be positive");
+                            Ok::<f32, anyhow::Error>(window)
+                        })
+                        .default_value("20"),
+                )
+                .arg(
+                    Arg::new("gain")
+                        .short('g')
+                        .help("The gain to apply to the waveform when displaying.")
+                        .value_parser(value_parser!(f32))
+                        .default_value("1.0"),
+                ),
+            Command::new("sweep")
+                .about("Plays a tone that sweeps between two frequencies, then exits.")
+                .arg(
+                    Arg::new("start")
+                        .short('s')
+                        .long("start")
+                        .help("The frequency to start the sweep at.")
+                        .value_parser(value_parser!(f32))
+                        .default_value("100"),
+                )
+                .arg(
+                    Arg::new("end")
+                        .short('e')
+                        .long("end")
+                        .help("The frequency to end the sweep at.")
+                        .value_parser(value_parser!(f32))
+                        .default_value("10000"),
+                )
+                .arg(
+                    Arg::new("duration")
+                        .short('d')
+                        .long("duration")
+                        .help("The length of the sweep in seconds.")
+                        .value_parser(value_parser!(f32))
+                        .default_value("10"),
+                ),
+            Command::new("sequence")
+                .about("Plays the tones in a sequence file, then exits.")
+                .arg(
+                    Arg::new("file")
+                        .help("The sequence file, with a `freq;seconds[;amplitude]` tone per line.")
+                        .value_parser(value_parser!(PathBuf))
+                        .required(true)
+                        .index(1),
+                ),
+            Command::new("tone")
+                .about("Writes a test tone to a WAV file, without using any audio devices.")
+                .arg(
+                    Arg::new("freq")
+                        .short('f')
+                        .long("freq")
+                        .help("The frequency of the tone in Hz.")
+                        .value_parser(value_parser!(f32))
+                        .default_value("1000"),
+                )
+                .arg(
+                    Arg::new("duration")
+                        .short('d')
+                        .long("duration")
+                        .help("The length of the tone in seconds.")
+                        .value_parser(value_parser!(f32))
+                        .default_value("5"),
+                )
+                .arg(
+                    Arg::new("amplitude")
+                        .short('a')
+                        .long("amplitude")
+                        .help("The peak value of the tone, from 0 to 1.")
+                        .value_parser(unit_interval)
+                        .default_value("1.0"),
+                )
+                .arg(
+                    Arg::new("waveform")
+                        .short('w')
+                        .long("waveform")
+                        .help("The shape of the wave.")
+                        .value_parser(value_parser!(Waveform))
+                        .default_value("sine"),
+                )
+                .arg(
+                    Arg::new("sample-rate")
+                        .short('r')
+                        .long("sample-rate")
+                        .help("The sample rate of the WAV file.")
+                        .value_parser(value_parser!(u32))
+                        .default_value("48000"),
+                )
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .help("The WAV file to write the tone to.")
+                        .value_parser(value_parser!(PathBuf))
+                        .required(true),
                 ),
             Command::new("true-random")
                 .alias("trng")
@@ -163,6 +750,41 @@ pub fn parse_args() -> ArgMatches {
                         .help("The size of the buffer to use.")
                         .value_parser(value_parser!(usize))
                         .default_value("1024"),
+                )
+                .arg(
+                    Arg::new("debias")
+                        .long("debias")
+                        .help("The algorithm used to remove bias from the random bits.")
+                        .value_parser(value_parser!(Debias))
+                        .default_value("von-neumann"),
+                )
+                .arg(
+                    Arg::new("save")
+                        .long("save")
+                        .help("Save the buffer to this file when stopped with Ctrl-C.")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("load")
+                        .long("load")
+                        .help("Load the buffer from this file on startup.")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("token").long("token").help(
+                        "Require this secret in the `Authorization` header of data requests.",
+                    ),
+                )
+                .arg(
+                    Arg::new("rate-limit")
+                        .long("rate-limit")
+                        .help("The most bytes per second each client IP can take from the buffer.")
+                        .value_parser(|x: &str| {
+                            // Less than a byte per second would never let a client take anything
+                            let rate = x.parse::<f32>()?;
+                            anyhow::ensure!(rate >= 1.0, "Must be at least 1");
+                            Ok::<f32, anyhow::Error>(rate)
+                        }),
                 ),
             Command::new("morse-code")
                 .alias("morse")
@@ -176,6 +798,14 @@ pub fn parse_args() -> ArgMatches {
                         .value_parser(value_parser!(u64))
                         .default_value("100"),
                 )
+                .arg(
+                    Arg::new("wpm")
+                        .short('w')
+                        .long("wpm")
+                        .help("The speed in words per minute. Used instead of the dit length.")
+                        .value_parser(value_parser!(u64).range(1..))
+                        .conflicts_with("dit"),
+                )
                 .arg(
                     Arg::new("frequency")
                         .short('f')
@@ -183,27 +813,198 @@ pub fn parse_args() -> ArgMatches {
                         .value_parser(value_parser!(f32))
                         .default_value("1000"),
                 )
+                .subcommands([
+                    Command::new("send")
+                        .alias("s")
+                        .arg(
+                            Arg::new("text")
+                                .help("Text to send, or `-` for stdin. Prosigns look like `[SK]`.")
+                                .required_unless_present("file")
+                                .index(1),
+                        )
+                        .arg(
+                            Arg::new("file")
+                                .long("file")
+                                .help("Read the text to transmit from a file.")
+                                .value_parser(value_parser!(PathBuf))
+                                .conflicts_with("text"),
+                        ),
+                    Command::new("receive")
+                        .alias("r")
+                        .arg(
+                            Arg::new("threshold-factor")
+                                .long("threshold-factor")
+                                .help(
+                                    "How many times louder than the noise floor a tone has to be.",
+                                )
+                                .value_parser(|x: &str| {
+                                    let factor = x.parse::<f32>()?;
+                                    anyhow::ensure!(factor > 1.0, "Must be greater than one");
+                                    Ok::<f32, anyhow::Error>(factor)
+                                })
+                                .default_value("6"),
+                        )
+                        .arg(
+                            Arg::new("auto-speed")
+                                .long("auto-speed")
+                                .help("Adapt the dit length to the speed of the received morse.")
+                                .num_args(0),
+                        )
+                        .arg(receive_window())
+                        .arg(decode_channel())
+                        .arg(notch()),
+                    Command::new("key")
+                        .alias("k")
+                        .about("Turns the keyboard into a straight key, decoding what is sent.")
+                        .arg(
+                            Arg::new("key")
+                                .short('k')
+                                .long("key")
+                                .help("The key to use, either a single character or `space`.")
+                                .value_parser(|x: &str| {
+                                    let mut chars = x.chars();
+                                    match (x, chars.next(), chars.next()) {
+                                        ("space", ..) => Ok(' '),
+                                        (_, Some(c), None) => Ok(c.to_ascii_lowercase()),
+                                        _ => anyhow::bail!("Must be a single character or `space`"),
+                                    }
+                                })
+                                .default_value("space"),
+                        ),
+                ]),
+            Command::new("rtty")
+                .about("Transmits text using RTTY (Baudot FSK)")
+                .subcommand_required(true)
+                .arg(
+                    Arg::new("mark")
+                        .short('m')
+                        .long("mark")
+                        .help("The frequency of the mark tone in Hz.")
+                        .value_parser(value_parser!(f32))
+                        .default_value("2125"),
+                )
+                .arg(
+                    Arg::new("shift")
+                        .short('s')
+                        .long("shift")
+                        .help("How far above the mark tone the space tone is in Hz.")
+                        .value_parser(value_parser!(f32))
+                        .default_value("170"),
+                )
+                .arg(
+                    Arg::new("baud")
+                        .short('b')
+                        .long("baud")
+                        .help("The number of bits sent per second.")
+                        .value_parser(value_parser!(f32))
+                        .default_value("45.45"),
+                )
                 .subcommands([
                     Command::new("send").alias("s").arg(
                         Arg::new("text")
-                            .help("The text to transmit")
+                            .help("The text to transmit.")
                             .required(true)
                             .index(1),
                     ),
                     Command::new("receive").alias("r"),
                 ]),
         ])
-        .get_matches()
+}
+
+/// The `--resample-quality` argument shared by the modules with a passthrough
+fn passthrough_resample_quality() -> Arg {
+    Arg::new("resample-quality")
+        .long("resample-quality")
+        .help("The quality of the resampler used for the passthrough.")
+        .value_parser(value_parser!(ResampleQuality))
+        .default_value("medium")
+}
+
+/// The `--channel-map` argument shared by the modules with a passthrough
+fn passthrough_channel_map() -> Arg {
+    Arg::new("channel-map")
+        .long("channel-map")
+        .help("Routes input to output channels in the passthrough, like `0:0,0:1`.")
+        .value_parser(ChannelMap::parse)
+        .requires("passthrough")
+}
+
+/// The `--notch` argument shared by the modules that analyze or decode the input
+fn notch() -> Arg {
+    Arg::new("notch")
+        .long("notch")
+        .help("Remove a frequency, like mains hum, from the input. Can be given more than once.")
+        .value_parser(positive_freq)
+        .action(ArgAction::Append)
+}
+
+/// The `--channel` argument shared by the modules that decode tones from the input
+fn decode_channel() -> Arg {
+    Arg::new("channel")
+        .long("channel")
+        .help("The input channel to decode, from 0, `mono` to mix them or `max` for the loudest.")
+        .value_parser(ChannelSelect::parse)
+        .default_value("0")
+}
+
+/// The `--crc` argument shared by the DTMF subcommands
+fn dtmf_crc() -> Arg {
+    Arg::new("crc")
+        .long("crc")
+        .help("Append a CRC-16 checksum to the data when sending, and verify it when receiving.")
+        .num_args(0)
+}
+
+/// The `--freq-table` argument shared by the DTMF subcommands
+fn dtmf_freq_table() -> Arg {
+    Arg::new("freq-table")
+        .long("freq-table")
+        .help("Custom DTMF tones, as a comma-separated list of the four row frequencies followed by the four column frequencies.")
+        .value_parser(DtmfConfig::parse)
+}
+
+/// The `--format` argument shared by the binary data subcommands
+fn data_format() -> Arg {
+    Arg::new("format")
+        .long("format")
+        .help("How the data is written, as text or binary in hex or base64.")
+        .value_parser(value_parser!(DataFormat))
+        .default_value("utf8")
+}
+
+/// The `--window` argument shared by the tone decoding subcommands
+fn receive_window() -> Arg {
+    Arg::new("window")
+        .long("window")
+        .help("The window function applied to each chunk before looking for tones.")
+        .value_parser(|x: &str| windows::parse(x).map(Arc::new))
+        .default_value("hann")
+}
+
+/// Parses a frequency in Hz, which has to be positive
+fn positive_freq(x: &str) -> anyhow::Result<f32> {
+    let freq = x.parse::<f32>()?;
+    anyhow::ensure!(freq > 0.0, "Must be positive");
+    Ok(freq)
+}
+
+/// Parses a value from 0 to 1, like an amplitude or level
+fn unit_interval(x: &str) -> anyhow::Result<f32> {
+    let value = x.parse::<f32>()?;
+    anyhow::ensure!((0.0..=1.0).contains(&value), "Must be from 0 to 1");
+    Ok(value)
 }
 
 /// Uses the args to pick the correct module and return it as a boxed trait object
 pub fn get_module(
     args: &ArgMatches,
+    input_name: String,
     input: SupportedStreamConfig,
     output: SupportedStreamConfig,
 ) -> BoxedModule {
     let ic = |x: &ArgMatches| InitContext {
         args: x.to_owned(),
+        input_name,
         input,
         output,
     };
@@ -213,23 +1014,78 @@ pub fn get_module(
             devices();
             process::exit(0);
         }
+        Some(("windows", _)) => {
+            list_windows();
+            process::exit(0);
+        }
         Some(("range", m)) => Box::new(range_test::RangeTest::new(ic(m))),
+        Some(("afsk", m)) => match m.subcommand() {
+            Some(("send", _)) => Box::new(afsk_send::AfskSend::new(ic(m))),
+            Some(("receive", _)) => Box::new(afsk_receive::AfskReceive::new(ic(m))),
+            _ => panic!("Invalid Subcommand"),
+        },
+        Some(("dataset", m)) => match m.subcommand() {
+            Some(("send", _)) => Box::new(dataset_send::DatasetSend::new(ic(m))),
+            Some(("receive", _)) => Box::new(dataset_receive::DatasetReceive::new(ic(m))),
+            _ => panic!("Invalid Subcommand"),
+        },
+        Some(("ctcss", m)) => match m.subcommand() {
+            Some(("send", _)) => Box::new(ctcss_send::CtcssSend::new(ic(m))),
+            Some(("receive", _)) => Box::new(ctcss_receive::CtcssReceive::new(ic(m))),
+            _ => panic!("Invalid Subcommand"),
+        },
         Some(("dtmf", m)) => match m.subcommand() {
             Some(("send", _)) => Box::new(dtmf_send::DtmfSend::new(ic(m))),
             Some(("receive", _)) => Box::new(dtmf_receive::DtmfReceive::new(ic(m))),
             _ => panic!("Invalid Subcommand"),
         },
         Some(("spectrum", m)) => Box::new(spectrum_analyzer::SpectrumAnalyzer::new(ic(m))),
+        Some(("freq", m)) => Box::new(frequency_counter::FrequencyCounter::new(ic(m))),
+        Some(("tune", m)) => Box::new(tune::Tune::new(ic(m))),
+        Some(("tones", m)) => match m.subcommand() {
+            Some(("detect", _)) => Box::new(tones_detect::TonesDetect::new(ic(m))),
+            _ => panic!("Invalid Subcommand"),
+        },
+        Some(("meter", m)) => Box::new(meter::Meter::new(ic(m))),
+        Some(("scope", m)) => Box::new(scope::Scope::new(ic(m))),
+        Some(("sweep", m)) => Box::new(sweep::Sweep::new(ic(m))),
+        Some(("sequence", m)) => Box::new(sequence::SequencePlayer::new(ic(m))),
         Some(("true-random", m)) => Box::new(true_random::TrueRandom::new(ic(m))),
         Some(("morse-code", m)) => match m.subcommand() {
             Some(("send", _)) => Box::new(morse_send::MorseSend::new(ic(m))),
             Some(("receive", _)) => Box::new(morse_receive::MorseReceive::new(ic(m))),
+            Some(("key", _)) => Box::new(morse_key::MorseKey::new(ic(m))),
+            _ => panic!("Invalid Subcommand"),
+        },
+        Some(("rtty", m)) => match m.subcommand() {
+            Some(("send", _)) => Box::new(rtty_send::RttySend::new(ic(m))),
+            Some(("receive", _)) => Box::new(rtty_receive::RttyReceive::new(ic(m))),
             _ => panic!("Invalid Subcommand"),
         },
         _ => panic!("Invalid Subcommand"),
     }
 }
 
+/// Prints out the window functions with a short description of each.
+fn list_windows() {
+    println!("[*] Windows ({})", windows::WINDOWS.len());
+    for (i, info) in windows::WINDOWS.iter().enumerate() {
+        let last = i + 1 == windows::WINDOWS.len();
+        let gain = info.build(None).unwrap().coherent_gain(1024);
+        println!(
+            " {}─ {} ({})",
+            if last { "└" } else { "├" },
+            info.usage(),
+            info.aliases.join(", ")
+        );
+        println!(
+            " {}  └─ {} Coherent gain {gain:.2}.",
+            if last { " " } else { "│" },
+            info.description
+        );
+    }
+}
+
 /// Prints out the audio host system and the available devices.
 fn devices() {
     let host = cpal::default_host();
@@ -245,12 +1101,35 @@ fn devices() {
     for (i, device) in devices.iter().enumerate() {
         let input = device.default_input_config().is_ok();
         let output = device.default_output_config().is_ok();
+        let last = i + 1 == devices.len();
         println!(
-            " {}─ {}{} {}",
-            if i + 1 == devices.len() { "└" } else { "├" },
+            " {}─ [{i}] {}{} {}",
+            if last { "└" } else { "├" },
             if input { "I" } else { "" },
             if output { "O" } else { "" },
             device.name().unwrap()
         );
+
+        // List the supported configs of the device, tagged with their direction
+        let mut configs = Vec::new();
+        if let Ok(i) = device.supported_input_configs() {
+            configs.extend(i.map(|x| ('I', x)));
+        }
+        if let Ok(i) = device.supported_output_configs() {
+            configs.extend(i.map(|x| ('O', x)));
+        }
+
+        for (j, (direction, config)) in configs.iter().enumerate() {
+            println!(
+                " {}  {}─ {direction} {}..{}Hz, {} channel{}, {:?}",
+                if last { " " } else { "│" },
+                if j + 1 == configs.len() { "└" } else { "├" },
+                config.min_sample_rate().0,
+                config.max_sample_rate().0,
+                config.channels(),
+                if config.channels() == 1 { "" } else { "s" },
+                config.sample_format()
+            );
+        }
     }
 }
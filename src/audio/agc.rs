@@ -0,0 +1,144 @@
+//! Automatic gain control, for keeping the input near a set level no matter how loud the source is.
+
+/// The default time for the gain to come down when the input gets louder, in seconds.
+const ATTACK: f32 = 0.01;
+/// The default time for the gain to go back up when the input gets quieter, in seconds.
+const RELEASE: f32 = 0.5;
+
+/// Automatic gain control.
+/// The peak level of the input is followed with an envelope that jumps up to new peaks and falls back over the release time.
+/// The gain is set to bring that envelope to the target, getting there over the attack time when it has to come down.
+/// All the interleaved channels share one gain, so their balance isn't changed.
+pub struct Agc {
+    // == Config ==
+    /// The peak level to keep the signal at.
+    target: f32,
+    min_gain: f32,
+    max_gain: f32,
+    /// How much of the gain is kept each frame when it is coming down.
+    attack: f32,
+    /// How much of the envelope is kept each frame when the level is falling.
+    release: f32,
+    channels: usize,
+    sample_rate: f32,
+
+    // == Internal ==
+    envelope: f32,
+    gain: f32,
+}
+
+impl Agc {
+    /// Creates a new AGC for `channels` interleaved channels, that keeps the peak level near `target`.
+    pub fn new(channels: usize, sample_rate: u32, target: f32) -> Self {
+        Self {
+            target,
+            min_gain: 0.0,
+            max_gain: f32::INFINITY,
+            attack: 0.0,
+            release: 0.0,
+            channels,
+            sample_rate: sample_rate as f32,
+
+            envelope: target,
+            gain: 1.0,
+        }
+        .attack(ATTACK)
+        .release(RELEASE)
+    }
+
+    /// Sets the lowest and highest gain that can be applied.
+    /// Panics if `min` is above `max`.
+    pub fn gain_range(mut self, min: f32, max: f32) -> Self {
+        assert!(min <= max, "The minimum gain must not be above the maximum");
+        self.min_gain = min;
+        self.max_gain = max;
+        self
+    }
+
+    /// Sets how quickly the gain comes down when the input gets louder, in seconds.
+    pub fn attack(mut self, attack: f32) -> Self {
+        self.attack = time_constant(attack, self.sample_rate);
+        self
+    }
+
+    /// Sets how quickly the gain goes back up when the input gets quieter, in seconds.
+    pub fn release(mut self, release: f32) -> Self {
+        self.release = time_constant(release, self.sample_rate);
+        self
+    }
+
+    /// Gets the gain currently being applied.
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Applies the gain to a slice of interleaved samples in place.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for frame in samples.chunks_mut(self.channels) {
+            let level = frame.iter().fold(0.0, |max, x| x.abs().max(max));
+            self.envelope = match level > self.envelope {
+                true => level,
+                false => self.envelope * self.release + level * (1.0 - self.release),
+            };
+
+            let wanted = (self.target / self.envelope).clamp(self.min_gain, self.max_gain);
+            self.gain = match wanted < self.gain {
+                true => self.gain * self.attack + wanted * (1.0 - self.attack),
+                false => wanted,
+            };
+            frame.iter_mut().for_each(|x| *x *= self.gain);
+        }
+    }
+}
+
+/// Gets how much of a one-pole smoother is kept each sample, for it to settle in `time` seconds.
+fn time_constant(time: f32, sample_rate: f32) -> f32 {
+    match time > 0.0 {
+        true => (-1.0 / (time * sample_rate)).exp(),
+        false => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::f32::consts::TAU;
+
+    use super::Agc;
+
+    /// Runs three seconds of a 1kHz sine through the AGC in chunks, returning the peak of the last 100ms.
+    fn settle(agc: &mut Agc, amplitude: f32) -> f32 {
+        let mut samples = (0..48000 * 3)
+            .map(|i| (TAU * 1000.0 * i as f32 / 48000.0).sin() * amplitude)
+            .collect::<Vec<_>>();
+        for chunk in samples.chunks_mut(512) {
+            agc.process(chunk);
+        }
+
+        samples[48000 * 3 - 4800..]
+            .iter()
+            .fold(0.0, |max, x| x.abs().max(max))
+    }
+
+    #[test]
+    fn test_agc() {
+        let mut agc = Agc::new(1, 48000, 0.5);
+        let peak = settle(&mut agc, 0.05);
+        assert!((peak - 0.5).abs() < 0.05, "{peak} != 0.5");
+
+        // Gets louder, then the gain has to come back down
+        let peak = settle(&mut agc, 0.9);
+        assert!((peak - 0.5).abs() < 0.05, "{peak} != 0.5");
+    }
+
+    #[test]
+    fn test_gain_range() {
+        let mut agc = Agc::new(1, 48000, 0.5).gain_range(0.5, 10.0);
+        let peak = settle(&mut agc, 0.001);
+        assert!((peak - 0.01).abs() < 0.001, "{peak} != 0.01");
+        assert_eq!(agc.gain(), 10.0);
+
+        let peak = settle(&mut agc, 4.0);
+        assert!((peak - 2.0).abs() < 0.01, "{peak} != 2.0");
+        assert!((agc.gain() - 0.5).abs() < 1e-3, "{} != 0.5", agc.gain());
+    }
+}
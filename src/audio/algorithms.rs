@@ -4,10 +4,33 @@ use std::f32::consts::PI;
 
 use num_complex::Complex;
 
+/// The level quieter amplitudes are clamped to when converting them to decibels, see [`to_db`].
+pub const DB_MIN: f32 = -80.0;
+
 /// Implements the [Goertzel algorithm](https://en.wikipedia.org/wiki/Goertzel_algorithm) to find the magnitude of a frequency in a slice of samples.
+/// The magnitude is normalized by half the number of samples, so a full-scale sine wave at `freq` gives about 1.0 no matter how many samples there are.
+/// The frequency is rounded to the nearest DFT bin of the slice, see [`goertzel_mag_exact`] to measure a frequency between bins.
 pub fn goertzel_mag(freq: f32, samples: &[f32], sample_rate: u32) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+
     let k = (0.5 + (samples.len() as f32 * freq) / sample_rate as f32).floor();
-    let omega = (2.0 * PI * k) / samples.len() as f32;
+    goertzel(2.0 * PI * k / samples.len() as f32, samples)
+}
+
+/// Finds the magnitude of a frequency like [`goertzel_mag`], but without rounding it to a DFT bin.
+/// Frequencies that fall between bins don't lose strength, which matters for short slices where the bins are wide.
+pub fn goertzel_mag_exact(freq: f32, samples: &[f32], sample_rate: u32) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+
+    goertzel(2.0 * PI * freq / sample_rate as f32, samples)
+}
+
+/// Runs the Goertzel filter at `omega` radians per sample, returning the normalized magnitude.
+fn goertzel(omega: f32, samples: &[f32]) -> f32 {
     let sin = omega.sin();
     let cos = omega.cos();
     let coeff = cos * 2.0;
@@ -24,23 +47,428 @@ pub fn goertzel_mag(freq: f32, samples: &[f32], sample_rate: u32) -> f32 {
     let real = s1 - s2 * cos;
     let imag = s2 * sin;
 
-    Complex::new(real, imag).norm()
+    Complex::new(real, imag).norm() / (samples.len() as f32 / 2.0)
+}
+
+/// Converts an amplitude to decibels relative to full scale.
+/// Silence is clamped to `floor` instead of negative infinity, which is usually [`DB_MIN`].
+pub fn to_db(amplitude: f32, floor: f32) -> f32 {
+    (20.0 * amplitude.log10()).max(floor)
+}
+
+/// Finds the magnitudes of multiple frequencies in a slice of samples, with the same normalization as [`goertzel_mag`].
+/// All the frequencies are computed in a single pass over the samples, which is faster than calling [`goertzel_mag`] for each one.
+pub fn goertzel_mag_multi(freqs: &[f32], samples: &[f32], sample_rate: u32) -> Vec<f32> {
+    if samples.is_empty() {
+        return vec![0.0; freqs.len()];
+    }
+
+    let len = samples.len() as f32;
+    let omegas = freqs
+        .iter()
+        .map(|freq| {
+            let k = (0.5 + (len * freq) / sample_rate as f32).floor();
+            (2.0 * PI * k) / len
+        })
+        .collect::<Vec<_>>();
+    let coeffs = omegas.iter().map(|x| x.cos() * 2.0).collect::<Vec<_>>();
+
+    let mut s1 = vec![0.0; freqs.len()];
+    let mut s2 = vec![0.0; freqs.len()];
+
+    for i in samples {
+        for j in 0..freqs.len() {
+            let s = coeffs[j] * s1[j] - s2[j] + i;
+            s2[j] = s1[j];
+            s1[j] = s;
+        }
+    }
+
+    omegas
+        .iter()
+        .enumerate()
+        .map(|(j, omega)| {
+            let real = s1[j] - s2[j] * omega.cos();
+            let imag = s2[j] * omega.sin();
+            Complex::new(real, imag).norm() / (len / 2.0)
+        })
+        .collect()
 }
 
 /// Converts a slice of samples, made of n interleaved channels, to a mono channel.
-/// This is done by averaging the samples of each channel.
+/// This is done by averaging the samples of each frame.
+/// If the slice doesn't hold a whole number of frames, the incomplete frame at the end is dropped.
 pub fn to_mono(samples: &[f32], channels: usize) -> Vec<f32> {
-    let mut out = Vec::with_capacity(samples.len() / channels);
+    samples
+        .chunks_exact(channels)
+        .map(|x| x.iter().sum::<f32>() / channels as f32)
+        .collect()
+}
+
+/// Extracts a single channel, starting at 0, from a slice of samples, made of n interleaved channels.
+/// Like [`to_mono`], the incomplete frame at the end is dropped if there is one.
+/// Panics if the channel doesn't exist.
+pub fn extract_channel(samples: &[f32], channels: usize, channel: usize) -> Vec<f32> {
+    assert!(
+        channel < channels,
+        "Channel {channel} does not exist, there are {channels} channels"
+    );
+    samples.chunks_exact(channels).map(|x| x[channel]).collect()
+}
+
+/// Which channel of an interleaved input a decoder listens to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelSelect {
+    /// A single channel, starting at 0.
+    Index(usize),
+    /// The average of all the channels.
+    Mono,
+    /// Whichever channel has the most energy, picked again for each slice of samples.
+    Max,
+}
+
+impl ChannelSelect {
+    /// Parses a channel number, `mono` or `max`.
+    pub fn parse(s: &str) -> anyhow::Result<Self> {
+        Ok(match s.trim().to_ascii_lowercase().as_str() {
+            "mono" => Self::Mono,
+            "max" => Self::Max,
+            x => Self::Index(x.parse().map_err(|_| {
+                anyhow::anyhow!("Expected a channel number, `mono` or `max`, got `{x}`")
+            })?),
+        })
+    }
+
+    /// Gets the selected channel from a slice of samples, made of n interleaved channels.
+    pub fn select(&self, samples: &[f32], channels: usize) -> Vec<f32> {
+        match self {
+            Self::Index(channel) => extract_channel(samples, channels, *channel),
+            Self::Mono => to_mono(samples, channels),
+            Self::Max => {
+                let energy = |channel: usize| {
+                    samples
+                        .iter()
+                        .skip(channel)
+                        .step_by(channels)
+                        .map(|x| x * x)
+                        .sum::<f32>()
+                };
+                let channel = (0..channels)
+                    .max_by(|&a, &b| energy(a).total_cmp(&energy(b)))
+                    .unwrap_or(0);
+                extract_channel(samples, channels, channel)
+            }
+        }
+    }
+}
+
+/// Keeps the newest `window` samples of a stream, and hands them out every `hop` new samples.
+/// Used to make overlapping measurements, where each one covers a longer time than the gap between them.
+pub struct SlidingWindow {
+    // == Settings ==
+    window: usize,
+    hop: usize,
+
+    // == Internal ==
+    samples: Vec<f32>,
+    /// The number of samples added since the last measurement.
+    new: usize,
+}
+
+impl SlidingWindow {
+    /// Creates a window of `window` samples, which moves forward `hop` samples between measurements.
+    pub fn new(window: usize, hop: usize) -> Self {
+        Self {
+            window,
+            hop,
+            samples: Vec::with_capacity(window + hop),
+            new: 0,
+        }
+    }
+
+    /// Gets the number of samples each measurement is made over.
+    pub fn size(&self) -> usize {
+        self.window
+    }
+
+    /// Adds some mono samples.
+    /// Returns the newest `window` samples once `hop` samples have been added since the last measurement and the window is full.
+    pub fn push(&mut self, samples: &[f32]) -> Option<&[f32]> {
+        self.samples.extend_from_slice(samples);
+        self.new += samples.len();
+
+        if self.new < self.hop {
+            return None;
+        }
+        self.new = 0;
+
+        // Only keep the samples in the current window
+        let len = self.samples.len();
+        self.samples.drain(..len.saturating_sub(self.window));
+        (self.samples.len() >= self.window).then_some(&self.samples[..])
+    }
+}
+
+/// A single-pole high-pass filter that removes any DC offset from a signal, `y[n] = x[n] - x[n-1] + a * y[n-1]`.
+/// Each of the interleaved channels is filtered on its own, and the state is kept between calls to [`DcBlocker::process`].
+pub struct DcBlocker {
+    /// The pole of the filter, closer to one means a lower cutoff.
+    a: f32,
+    /// The last input sample of each channel.
+    last_in: Vec<f32>,
+    /// The last output sample of each channel.
+    last_out: Vec<f32>,
+}
+
+impl DcBlocker {
+    /// Creates a new filter for `channels` interleaved channels, with a cutoff frequency in Hz.
+    pub fn new(channels: usize, cutoff: f32, sample_rate: u32) -> Self {
+        Self {
+            a: (-2.0 * PI * cutoff / sample_rate as f32).exp(),
+            last_in: vec![0.0; channels],
+            last_out: vec![0.0; channels],
+        }
+    }
 
-    let mut sum = 0.0;
-    for (i, &e) in samples.iter().enumerate() {
-        sum += e;
-        if i != 0 && i % channels == 0 {
-            out.push(sum / channels as f32);
-            sum = 0.0;
+    /// Filters a slice of interleaved samples in place.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        let channels = self.last_in.len();
+        for (i, x) in samples.iter_mut().enumerate() {
+            let channel = i % channels;
+            let out = *x - self.last_in[channel] + self.a * self.last_out[channel];
+            self.last_in[channel] = *x;
+            self.last_out[channel] = out;
+            *x = out;
         }
     }
-    out.push(sum / channels as f32);
+}
+
+/// A biquad IIR notch filter, that removes a narrow band around one frequency, like mains hum.
+/// Each of the interleaved channels is filtered on its own, and the state is kept between calls to [`Notch::process`].
+pub struct Notch {
+    /// The feedforward coefficients, normalized so `a0` is one.
+    b: [f32; 3],
+    /// The feedback coefficients `a1` and `a2`.
+    a: [f32; 2],
+    /// The last two input samples of each channel.
+    inputs: Vec<[f32; 2]>,
+    /// The last two output samples of each channel.
+    outputs: Vec<[f32; 2]>,
+}
+
+impl Notch {
+    /// Creates a new filter for `channels` interleaved channels.
+    /// The quality factor `q` is the frequency over the width of the notch, so higher is narrower.
+    pub fn new(channels: usize, freq: f32, q: f32, sample_rate: u32) -> Self {
+        let omega = 2.0 * PI * freq / sample_rate as f32;
+        let alpha = omega.sin() / (2.0 * q);
+        let cos = omega.cos();
+        let a0 = 1.0 + alpha;
+
+        Self {
+            b: [1.0 / a0, -2.0 * cos / a0, 1.0 / a0],
+            a: [-2.0 * cos / a0, (1.0 - alpha) / a0],
+            inputs: vec![[0.0; 2]; channels],
+            outputs: vec![[0.0; 2]; channels],
+        }
+    }
+
+    /// Filters a slice of interleaved samples in place.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        let channels = self.inputs.len();
+        for (i, x) in samples.iter_mut().enumerate() {
+            let (inputs, outputs) = (
+                &mut self.inputs[i % channels],
+                &mut self.outputs[i % channels],
+            );
+            let out = self.b[0] * *x + self.b[1] * inputs[0] + self.b[2] * inputs[1]
+                - self.a[0] * outputs[0]
+                - self.a[1] * outputs[1];
+
+            *inputs = [*x, inputs[0]];
+            *outputs = [out, outputs[0]];
+            *x = out;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::f32::consts::TAU;
 
-    out
+    use super::{
+        extract_channel, goertzel_mag, goertzel_mag_exact, goertzel_mag_multi, to_db, to_mono,
+        ChannelSelect, DcBlocker, Notch, SlidingWindow, DB_MIN,
+    };
+
+    #[test]
+    fn test_goertzel_mag() {
+        for len in [480, 1200, 4800] {
+            let samples = (0..len)
+                .map(|i| (TAU * 1000.0 * i as f32 / 48000.0).sin())
+                .collect::<Vec<_>>();
+
+            let mag = goertzel_mag(1000.0, &samples, 48000);
+            assert!((mag - 1.0).abs() < 0.01, "{mag} != 1.0 ({len} samples)");
+            assert!(goertzel_mag(2000.0, &samples, 48000) < 0.01);
+        }
+
+        assert_eq!(goertzel_mag(1000.0, &[], 48000), 0.0);
+    }
+
+    #[test]
+    fn test_goertzel_mag_exact() {
+        // 1050Hz is halfway between the 100Hz wide bins of 480 samples
+        let samples = (0..480)
+            .map(|i| (TAU * 1050.0 * i as f32 / 48000.0).sin())
+            .collect::<Vec<_>>();
+
+        let mag = goertzel_mag_exact(1050.0, &samples, 48000);
+        assert!((mag - 1.0).abs() < 0.01, "{mag} != 1.0");
+        assert!(goertzel_mag(1050.0, &samples, 48000) < 0.7);
+        assert_eq!(goertzel_mag_exact(1000.0, &[], 48000), 0.0);
+    }
+
+    #[test]
+    fn test_to_db() {
+        assert_eq!(to_db(1.0, DB_MIN), 0.0);
+        assert!((to_db(0.1, DB_MIN) + 20.0).abs() < 1e-4);
+        assert_eq!(to_db(0.0, DB_MIN), DB_MIN);
+        assert_eq!(to_db(0.01, -30.0), -30.0);
+    }
+
+    #[test]
+    fn test_goertzel_mag_multi() {
+        let freqs = [697.0, 770.0, 852.0, 941.0, 1209.0, 1336.0, 1477.0, 1633.0];
+        let samples = (0..512)
+            .map(|i| {
+                let t = i as f32 / 48000.0;
+                (TAU * 770.0 * t).sin() * 0.5 + (TAU * 1477.0 * t).sin() * 0.5
+            })
+            .collect::<Vec<_>>();
+
+        let multi = goertzel_mag_multi(&freqs, &samples, 48000);
+        for (freq, mag) in freqs.iter().zip(multi) {
+            let single = goertzel_mag(*freq, &samples, 48000);
+            assert!((mag - single).abs() < 1e-4, "{mag} != {single} ({freq}Hz)");
+        }
+
+        assert_eq!(goertzel_mag_multi(&freqs, &[], 48000), [0.0; 8]);
+    }
+
+    #[test]
+    fn test_to_mono() {
+        let samples = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0];
+        assert_eq!(to_mono(&samples, 1), samples);
+        assert_eq!(to_mono(&samples, 2), [1.5, 3.5, 5.5]);
+        assert_eq!(to_mono(&samples, 3), [2.0, 5.0]);
+        assert_eq!(to_mono(&samples[..6], 3), [2.0, 5.0]);
+        assert!(to_mono(&samples[..2], 3).is_empty());
+    }
+
+    #[test]
+    fn test_extract_channel() {
+        let samples = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0];
+        assert_eq!(extract_channel(&samples, 1, 0), samples);
+        assert_eq!(extract_channel(&samples, 2, 0), [1.0, 3.0, 5.0]);
+        assert_eq!(extract_channel(&samples, 2, 1), [2.0, 4.0, 6.0]);
+        assert_eq!(extract_channel(&samples, 3, 2), [3.0, 6.0]);
+        assert!(extract_channel(&samples[..2], 3, 0).is_empty());
+        assert!(extract_channel(&[], 2, 1).is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_extract_channel_missing() {
+        extract_channel(&[1.0, 2.0], 2, 2);
+    }
+
+    #[test]
+    fn test_channel_select() {
+        assert_eq!(ChannelSelect::parse("1").unwrap(), ChannelSelect::Index(1));
+        assert_eq!(ChannelSelect::parse("Mono").unwrap(), ChannelSelect::Mono);
+        assert_eq!(ChannelSelect::parse("max").unwrap(), ChannelSelect::Max);
+        assert!(ChannelSelect::parse("left").is_err());
+        assert!(ChannelSelect::parse("-1").is_err());
+
+        // A 1kHz sine on the second channel of a stereo input, with silence on the first
+        let samples = (0..4800)
+            .flat_map(|i| [0.0, (TAU * 1000.0 * i as f32 / 48000.0).sin()])
+            .collect::<Vec<_>>();
+        let mag = |select: ChannelSelect| {
+            let channel = select.select(&samples, 2);
+            assert_eq!(channel.len(), 4800);
+            goertzel_mag(1000.0, &channel, 48000)
+        };
+
+        assert!(mag(ChannelSelect::Index(0)) < 0.01);
+        assert!((mag(ChannelSelect::Index(1)) - 1.0).abs() < 0.01);
+        assert!((mag(ChannelSelect::Mono) - 0.5).abs() < 0.01);
+        assert!((mag(ChannelSelect::Max) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_sliding_window() {
+        let mut window = SlidingWindow::new(4, 2);
+        assert_eq!(window.push(&[1.0]), None);
+
+        // Not full yet, even though a hop has passed
+        assert_eq!(window.push(&[2.0]), None);
+        assert_eq!(window.push(&[3.0, 4.0]), Some(&[1.0, 2.0, 3.0, 4.0][..]));
+        assert_eq!(window.push(&[5.0]), None);
+        assert_eq!(
+            window.push(&[6.0, 7.0, 8.0]),
+            Some(&[5.0, 6.0, 7.0, 8.0][..])
+        );
+        assert_eq!(window.size(), 4);
+    }
+
+    #[test]
+    fn test_dc_blocker() {
+        let mut filter = DcBlocker::new(2, 10.0, 48000);
+        let mean = |x: &[f32]| x.iter().sum::<f32>() / x.len() as f32;
+
+        // A 1kHz sine with an offset on the left channel, and just the offset on the right
+        let mut chunks = Vec::new();
+        for chunk in 0..10 {
+            let mut samples = (0..4800)
+                .flat_map(|i| {
+                    let t = (chunk * 4800 + i) as f32 / 48000.0;
+                    [(TAU * 1000.0 * t).sin() * 0.5 + 0.3, 0.3]
+                })
+                .collect::<Vec<_>>();
+            filter.process(&mut samples);
+            chunks.push(samples);
+        }
+
+        let last = chunks.last().unwrap();
+        let left = last.iter().step_by(2).copied().collect::<Vec<_>>();
+        let right = last.iter().skip(1).step_by(2).copied().collect::<Vec<_>>();
+        assert!(mean(&left).abs() < 0.01, "{} != 0", mean(&left));
+        assert!(mean(&right).abs() < 0.01, "{} != 0", mean(&right));
+        assert!(mean(&chunks[0]) > mean(last));
+
+        // The sine itself should pass through
+        let peak = left.iter().fold(0.0_f32, |max, x| x.abs().max(max));
+        assert!((peak - 0.5).abs() < 0.02, "{peak} != 0.5");
+    }
+
+    #[test]
+    fn test_notch() {
+        let sine = |freq: f32| {
+            (0..48000)
+                .map(|i| (TAU * freq * i as f32 / 48000.0).sin())
+                .collect::<Vec<_>>()
+        };
+        // Skip the first half second, while the filter settles
+        let level = |samples: &[f32], freq: f32| goertzel_mag(freq, &samples[24000..], 48000);
+
+        let mut hum = sine(60.0);
+        Notch::new(1, 60.0, 10.0, 48000).process(&mut hum);
+        assert!(level(&hum, 60.0) < 0.01, "{} != 0", level(&hum, 60.0));
+
+        let mut tone = sine(100.0);
+        Notch::new(1, 60.0, 10.0, 48000).process(&mut tone);
+        assert!(level(&tone, 100.0) > 0.9, "{} != 1", level(&tone, 100.0));
+    }
 }
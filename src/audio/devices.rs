@@ -4,13 +4,13 @@
 use clap::ArgMatches;
 use cpal::{
     traits::{DeviceTrait, HostTrait},
-    Device, SupportedStreamConfig,
+    Device, Host, SampleFormat, SampleRate, SupportedStreamConfig, SupportedStreamConfigRange,
 };
 
 use crate::misc::Similarity;
 
 /// Information about the input and output devices being used.
-/// Holds the [`Device`] and [`SupportedStreamConfig`] struct from cpal and a gain value which is applied in the Module's [`crate::modules::Module::input_raw`] and [`crate::modules::Module::output_raw`] functions.
+/// Holds the [`Device`] and [`SupportedStreamConfig`] struct from cpal and a gain value which is applied in the Module's `input_raw` and `output_raw` functions.
 pub struct Devices {
     /// cpal device for input
     pub input_device: Device,
@@ -29,7 +29,8 @@ pub struct Devices {
 }
 
 /// Uses the command line flags (-i and -o) to pick the audio devices, returning a [`Devices`] struct.
-/// Note: The devices are picked by finding the device with the highest string similarity (dice coefficient) to the given name.
+/// Note: If the flag is a number, it is used as the index of the device in the list printed by the `device` subcommand.
+/// Otherwise the devices are picked by finding the device with the highest string similarity (dice coefficient) to the given name.
 pub fn get_devices(args: &ArgMatches) -> Devices {
     let host = cpal::default_host();
     let wanted_output_device = args
@@ -44,10 +45,15 @@ pub fn get_devices(args: &ArgMatches) -> Devices {
     let comp_name =
         |dev: &Device, wanted: &String| dev.name().unwrap().to_lowercase().similarity(wanted);
 
-    let output_device = match wanted_output_device.as_str() {
-        "default" => host
+    let output_device = match (
+        wanted_output_device.as_str(),
+        wanted_output_device.parse::<usize>(),
+    ) {
+        ("default", _) => host
             .default_output_device()
             .expect("No default output device"),
+        (_, Ok(index)) => device_by_index(&host, index, |x| x.default_output_config().is_ok())
+            .expect("No output device with that index"),
         _ => {
             host.output_devices()
                 .unwrap()
@@ -58,10 +64,15 @@ pub fn get_devices(args: &ArgMatches) -> Devices {
         }
     };
 
-    let input_device = match wanted_input_device.as_str() {
-        "default" => host
+    let input_device = match (
+        wanted_input_device.as_str(),
+        wanted_input_device.parse::<usize>(),
+    ) {
+        ("default", _) => host
             .default_input_device()
             .expect("No default input device"),
+        (_, Ok(index)) => device_by_index(&host, index, |x| x.default_input_config().is_ok())
+            .expect("No input device with that index"),
         _ => {
             host.input_devices()
                 .unwrap()
@@ -73,15 +84,74 @@ pub fn get_devices(args: &ArgMatches) -> Devices {
     };
 
     Devices {
-        input_config: input_device
-            .default_input_config()
-            .expect("No default input config"),
+        input_config: match args.get_one::<u32>("input-rate") {
+            Some(&rate) => pick_config(
+                input_device
+                    .supported_input_configs()
+                    .expect("Error getting input configs"),
+                rate,
+            ),
+            None => input_device
+                .default_input_config()
+                .expect("No default input config"),
+        },
         input_device,
         input_gain: *args.get_one::<f32>("input-gain").unwrap(),
-        output_config: output_device
-            .default_output_config()
-            .expect("No default output config"),
+        output_config: match args.get_one::<u32>("output-rate") {
+            Some(&rate) => pick_config(
+                output_device
+                    .supported_output_configs()
+                    .expect("Error getting output configs"),
+                rate,
+            ),
+            None => output_device
+                .default_output_config()
+                .expect("No default output config"),
+        },
         output_device,
         output_gain: *args.get_one::<f32>("output-gain").unwrap(),
     }
 }
+
+/// Gets the device at `index` in the host's device list, the same order used by the `device` subcommand.
+/// Returns None if there is no device at that index or it is not `supported`.
+fn device_by_index(
+    host: &Host,
+    index: usize,
+    supported: impl Fn(&Device) -> bool,
+) -> Option<Device> {
+    host.devices().unwrap().nth(index).filter(supported)
+}
+
+/// Picks a stream config with the given sample rate from the supported config ranges.
+/// Configs using f32 samples are preferred, as that is what the streams are built with.
+/// Panics with a list of the supported ranges if none of them contain the sample rate.
+fn pick_config(
+    configs: impl Iterator<Item = SupportedStreamConfigRange>,
+    rate: u32,
+) -> SupportedStreamConfig {
+    let configs = configs.collect::<Vec<_>>();
+    configs
+        .iter()
+        .filter(|x| (x.min_sample_rate().0..=x.max_sample_rate().0).contains(&rate))
+        .max_by_key(|x| x.sample_format() == SampleFormat::F32)
+        .map(|x| x.clone().with_sample_rate(SampleRate(rate)))
+        .unwrap_or_else(|| {
+            let supported = configs
+                .iter()
+                .map(|x| {
+                    format!(
+                        "{}..{}Hz [{}] ({:?})",
+                        x.min_sample_rate().0,
+                        x.max_sample_rate().0,
+                        x.channels(),
+                        x.sample_format()
+                    )
+                })
+                .collect::<Vec<_>>();
+            panic!(
+                "No config with a sample rate of {rate}Hz. Supported configs: {}",
+                supported.join(", ")
+            )
+        })
+}
@@ -1,9 +1,11 @@
 //! Audio utilities.
 //! Tone generation and sequencing.
 
+pub mod agc;
 pub mod algorithms;
 pub mod devices;
 pub mod passthrough;
 pub mod sequence;
 pub mod tone;
+pub mod wav;
 pub mod windows;
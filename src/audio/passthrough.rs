@@ -1,21 +1,46 @@
 use std::collections::VecDeque;
 
+use clap::ValueEnum;
+use cpal::SupportedStreamConfig;
 use rubato::{
     Resampler, SincFixedIn, SincInterpolationParameters, SincInterpolationType, WindowFunction,
 };
 
-use crate::modules::InitContext;
+use crate::misc::SampleRate;
 
 /// Buffer time in seconds.
 /// This is the time the audio is buffered before it is written to the output.
 const BUFFER_SIZE: f32 = 5.0 / 1000.0;
 
+/// The quality of the resampler used to convert the input to the output sample rate.
+/// Higher qualities have less artifacts, but use more CPU.
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum ResampleQuality {
+    /// Short sinc filter with nearest neighbor interpolation.
+    Low,
+    /// Long sinc filter with linear interpolation.
+    Medium,
+    /// Long sinc filter with cubic interpolation.
+    High,
+}
+
+/// Routes input channels to output channels in the pass-through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelMap {
+    /// Pairs of input and output channel indexes, starting at 0.
+    /// An input can be sent to multiple outputs, and inputs sent to the same output are mixed together.
+    pub routes: Vec<(usize, usize)>,
+}
+
 /// Used to pass audio from the input to the output.
 /// Useful if you want to hear the audio while analyzing it.
-/// Note: The buffers are Vecs of VecDeques because they are storing the samples of each channel individually.
+/// Note: The buffers are Vecs of VecDeques because they are storing the samples of each route of the [`ChannelMap`] individually.
 pub struct PassThrough {
     // == Constants ==
-    ctx: InitContext,
+    sample_rate: SampleRate,
+    input_channels: usize,
+    output_channels: usize,
+    channel_map: ChannelMap,
     resample_size: usize,
     rate_ratio: f32,
 
@@ -23,29 +48,43 @@ pub struct PassThrough {
     buffer: Vec<VecDeque<f32>>,
     out_buffer: Vec<VecDeque<f32>>,
     buffer_filled: bool,
+    /// The number of output callbacks that ran out of buffered samples.
+    underruns: usize,
 }
 
 impl PassThrough {
-    /// Creates a new pass-through
-    pub fn new(ctx: InitContext, resample_size: usize) -> Self {
-        let channels = ctx.input.channels().min(ctx.output.channels()) as usize;
-        let parameters = SincInterpolationParameters {
-            sinc_len: 256,
-            f_cutoff: 0.95,
-            interpolation: SincInterpolationType::Linear,
-            oversampling_factor: 256,
-            window: WindowFunction::BlackmanHarris2,
-        };
+    /// Creates a new pass-through from the input to the output stream config.
+    /// Without a channel map, each input channel is sent to the output channel with the same index.
+    /// Errors if the channel map uses a channel the input or output doesn't have.
+    pub fn new(
+        input: &SupportedStreamConfig,
+        output: &SupportedStreamConfig,
+        channel_map: Option<ChannelMap>,
+        resample_size: usize,
+        quality: ResampleQuality,
+    ) -> anyhow::Result<Self> {
+        let sample_rate = SampleRate::new(input.sample_rate().0, output.sample_rate().0);
+        let (input_channels, output_channels) =
+            (input.channels() as usize, output.channels() as usize);
+        let channel_map = channel_map
+            .unwrap_or_else(|| ChannelMap::identity(input_channels.min(output_channels)));
+        channel_map.check(input_channels, output_channels)?;
+
+        let channels = channel_map.routes.len();
+        let parameters = quality.parameters();
 
         // Inits the resampler
         // This is needed because the input and output sample rates are not always the same.
         // So we have to resample the input to the output sample rate before writing it to the output.
-        let resample_ratio = ctx.sample_rate().output as f64 / ctx.sample_rate().input as f64;
+        let resample_ratio = sample_rate.output as f64 / sample_rate.input as f64;
         let resampler =
             SincFixedIn::new(resample_ratio, 2., parameters, resample_size, channels).unwrap();
 
-        Self {
-            ctx,
+        Ok(Self {
+            sample_rate,
+            input_channels,
+            output_channels,
+            channel_map,
             resampler,
             rate_ratio: resample_ratio.recip() as f32,
 
@@ -53,23 +92,20 @@ impl PassThrough {
             buffer: vec![VecDeque::new(); channels],
             out_buffer: vec![VecDeque::new(); channels],
             buffer_filled: false,
-        }
+            underruns: 0,
+        })
     }
 
     /// Adds samples from the input to the buffer.
     /// If the buffer is big enough, it will resample the samples and but them in the output buffer.
     pub fn add_samples(&mut self, samples: &[f32]) {
-        let inp_channels = self.ctx.input.channels() as usize;
         let channels = self.buffer.len();
 
-        // Adds the samples to the buffer of the corresponding channel
-        for (i, &e) in samples.iter().enumerate() {
-            let channel = i % inp_channels;
-            if channel >= channels {
-                continue;
+        // Adds the samples of each routed input channel to the buffer of its route
+        for frame in samples.chunks_exact(self.input_channels) {
+            for (buf, &(input, _)) in self.buffer.iter_mut().zip(&self.channel_map.routes) {
+                buf.push_back(frame[input]);
             }
-
-            self.buffer[channel].push_back(e);
         }
 
         // Resamples the samples if the buffer is big enough
@@ -88,33 +124,132 @@ impl PassThrough {
         }
     }
 
+    /// Gets the number of times the output ran out of buffered samples.
+    /// If this keeps going up, the buffer latency is too low for the system.
+    pub fn underruns(&self) -> usize {
+        self.underruns
+    }
+
     /// Writes the output to the output buffer.
+    /// If the buffered samples run out, the rest of the output is silence and the buffer is refilled before playing again.
     pub fn write_output(&mut self, output: &mut [f32]) {
-        let out_channels = self.ctx.output.channels() as usize;
-
+        output.fill(0.0);
         if !self.buffer_filled {
             let samples = self.out_buffer.iter().map(|x| x.len()).max().unwrap_or(0);
-            self.buffer_filled = samples as f32 / self.ctx.sample_rate().output as f32
-                * self.rate_ratio
-                >= BUFFER_SIZE;
+            self.buffer_filled =
+                samples as f32 / self.sample_rate.output as f32 * self.rate_ratio >= BUFFER_SIZE;
             return;
         }
 
-        for (i, e) in output.iter_mut().enumerate() {
-            let channel = i % self.ctx.output.channels() as usize;
-            if channel >= out_channels {
-                *e = 0.0;
-                continue;
+        // Mixes the samples of each route into its output channel, unrouted channels are left silent
+        let mut underrun = false;
+        for frame in output.chunks_exact_mut(self.output_channels) {
+            for (buf, &(_, channel)) in self.out_buffer.iter_mut().zip(&self.channel_map.routes) {
+                match buf.pop_front() {
+                    Some(x) => frame[channel] += x,
+                    None => underrun = true,
+                }
             }
+        }
 
-            let channel_buf = match self.out_buffer.get_mut(channel) {
-                Some(x) => x,
-                None => {
-                    *e = 0.0;
-                    continue;
-                }
-            };
-            *e = channel_buf.pop_front().unwrap_or(0.0);
+        if underrun {
+            self.underruns += 1;
+            self.buffer_filled = false;
+        }
+    }
+}
+
+impl ChannelMap {
+    /// Routes each of the first `channels` inputs to the output with the same index.
+    pub fn identity(channels: usize) -> Self {
+        Self {
+            routes: (0..channels).map(|x| (x, x)).collect(),
+        }
+    }
+
+    /// Parses a comma-separated list of `input:output` channel pairs, like `0:0,0:1`.
+    pub fn parse(s: &str) -> anyhow::Result<Self> {
+        let routes = s
+            .split(',')
+            .map(|x| {
+                let (input, output) = x
+                    .split_once(':')
+                    .ok_or_else(|| anyhow::anyhow!("Expected `input:output`, got `{x}`"))?;
+                Ok((input.trim().parse()?, output.trim().parse()?))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Self { routes })
+    }
+
+    /// Checks that every route uses channels the input and output have.
+    pub fn check(&self, input_channels: usize, output_channels: usize) -> anyhow::Result<()> {
+        for &(input, output) in &self.routes {
+            anyhow::ensure!(
+                input < input_channels,
+                "Input channel {input} does not exist, the input has {input_channels} channels"
+            );
+            anyhow::ensure!(
+                output < output_channels,
+                "Output channel {output} does not exist, the output has {output_channels} channels"
+            );
+        }
+        Ok(())
+    }
+}
+
+impl ResampleQuality {
+    /// Gets the sinc interpolation parameters for the resampler.
+    fn parameters(&self) -> SincInterpolationParameters {
+        match self {
+            ResampleQuality::Low => SincInterpolationParameters {
+                sinc_len: 64,
+                f_cutoff: 0.9,
+                interpolation: SincInterpolationType::Nearest,
+                oversampling_factor: 128,
+                window: WindowFunction::Blackman2,
+            },
+            ResampleQuality::Medium => SincInterpolationParameters {
+                sinc_len: 256,
+                f_cutoff: 0.95,
+                interpolation: SincInterpolationType::Linear,
+                oversampling_factor: 256,
+                window: WindowFunction::BlackmanHarris2,
+            },
+            ResampleQuality::High => SincInterpolationParameters {
+                sinc_len: 256,
+                f_cutoff: 0.95,
+                interpolation: SincInterpolationType::Cubic,
+                oversampling_factor: 256,
+                window: WindowFunction::BlackmanHarris2,
+            },
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::ChannelMap;
+
+    #[test]
+    fn test_channel_map_parse() {
+        let map = ChannelMap::parse("0:0, 0:1,2:1").unwrap();
+        assert_eq!(map.routes, [(0, 0), (0, 1), (2, 1)]);
+        assert_eq!(
+            ChannelMap::identity(2),
+            ChannelMap::parse("0:0,1:1").unwrap()
+        );
+
+        assert!(ChannelMap::parse("0").is_err());
+        assert!(ChannelMap::parse("0:a").is_err());
+        assert!(ChannelMap::parse("").is_err());
+    }
+
+    #[test]
+    fn test_channel_map_check() {
+        let map = ChannelMap::parse("0:0,1:1").unwrap();
+        assert!(map.check(2, 2).is_ok());
+        assert!(map.check(1, 2).is_err());
+        assert!(map.check(2, 1).is_err());
+    }
+}
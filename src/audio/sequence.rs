@@ -1,18 +1,24 @@
 //! Tone sequencer.
 
+use anyhow::Context;
+
 use crate::misc::SampleRate;
 
-use super::tone::{SmoothTone, Tone};
+use super::tone::{SmoothTone, Tone, Waveform};
 
 /// A sequence of tones.
 /// Will continue to the next tone when the current one is finished.
+#[derive(Clone)]
 pub struct Sequence<T: Sequenceable> {
     tones: Vec<T>,
     index: usize,
 }
 
+/// A tone that can be played in a [`Sequence`].
 pub trait Sequenceable {
-    fn new(freq: f32, sample_rate: SampleRate, duration: usize) -> Self;
+    /// Creates a tone with a duration in samples and a peak value from 0 to 1.
+    /// Rests are created as a tone with a frequency and amplitude of zero.
+    fn new(freq: f32, sample_rate: SampleRate, duration: usize, amplitude: f32) -> Self;
     fn next(&mut self) -> Option<f32>;
 }
 
@@ -32,26 +38,74 @@ impl<T: Sequenceable> Sequence<T> {
     }
 
     /// Create a sequence from a string.
+    /// Blank lines are skipped and anything after a `#` is a comment.
+    /// The amplitude is optional and defaults to 1, and `rest` can be used in place of the frequency for silence.
     /// The format is as follows:
     /// ```text
-    /// Freq;time(s)
+    /// # Freq;time(s);amplitude
     /// 440;1.2
+    /// 440;0.5;0.25
+    /// rest;0.5
     /// ```
-    pub fn from_seq(seq: &str, sample_rate: SampleRate) -> Self {
+    pub fn from_seq(seq: &str, sample_rate: SampleRate) -> anyhow::Result<Self> {
         let mut tones = Vec::new();
 
-        for i in seq.lines() {
-            let (freq, time) = i.split_once(';').unwrap();
-            let freq = freq.parse::<f32>().unwrap();
-            let time = time.parse::<f32>().unwrap();
+        for (i, line) in seq.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (freq, time, amplitude) =
+                parse_line(line).with_context(|| format!("Line {}", i + 1))?;
             tones.push(T::new(
                 freq,
                 sample_rate,
                 (sample_rate.output as f32 * time) as usize,
+                amplitude,
             ));
         }
 
-        Self { tones, index: 0 }
+        Ok(Self { tones, index: 0 })
+    }
+}
+
+/// Parses a `freq;time;amplitude` or `rest;time` line into its frequency, time in seconds and amplitude.
+fn parse_line(line: &str) -> anyhow::Result<(f32, f32, f32)> {
+    let parts = line.split(';').map(str::trim).collect::<Vec<_>>();
+    let (freq, time, amplitude) = match parts[..] {
+        ["rest", time] => ("0", time, "0"),
+        ["rest", _, _] => anyhow::bail!("Rests can't have an amplitude"),
+        [freq, time] => (freq, time, "1"),
+        [freq, time, amplitude] => (freq, time, amplitude),
+        _ => anyhow::bail!("Expected `freq;time;amplitude` or `rest;time`, got `{line}`"),
+    };
+
+    let freq = freq
+        .parse::<f32>()
+        .with_context(|| format!("Invalid frequency `{freq}`"))?;
+    let time = time
+        .parse::<f32>()
+        .with_context(|| format!("Invalid time `{time}`"))?;
+    let amplitude = amplitude
+        .parse::<f32>()
+        .with_context(|| format!("Invalid amplitude `{amplitude}`"))?;
+    anyhow::ensure!(time >= 0.0, "The time can't be negative");
+    anyhow::ensure!(
+        (0.0..=1.0).contains(&amplitude),
+        "The amplitude must be from 0 to 1"
+    );
+
+    Ok((freq, time, amplitude))
+}
+
+impl Sequence<Tone> {
+    /// Sets the waveform of every tone in the sequence.
+    pub fn waveform(mut self, waveform: Waveform) -> Self {
+        for tone in self.tones.iter_mut() {
+            *tone = tone.waveform(waveform);
+        }
+        self
     }
 }
 
@@ -75,8 +129,10 @@ impl<T: Sequenceable> Default for Sequence<T> {
 }
 
 impl Sequenceable for Tone {
-    fn new(freq: f32, sample_rate: SampleRate, duration: usize) -> Self {
-        Self::new(freq, sample_rate).duration(duration)
+    fn new(freq: f32, sample_rate: SampleRate, duration: usize, amplitude: f32) -> Self {
+        Self::new(freq, sample_rate)
+            .duration(duration)
+            .amplitude(amplitude)
     }
 
     fn next(&mut self) -> Option<f32> {
@@ -85,15 +141,71 @@ impl Sequenceable for Tone {
 }
 
 impl Sequenceable for SmoothTone {
-    fn new(freq: f32, sample_rate: SampleRate, duration: usize) -> Self {
+    fn new(freq: f32, sample_rate: SampleRate, duration: usize, amplitude: f32) -> Self {
         Self::new(
             freq,
             sample_rate,
             duration as f32 / sample_rate.output as f32,
         )
+        .amplitude(amplitude)
     }
 
     fn next(&mut self) -> Option<f32> {
         Iterator::next(self)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Sequence;
+    use crate::{audio::tone::Tone, misc::SampleRate};
+
+    const SAMPLE_RATE: u32 = 1000;
+
+    fn parse(seq: &str) -> anyhow::Result<Sequence<Tone>> {
+        Sequence::from_seq(seq, SampleRate::from_hz(SAMPLE_RATE))
+    }
+
+    #[test]
+    fn test_from_seq() {
+        let seq = parse("# A test\n440;0.5\n\n  880 ; 0.25 # Up an octave\n").unwrap();
+        assert_eq!(seq.tones.len(), 2);
+        assert_eq!(seq.count(), 750);
+    }
+
+    #[test]
+    fn test_from_seq_errors() {
+        let err = parse("440;1\n440").err().unwrap();
+        assert_eq!(
+            format!("{err:#}"),
+            "Line 2: Expected `freq;time;amplitude` or `rest;time`, got `440`"
+        );
+        assert!(parse("abc;1").is_err());
+        assert!(parse("440;x").is_err());
+        assert!(parse("440;-1").is_err());
+        assert!(parse("440;1;0.5;1").is_err());
+    }
+
+    #[test]
+    fn test_amplitude() {
+        let seq = parse("250;0.1;0.5").unwrap().collect::<Vec<_>>();
+        assert_eq!(seq.len(), 100);
+        assert!((seq[0] - 0.5).abs() < 1e-6);
+        assert!(seq.iter().all(|x| x.abs() <= 0.5 + 1e-6));
+
+        assert!(parse("440;1;2").is_err());
+        assert!(parse("440;1;-0.5").is_err());
+        assert!(parse("440;1;loud").is_err());
+    }
+
+    #[test]
+    fn test_rest() {
+        let seq = parse("rest;0.1\n250;0.1").unwrap().collect::<Vec<_>>();
+        assert_eq!(seq.len(), 200);
+        assert!(seq[..100].iter().all(|&x| x == 0.0));
+        assert!(seq[100..].iter().any(|&x| x != 0.0));
+
+        assert!(parse("rest;1;0.5").is_err());
+        assert!(parse("rest").is_err());
+    }
+}
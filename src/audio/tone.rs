@@ -3,6 +3,8 @@
 
 use std::f32::consts::PI;
 
+use clap::ValueEnum;
+
 use crate::misc::SampleRate;
 
 /// A static frequency tone generator.
@@ -10,6 +12,9 @@ use crate::misc::SampleRate;
 pub struct Tone {
     /// The index of the current sample.
     i: usize,
+    /// How far through the current period the tone is, from 0 to 1.
+    /// Accumulated each sample so changing the frequency doesn't cause a discontinuity.
+    phase: f32,
     /// The frequency of the tone (Hz).
     tone: f32,
     /// The output device's sample rate.
@@ -17,6 +22,20 @@ pub struct Tone {
     /// An optional duration for the tone in samples.
     /// Will just cut off the tone when the duration is reached.
     duration: Option<usize>,
+    /// The shape of the wave.
+    waveform: Waveform,
+    /// The peak value of the wave, each sample is multiplied by this.
+    amplitude: f32,
+}
+
+/// The shape of the wave generated by a [`Tone`].
+/// All of the waveforms start at zero and rise, like a sine wave.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Waveform {
+    Sine,
+    Square,
+    Triangle,
+    Sawtooth,
 }
 
 /// An extension of the Tone struct that ramps the volume up and down at the start and end of the tone to prevent popping.
@@ -33,14 +52,35 @@ pub struct SmoothTone {
     out_point: usize,
 }
 
+/// A tone that sweeps linearly from a start frequency to an end frequency (a chirp).
+/// The phase is integrated over the changing frequency, so the sweep is smooth.
+#[derive(Clone, Copy, Debug)]
+pub struct Chirp {
+    /// The index of the current sample.
+    i: usize,
+    /// How far through the current period the tone is, from 0 to 1.
+    phase: f32,
+    /// The frequency at the start of the sweep (Hz).
+    start: f32,
+    /// The frequency at the end of the sweep (Hz).
+    end: f32,
+    /// The output device's sample rate.
+    sample_rate: f32,
+    /// The duration of the sweep in samples.
+    duration: usize,
+}
+
 impl Tone {
     /// Create a new tone with the given frequency and sample rate.
     pub fn new(tone: f32, sample_rate: SampleRate) -> Self {
         Self {
             i: 0,
+            phase: 0.0,
             sample_rate: sample_rate.output as f32,
             tone,
             duration: None,
+            waveform: Waveform::Sine,
+            amplitude: 1.0,
         }
     }
 
@@ -50,10 +90,43 @@ impl Tone {
         self
     }
 
+    /// Sets the shape of the wave, defaults to [`Waveform::Sine`].
+    pub fn waveform(mut self, waveform: Waveform) -> Self {
+        self.waveform = waveform;
+        self
+    }
+
+    /// Sets the peak value of the wave, defaults to 1.0.
+    pub fn amplitude(mut self, amplitude: f32) -> Self {
+        self.amplitude = amplitude;
+        self
+    }
+
+    /// Changes the frequency of the tone without resetting its phase.
+    pub fn set_frequency(&mut self, tone: f32) {
+        self.tone = tone;
+    }
+
     /// Resets the tone to the beginning.
     /// Useful if you have passed the duration and want to play the tone again.
     pub fn reset(&mut self) {
         self.i = 0;
+        self.phase = 0.0;
+    }
+}
+
+impl Waveform {
+    /// Gets the value of the wave at `phase`, which goes from 0 to 1 over one period.
+    fn sample(&self, phase: f32) -> f32 {
+        match self {
+            Self::Sine => (phase * 2.0 * PI).sin(),
+            Self::Square if phase < 0.5 => 1.0,
+            Self::Square => -1.0,
+            Self::Triangle if phase < 0.25 => phase * 4.0,
+            Self::Triangle if phase < 0.75 => 2.0 - phase * 4.0,
+            Self::Triangle => phase * 4.0 - 4.0,
+            Self::Sawtooth => (phase + 0.5).fract() * 2.0 - 1.0,
+        }
     }
 }
 
@@ -68,7 +141,39 @@ impl Iterator for Tone {
             _ => {}
         }
 
-        Some((self.i as f32 * self.tone * 2.0 * PI / self.sample_rate).sin())
+        self.phase = (self.phase + self.tone / self.sample_rate).fract();
+        Some(self.waveform.sample(self.phase) * self.amplitude)
+    }
+}
+
+impl Chirp {
+    /// Create a new chirp sweeping from `start` to `end` over `duration` seconds.
+    pub fn new(start: f32, end: f32, sample_rate: SampleRate, duration: f32) -> Self {
+        Self {
+            i: 0,
+            phase: 0.0,
+            start,
+            end,
+            sample_rate: sample_rate.output as f32,
+            duration: (sample_rate.output as f32 * duration) as usize,
+        }
+    }
+}
+
+impl Iterator for Chirp {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.i >= self.duration {
+            return None;
+        }
+
+        let t = self.i as f32 / self.duration as f32;
+        let freq = self.start + (self.end - self.start) * t;
+        self.i += 1;
+
+        self.phase = (self.phase + freq / self.sample_rate).fract();
+        Some((self.phase * 2.0 * PI).sin())
     }
 }
 
@@ -97,6 +202,12 @@ impl SmoothTone {
         self
     }
 
+    /// Sets the peak value of the wave, defaults to 1.0.
+    pub fn amplitude(mut self, amplitude: f32) -> Self {
+        self.inner = self.inner.amplitude(amplitude);
+        self
+    }
+
     /// Sets the point at which the volume ramp up will be complete, in seconds.
     pub fn in_point(mut self, in_point: f32) -> Self {
         self.in_point = (self.inner.sample_rate * in_point) as usize;
@@ -130,3 +241,59 @@ impl Iterator for SmoothTone {
         Some(raw)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Chirp, Tone, Waveform};
+    use crate::misc::SampleRate;
+
+    /// Gets one period of a 1kHz tone sampled at 8kHz.
+    fn period(waveform: Waveform) -> Vec<f32> {
+        Tone::new(1000.0, SampleRate::from_hz(8000))
+            .waveform(waveform)
+            .take(8)
+            .collect()
+    }
+
+    #[test]
+    fn test_tone_waveforms() {
+        assert_eq!(
+            period(Waveform::Square),
+            [1.0, 1.0, 1.0, -1.0, -1.0, -1.0, -1.0, 1.0]
+        );
+        assert_eq!(
+            period(Waveform::Triangle),
+            [0.5, 1.0, 0.5, 0.0, -0.5, -1.0, -0.5, 0.0]
+        );
+        assert_eq!(
+            period(Waveform::Sawtooth),
+            [0.25, 0.5, 0.75, -1.0, -0.75, -0.5, -0.25, 0.0]
+        );
+
+        let sine = period(Waveform::Sine);
+        assert!((sine[1] - 1.0).abs() < 1e-6);
+        assert!((sine[5] + 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_chirp() {
+        let sample_rate = SampleRate::from_hz(8000);
+        let chirp = Chirp::new(1000.0, 1000.0, sample_rate, 0.01).collect::<Vec<_>>();
+        let tone = Tone::new(1000.0, sample_rate).take(80);
+
+        assert_eq!(chirp.len(), 80);
+        for (a, b) in chirp.into_iter().zip(tone) {
+            assert!((a - b).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_tone_amplitude() {
+        let peak = Tone::new(1000.0, SampleRate::from_hz(8000))
+            .amplitude(0.25)
+            .take(8)
+            .fold(0.0, f32::max);
+
+        assert!((peak - 0.25).abs() < 1e-6);
+    }
+}
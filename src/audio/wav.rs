@@ -0,0 +1,147 @@
+//! WAV file input and output.
+//! Lets modules be used with recordings instead of a live audio device.
+
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use cpal::{SupportedBufferSize, SupportedStreamConfig};
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+
+/// How often in seconds the header of a recording is updated.
+/// The file is finalized when the recorder is dropped, this just limits what is lost if the program is killed.
+const RECORD_FLUSH_INTERVAL: u32 = 1;
+
+/// Reads a WAV file and plays its samples into a module like an input device would.
+pub struct WavSource {
+    spec: WavSpec,
+    samples: Vec<f32>,
+    looping: bool,
+}
+
+/// Writes the samples sent to an output stream into a WAV file.
+pub struct WavRecorder {
+    writer: WavWriter<BufWriter<File>>,
+    /// The number of samples to write between header updates.
+    flush_interval: usize,
+    /// The number of samples written since the header was last updated.
+    unflushed: usize,
+}
+
+impl WavRecorder {
+    /// Creates a new WAV file at `path` with the same sample rate and channel count as the output stream.
+    /// Samples are stored as 32 bit floats, just like they are sent to the device.
+    pub fn create(path: &Path, config: &SupportedStreamConfig) -> hound::Result<Self> {
+        Self::new(path, config.channels(), config.sample_rate().0)
+    }
+
+    /// Creates a new WAV file at `path` with the given channel count and sample rate.
+    /// Used when the samples don't come from an output stream, like when generating a tone.
+    pub fn new(path: &Path, channels: u16, sample_rate: u32) -> hound::Result<Self> {
+        let spec = WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+
+        Ok(Self {
+            writer: WavWriter::create(path, spec)?,
+            flush_interval: (sample_rate * RECORD_FLUSH_INTERVAL) as usize * channels as usize,
+            unflushed: 0,
+        })
+    }
+
+    /// Adds the interleaved samples to the file.
+    /// The header is updated about every second, and the file is finalized once the recorder is dropped.
+    pub fn write(&mut self, samples: &[f32]) -> hound::Result<()> {
+        for &sample in samples {
+            self.writer.write_sample(sample)?;
+        }
+
+        self.unflushed += samples.len();
+        if self.unflushed >= self.flush_interval {
+            self.unflushed = 0;
+            self.writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl WavSource {
+    /// Loads all the samples from the WAV file at `path`, integer samples are converted to floats from -1 to 1.
+    /// If `looping` is set the file will be played forever, otherwise the input ends once it has finished.
+    pub fn open(path: &Path, looping: bool) -> anyhow::Result<Self> {
+        let mut reader = WavReader::open(path)?;
+        let spec = reader.spec();
+        let samples = match spec.sample_format {
+            SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+            SampleFormat::Int => {
+                let scale = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .samples::<i32>()
+                    .map(|x| x.map(|x| x as f32 / scale))
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+        };
+
+        anyhow::ensure!(!samples.is_empty(), "WAV file has no samples");
+        Ok(Self {
+            spec,
+            samples,
+            looping,
+        })
+    }
+
+    /// Gets a stream config matching the format of the file.
+    /// This is used in place of the input device's config, so modules see the sample rate and channels of the file.
+    pub fn config(&self) -> SupportedStreamConfig {
+        SupportedStreamConfig::new(
+            self.spec.channels,
+            cpal::SampleRate(self.spec.sample_rate),
+            SupportedBufferSize::Unknown,
+            cpal::SampleFormat::F32,
+        )
+    }
+
+    /// Starts a thread that passes the samples to `callback` in chunks of about 10ms.
+    /// The chunks are sent at the same rate they would come from a real device.
+    /// Once the file has been read, `on_end` is called.
+    pub fn start(
+        self,
+        mut callback: impl FnMut(&[f32]) + Send + 'static,
+        on_end: impl FnOnce() + Send + 'static,
+    ) {
+        let channels = self.spec.channels as usize;
+        let chunk = (self.spec.sample_rate as usize / 100).max(1) * channels;
+
+        thread::spawn(move || {
+            let start = Instant::now();
+            let mut frames = 0;
+
+            loop {
+                for i in self.samples.chunks(chunk) {
+                    callback(i);
+
+                    frames += i.len() / channels;
+                    let target =
+                        Duration::from_secs_f64(frames as f64 / self.spec.sample_rate as f64);
+                    if let Some(wait) = target.checked_sub(start.elapsed()) {
+                        thread::sleep(wait);
+                    }
+                }
+
+                if !self.looping {
+                    break;
+                }
+            }
+
+            println!("[*] Finished reading WAV file");
+            on_end();
+        });
+    }
+}
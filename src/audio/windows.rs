@@ -5,9 +5,91 @@ use std::{borrow::Cow, f32::consts::PI};
 /// A boxed, thread safe Window trait object
 pub type BoxedWindow = Box<dyn Window + Send + Sync + 'static>;
 
-/// Different valid windows.
-/// This is only used in the command like error message
-pub const WINDOWS: &[&str] = &["square", "hann", "blackman"];
+/// The beta used for the kaiser window if none is given.
+/// Gives side lobes similar to the blackman window.
+const KAISER_BETA: f32 = 8.6;
+
+/// Every window that can be picked by name.
+/// Used by [`get_window`] to parse windows and by the `windows` subcommand to list them.
+pub const WINDOWS: &[WindowInfo] = &[
+    WindowInfo {
+        name: "square",
+        aliases: &["s"],
+        param: None,
+        description: "No windowing, best resolution but the most leakage. Main lobe 2 bins wide.",
+        create: |_| Box::new(SquareWindow),
+    },
+    WindowInfo {
+        name: "hann",
+        aliases: &["h"],
+        param: None,
+        description: "Good general purpose window. Main lobe 4 bins wide.",
+        create: |_| Box::new(HannWindow),
+    },
+    WindowInfo {
+        name: "hamming",
+        aliases: &["m"],
+        param: None,
+        description: "Like hann, with a lower first side lobe. Main lobe 4 bins wide.",
+        create: |_| Box::new(HammingWindow),
+    },
+    WindowInfo {
+        name: "blackman",
+        aliases: &["b"],
+        param: None,
+        description: "Blackman Nuttall, very low side lobes. Main lobe 8 bins wide.",
+        create: |_| Box::new(BlackmanNuttallWindow),
+    },
+    WindowInfo {
+        name: "flattop",
+        aliases: &["f"],
+        param: None,
+        description: "Accurate amplitudes between bins, poor resolution. Main lobe 10 bins wide.",
+        create: |_| Box::new(FlatTopWindow),
+    },
+    WindowInfo {
+        name: "kaiser",
+        aliases: &["k"],
+        param: Some(("beta", KAISER_BETA)),
+        description:
+            "Higher betas trade resolution for less leakage. Main lobe 6 bins wide by default.",
+        create: |beta| Box::new(KaiserWindow::new(beta)),
+    },
+];
+
+/// Information about a window function, used to parse and list them.
+pub struct WindowInfo {
+    /// The name of the window, as given by [`Window::name`].
+    pub name: &'static str,
+    /// Shorter names that can be used instead of the name.
+    pub aliases: &'static [&'static str],
+    /// The name and default value of the window's parameter, if it takes one.
+    pub param: Option<(&'static str, f32)>,
+    /// A short description of the window.
+    pub description: &'static str,
+    /// Creates the window, with the parameter if it takes one.
+    create: fn(f32) -> BoxedWindow,
+}
+
+impl WindowInfo {
+    /// Creates the window, using the default parameter if none is given.
+    /// Returns None if the window was given a parameter it doesn't take.
+    pub fn build(&self, param: Option<f32>) -> Option<BoxedWindow> {
+        match (self.param, param) {
+            (None, Some(_)) => None,
+            (Some((_, default)), param) => Some((self.create)(param.unwrap_or(default))),
+            (None, None) => Some((self.create)(0.0)),
+        }
+    }
+
+    /// The usage of the window, like `kaiser[:beta]` for windows that take a parameter.
+    pub fn usage(&self) -> String {
+        match self.param {
+            Some((param, _)) => format!("{}[:{param}]", self.name),
+            None => self.name.to_owned(),
+        }
+    }
+}
 
 /// Trait implemented by window functions.
 /// Takes in a slice of samples and outputs those same samples after being transformed
@@ -17,17 +99,37 @@ pub trait Window {
     fn name(&self) -> &'static str;
     /// The main method to run the windowing function
     fn window<'a>(&self, samples: &'a [f32]) -> Cow<'a, [f32]>;
+    /// The coherent gain of the window, which is the mean of its coefficients.
+    /// Dividing by this makes the magnitude of a tone the same no matter what window is used.
+    fn coherent_gain(&self, len: usize) -> f32 {
+        let ones = vec![1.0; len];
+        self.window(&ones).iter().sum::<f32>() / len as f32
+    }
 }
 
 /// Gets a windowing function by its name.
-/// Returns None if there is not one named `name`.
+/// Some windows take a parameter, which is passed in as `param`.
+/// Returns None if there is not one named `name` or it doesn't accept the parameter.
 /// Used in command like arg parsing
-pub fn get_window(name: &str) -> Option<BoxedWindow> {
-    Some(match name.to_ascii_lowercase().as_str() {
-        "s" | "square" => Box::new(SquareWindow),
-        "h" | "hann" => Box::new(HannWindow),
-        "b" | "blackman" => Box::new(BlackmanNuttallWindow),
-        _ => return None,
+pub fn get_window(name: &str, param: Option<f32>) -> Option<BoxedWindow> {
+    let name = name.to_ascii_lowercase();
+    WINDOWS
+        .iter()
+        .find(|x| x.name == name || x.aliases.contains(&name.as_str()))?
+        .build(param)
+}
+
+/// Parses a window from its name, with an optional parameter after a colon, like `kaiser:8.0`.
+/// Used for command line args and config files.
+pub fn parse(s: &str) -> anyhow::Result<BoxedWindow> {
+    let (name, param) = match s.split_once(':') {
+        Some((name, param)) => (name, Some(param.parse::<f32>()?)),
+        None => (s, None),
+    };
+
+    get_window(name, param).ok_or_else(|| {
+        let windows = WINDOWS.iter().map(|x| x.usage()).collect::<Vec<_>>();
+        anyhow::anyhow!("Must be: {}", windows.join(", "))
     })
 }
 
@@ -43,6 +145,10 @@ impl Window for SquareWindow {
     fn window<'a>(&self, samples: &'a [f32]) -> Cow<'a, [f32]> {
         Cow::Borrowed(samples)
     }
+
+    fn coherent_gain(&self, _len: usize) -> f32 {
+        1.0
+    }
 }
 
 /// Hann windowing function.
@@ -69,6 +175,33 @@ impl Window for HannWindow {
     }
 }
 
+/// Hamming windowing function.
+/// \[[Hann and Hamming Windows](https://en.wikipedia.org/wiki/Window_function#Hann_and_Hamming_windows)\]
+pub struct HammingWindow;
+
+impl Window for HammingWindow {
+    fn name(&self) -> &'static str {
+        "hamming"
+    }
+
+    fn window<'a>(&self, samples: &'a [f32]) -> Cow<'a, [f32]> {
+        const A0: f32 = 0.54;
+        const A1: f32 = 0.46;
+
+        let out = samples
+            .iter()
+            .enumerate()
+            .map(|(i, &e)| {
+                let a = (2.0 * PI * i as f32) / samples.len() as f32;
+                let w = A0 - A1 * a.cos();
+                w * e
+            })
+            .collect();
+
+        Cow::Owned(out)
+    }
+}
+
 /// Blackman Nuttall windowing function.
 /// \[[Blackman Nuttall Window](https://en.wikipedia.org/wiki/Window_function#Blackman%E2%80%93Nuttall_window)\]
 pub struct BlackmanNuttallWindow;
@@ -100,3 +233,176 @@ impl Window for BlackmanNuttallWindow {
         Cow::Owned(out)
     }
 }
+
+/// 5-term flat top windowing function.
+/// It has very little scalloping loss, so a tone reads the same magnitude no matter where it falls between bins.
+/// The main lobe is very wide though, so it is intended for reading amplitudes, not for resolving close frequencies.
+/// \[[Flat top window](https://en.wikipedia.org/wiki/Window_function#Flat_top_window)\]
+pub struct FlatTopWindow;
+
+impl Window for FlatTopWindow {
+    fn name(&self) -> &'static str {
+        "flattop"
+    }
+
+    fn window<'a>(&self, samples: &'a [f32]) -> Cow<'a, [f32]> {
+        const A0: f32 = 0.21557895;
+        const A1: f32 = 0.41663158;
+        const A2: f32 = 0.27726316;
+        const A3: f32 = 0.083578947;
+        const A4: f32 = 0.006947368;
+
+        let n = samples.len() as f32;
+        let out = samples
+            .iter()
+            .enumerate()
+            .map(|(i, &e)| {
+                let c1 = (2.0 * PI * i as f32) / n;
+                let c2 = (4.0 * PI * i as f32) / n;
+                let c3 = (6.0 * PI * i as f32) / n;
+                let c4 = (8.0 * PI * i as f32) / n;
+                let w = A0 - A1 * c1.cos() + A2 * c2.cos() - A3 * c3.cos() + A4 * c4.cos();
+                e * w
+            })
+            .collect();
+
+        Cow::Owned(out)
+    }
+}
+
+/// Kaiser windowing function.
+/// The beta parameter controls the tradeoff between main lobe width and side lobe level,
+/// a beta of zero is the same as a square window and larger values reduce leakage at the cost of resolution.
+/// \[[Kaiser window](https://en.wikipedia.org/wiki/Kaiser_window)\]
+pub struct KaiserWindow {
+    beta: f32,
+}
+
+impl KaiserWindow {
+    /// Create a new kaiser window with the given beta.
+    pub fn new(beta: f32) -> Self {
+        Self { beta }
+    }
+}
+
+impl Window for KaiserWindow {
+    fn name(&self) -> &'static str {
+        "kaiser"
+    }
+
+    fn window<'a>(&self, samples: &'a [f32]) -> Cow<'a, [f32]> {
+        let n = samples.len() as f32;
+        let denominator = bessel_i0(self.beta);
+        let out = samples
+            .iter()
+            .enumerate()
+            .map(|(i, &e)| {
+                let x = 2.0 * i as f32 / n - 1.0;
+                let w = bessel_i0(self.beta * (1.0 - x * x).sqrt()) / denominator;
+                e * w
+            })
+            .collect();
+
+        Cow::Owned(out)
+    }
+}
+
+/// Zeroth-order modified Bessel function of the first kind.
+/// Calculated with its power series, which converges quickly for the values used in the kaiser window.
+fn bessel_i0(x: f32) -> f32 {
+    let half = x / 2.0;
+    let mut sum = 1.0;
+    let mut term = 1.0;
+
+    for k in 1..50 {
+        term *= half / k as f32;
+        let add = term * term;
+        sum += add;
+
+        if add < sum * f32::EPSILON {
+            break;
+        }
+    }
+
+    sum
+}
+
+#[cfg(test)]
+mod test {
+    use std::f32::consts::PI;
+
+    use super::{bessel_i0, get_window, parse, Window, WINDOWS};
+
+    const SIZE: usize = 256;
+
+    /// Windows a sine wave at `bin` (can be fractional) and returns the largest DFT magnitude.
+    fn peak(window: &dyn Window, bin: f32) -> f32 {
+        let samples = (0..SIZE)
+            .map(|i| (2.0 * PI * bin * i as f32 / SIZE as f32).sin())
+            .collect::<Vec<_>>();
+        let samples = window.window(&samples);
+
+        (0..SIZE / 2)
+            .map(|k| {
+                let (mut re, mut im) = (0.0, 0.0);
+                for (i, e) in samples.iter().enumerate() {
+                    let a = 2.0 * PI * k as f32 * i as f32 / SIZE as f32;
+                    re += e * a.cos();
+                    im -= e * a.sin();
+                }
+                (re * re + im * im).sqrt()
+            })
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn test_flat_top_scalloping() {
+        let window = get_window("flattop", None).unwrap();
+        let centered = peak(window.as_ref(), 20.0);
+
+        for offset in [0.1, 0.25, 0.5] {
+            let between = peak(window.as_ref(), 20.0 + offset);
+            assert!((between / centered - 1.0).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn test_bessel_i0() {
+        assert_eq!(bessel_i0(0.0), 1.0);
+        assert!((bessel_i0(1.0) - 1.266_066).abs() < 1e-5);
+        assert!((bessel_i0(8.6) / 750.4612 - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_coherent_gain() {
+        let expected = SIZE as f32 / 2.0;
+        for name in ["square", "hann", "blackman"] {
+            let window = get_window(name, None).unwrap();
+            let gain = window.coherent_gain(SIZE);
+            let peak = peak(window.as_ref(), 20.0) / gain;
+            assert!((peak / expected - 1.0).abs() < 0.01, "{name}: {peak}");
+        }
+
+        let hann = get_window("hann", None).unwrap();
+        assert!((hann.coherent_gain(SIZE) - 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("hann").unwrap().name(), "hann");
+        assert_eq!(parse("kaiser:4.0").unwrap().name(), "kaiser");
+        assert!(parse("hann:4.0").is_err());
+        assert!(parse("kaiser:x").is_err());
+        assert!(parse("triangle").is_err());
+    }
+
+    #[test]
+    fn test_registry() {
+        for info in WINDOWS {
+            for name in info.aliases.iter().chain([&info.name]) {
+                let window = get_window(name, None).unwrap();
+                assert_eq!(window.name(), info.name);
+            }
+        }
+    }
+}
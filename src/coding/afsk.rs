@@ -0,0 +1,282 @@
+//! AFSK (audio frequency-shift keying) binary encoder and decoder.
+//! Ones are sent as a mark tone and zeros as a space tone, for example Bell 202 uses a 1200Hz mark and 2200Hz space at 1200 baud.
+//! Each symbol is framed like a serial port, with a start bit (space), the data bits (least significant first) and the stop bits (mark).
+//! The line idles on the mark tone.
+
+use std::collections::VecDeque;
+
+use crate::{
+    audio::{algorithms::goertzel_mag, tone::Tone},
+    misc::SampleRate,
+};
+
+/// The number of idle mark bits sent before and after the data.
+const IDLE_BITS: usize = 16;
+/// The amplitude below which the decoder considers the line silent.
+const AMPLITUDE_EPSILON: f32 = 0.01;
+/// How many times per bit the decoder checks the tone.
+const CHECKS_PER_BIT: f32 = 16.0;
+
+/// The tones and framing used by an AFSK modem.
+#[derive(Debug, Clone, Copy)]
+pub struct AfskConfig {
+    /// The frequency of a one bit (Hz).
+    pub mark: f32,
+    /// The frequency of a zero bit (Hz).
+    pub space: f32,
+    /// The number of bits sent per second.
+    pub baud: f32,
+    /// The number of data bits in each symbol.
+    pub data_bits: usize,
+    /// The length of the stop bit, in bits.
+    /// Rounded to the nearest half bit.
+    pub stop_bits: f32,
+}
+
+/// Encode binary data into AFSK tones.
+pub struct AfskEncoder {
+    // == Config ==
+    config: AfskConfig,
+    /// The bits are stored in half bit steps, so 1.5 stop bits can be sent.
+    samples_per_step: f32,
+
+    // == Internal ==
+    tone: Tone,
+    steps: Vec<bool>,
+    i: usize,
+}
+
+/// Decode AFSK tones into binary data.
+/// Uses the [Goertzel algorithm](https://en.wikipedia.org/wiki/Goertzel_algorithm) to tell mark and space apart.
+pub struct AfskDecoder {
+    // == Config ==
+    sample_rate: SampleRate,
+    config: AfskConfig,
+    samples_per_bit: f32,
+    check_interval: usize,
+
+    // == Internal ==
+    window: VecDeque<f32>,
+    /// Samples until the tone is checked again.
+    countdown: usize,
+    /// The bit from the last check.
+    current: bool,
+    last: bool,
+    state: DecodeState,
+    callback: Box<dyn FnMut(u8) + Send + Sync + 'static>,
+}
+
+/// The state of the [`AfskDecoder`].
+enum DecodeState {
+    /// Waiting for the mark to space transition of a start bit.
+    Idle,
+    /// Receiving a symbol.
+    Receiving {
+        /// Samples since the start bit was detected.
+        elapsed: usize,
+        /// The index of the next bit to sample, 0 is the start bit and `data_bits + 1` is the stop bit.
+        bit: usize,
+        symbol: u8,
+    },
+}
+
+impl AfskConfig {
+    /// The Bell 202 modem used by APRS and packet radio.
+    pub const BELL_202: Self = Self {
+        mark: 1200.0,
+        space: 2200.0,
+        baud: 1200.0,
+        data_bits: 8,
+        stop_bits: 1.0,
+    };
+}
+
+impl AfskEncoder {
+    /// Create a new encoder from a slice of symbols.
+    /// Only the lowest `data_bits` bits of each symbol are sent.
+    pub fn new(data: &[u8], config: AfskConfig, sample_rate: SampleRate) -> Self {
+        let stop_steps = (config.stop_bits * 2.0).round() as usize;
+
+        let mut steps = vec![true; IDLE_BITS * 2];
+        for symbol in data {
+            steps.extend([false; 2]);
+            for i in 0..config.data_bits {
+                steps.extend([symbol >> i & 1 == 1; 2]);
+            }
+            steps.extend(vec![true; stop_steps]);
+        }
+        steps.extend([true; IDLE_BITS * 2]);
+
+        Self {
+            config,
+            samples_per_step: sample_rate.output as f32 / config.baud / 2.0,
+            tone: Tone::new(config.mark, sample_rate),
+            steps,
+            i: 0,
+        }
+    }
+}
+
+impl Iterator for AfskEncoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bit = *self
+            .steps
+            .get((self.i as f32 / self.samples_per_step) as usize)?;
+        self.i += 1;
+
+        // The tone keeps its phase when the frequency changes, so the signal stays continuous
+        self.tone.set_frequency(match bit {
+            true => self.config.mark,
+            false => self.config.space,
+        });
+        self.tone.next()
+    }
+}
+
+impl AfskDecoder {
+    /// Create a new decoder, the callback will be called when a symbol is decoded.
+    pub fn new(
+        config: AfskConfig,
+        sample_rate: SampleRate,
+        callback: impl FnMut(u8) + Send + Sync + 'static,
+    ) -> Self {
+        let samples_per_bit = sample_rate.input as f32 / config.baud;
+        Self {
+            sample_rate,
+            config,
+            samples_per_bit,
+            check_interval: ((samples_per_bit / CHECKS_PER_BIT) as usize).max(1),
+            window: VecDeque::with_capacity(samples_per_bit as usize + 1),
+            countdown: 0,
+            current: true,
+            last: true,
+            state: DecodeState::Idle,
+            callback: Box::new(callback),
+        }
+    }
+
+    /// Add some samples to the decoder.
+    /// Will call the callback for each symbol decoded.
+    pub fn process(&mut self, data: &[f32]) {
+        for &sample in data {
+            self.window.push_back(sample);
+            if self.window.len() > self.samples_per_bit as usize {
+                self.window.pop_front();
+            }
+
+            // Running the Goertzel algorithm on every sample would be slow for low baud rates
+            if self.countdown == 0 {
+                self.current = self.bit();
+                self.countdown = self.check_interval;
+            }
+            self.countdown -= 1;
+
+            let bit = self.current;
+            self.state = match std::mem::replace(&mut self.state, DecodeState::Idle) {
+                // The window starts to be mostly space about half way through the start bit
+                DecodeState::Idle if self.last && !bit => DecodeState::Receiving {
+                    elapsed: 0,
+                    bit: 0,
+                    symbol: 0,
+                },
+                DecodeState::Idle => DecodeState::Idle,
+                DecodeState::Receiving {
+                    elapsed,
+                    bit: index,
+                    symbol,
+                } => self.receive(elapsed + 1, index, symbol, bit),
+            };
+            self.last = bit;
+        }
+    }
+
+    /// Handles a sample while receiving a symbol.
+    /// Bits are sampled when the window lines up with them, half a bit after the start bit was detected.
+    fn receive(&mut self, elapsed: usize, index: usize, symbol: u8, bit: bool) -> DecodeState {
+        let sample_point = self.samples_per_bit * (index as f32 + 0.5);
+        if (elapsed as f32) < sample_point {
+            return DecodeState::Receiving {
+                elapsed,
+                bit: index,
+                symbol,
+            };
+        }
+
+        match index {
+            // A start bit that isn't still a space was just noise
+            0 if bit => DecodeState::Idle,
+            // Only accept symbols with a valid stop bit
+            i if i > self.config.data_bits => {
+                if bit {
+                    (self.callback)(symbol);
+                }
+                DecodeState::Idle
+            }
+            0 => DecodeState::Receiving {
+                elapsed,
+                bit: 1,
+                symbol,
+            },
+            _ => DecodeState::Receiving {
+                elapsed,
+                bit: index + 1,
+                symbol: symbol | (bit as u8) << (index - 1),
+            },
+        }
+    }
+
+    /// Gets the bit the current window holds, true for mark and false for space.
+    /// A silent line is treated as mark, just like an idle one.
+    fn bit(&self) -> bool {
+        if self.window.len() < self.samples_per_bit as usize {
+            return true;
+        }
+
+        let (a, b) = self.window.as_slices();
+        let window = [a, b].concat();
+        let mark = goertzel_mag(self.config.mark, &window, self.sample_rate.input);
+        let space = goertzel_mag(self.config.space, &window, self.sample_rate.input);
+
+        if mark.max(space) < AMPLITUDE_EPSILON {
+            return true;
+        }
+
+        mark >= space
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use super::{AfskConfig, AfskDecoder, AfskEncoder};
+    use crate::misc::SampleRate;
+
+    fn round_trip(data: &[u8], sample_rate: u32) -> Vec<u8> {
+        let config = AfskConfig::BELL_202;
+        let sample_rate = SampleRate::from_hz(sample_rate);
+        let samples = AfskEncoder::new(data, config, sample_rate).collect::<Vec<_>>();
+
+        let out = Arc::new(Mutex::new(Vec::new()));
+        let out_ref = out.clone();
+        let mut decoder = AfskDecoder::new(config, sample_rate, move |x| {
+            out_ref.lock().unwrap().push(x)
+        });
+        for chunk in samples.chunks(512) {
+            decoder.process(chunk);
+        }
+
+        let out = out.lock().unwrap().clone();
+        out
+    }
+
+    #[test]
+    fn test_afsk_round_trip() {
+        let data = b"Hello World! \x00\xFF\x55\xAA";
+        for sample_rate in [48000, 44100, 22050] {
+            assert_eq!(round_trip(data, sample_rate), data, "{sample_rate}Hz");
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! CTCSS (Continuous Tone-Coded Squelch System) sub-audible tones.
+//! A low frequency tone is sent continuously under the audio, and receivers only open their squelch when they hear their tone.
+//!
+//! The decoder measures every standard tone with the Goertzel algorithm, and a tone is only detected if it is the strongest one.
+//! Adjacent tones are only a few Hz apart, so a long window is needed to tell them apart.
+
+use crate::{
+    audio::algorithms::{goertzel_mag_multi, SlidingWindow},
+    misc::SampleRate,
+};
+
+/// The standard CTCSS tones (Hz), in the order of their numeric codes starting at 1.
+pub const TONES: [f32; 38] = [
+    67.0, 71.9, 74.4, 77.0, 79.7, 82.5, 85.4, 88.5, 91.5, 94.8, 97.4, 100.0, 103.5, 107.2, 110.9,
+    114.8, 118.8, 123.0, 127.3, 131.8, 136.5, 141.3, 146.2, 151.4, 156.7, 162.2, 167.9, 173.8,
+    179.9, 186.2, 192.8, 203.5, 210.7, 218.1, 225.7, 233.6, 241.8, 250.3,
+];
+
+/// The length of the window each measurement is made over, in seconds.
+const WINDOW: f32 = 0.5;
+/// The time between measurements, in seconds.
+const HOP: f32 = 0.1;
+
+/// CTCSS tone decoder.
+pub struct CtcssDecoder {
+    // == Config ==
+    /// The lowest magnitude a tone is detected at.
+    threshold: f32,
+    sample_rate: u32,
+
+    // == Internal ==
+    window: SlidingWindow,
+    /// The index of the tone found by the last measurement.
+    tone: Option<usize>,
+}
+
+impl CtcssDecoder {
+    /// Create a new decoder.
+    pub fn new(sample_rate: SampleRate, threshold: f32) -> Self {
+        let sample_rate = sample_rate.input;
+        let window = SlidingWindow::new(
+            (sample_rate as f32 * WINDOW) as usize,
+            (sample_rate as f32 * HOP) as usize,
+        );
+
+        Self {
+            threshold,
+            sample_rate,
+
+            window,
+            tone: None,
+        }
+    }
+
+    /// Adds some mono samples to the decoder.
+    /// Returns true if a new measurement was made, which may have changed the [`CtcssDecoder::tone`].
+    pub fn process(&mut self, samples: &[f32]) -> bool {
+        let samples = match self.window.push(samples) {
+            Some(samples) => samples,
+            None => return false,
+        };
+
+        let magnitudes = goertzel_mag_multi(&TONES, samples, self.sample_rate);
+        self.tone = magnitudes
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .filter(|x| *x.1 >= self.threshold)
+            .map(|x| x.0);
+        true
+    }
+
+    /// Gets the frequency of the tone found by the last measurement, if there was one.
+    pub fn tone(&self) -> Option<f32> {
+        self.tone.map(|x| TONES[x])
+    }
+}
+
+/// Parses a CTCSS tone, either by its frequency like `100.0` or by its numeric code from 1 to 38.
+pub fn parse_tone(s: &str) -> anyhow::Result<f32> {
+    let val = s.trim().parse::<f32>()?;
+
+    if val.fract() == 0.0 && (1.0..=TONES.len() as f32).contains(&val) {
+        return Ok(TONES[val as usize - 1]);
+    }
+
+    TONES
+        .iter()
+        .copied()
+        .find(|x| (x - val).abs() < 0.05)
+        .ok_or_else(|| anyhow::anyhow!("{val} isn't a standard CTCSS tone or code (1-38)"))
+}
+
+#[cfg(test)]
+mod test {
+    use std::f32::consts::TAU;
+
+    use super::{parse_tone, CtcssDecoder, TONES};
+    use crate::misc::SampleRate;
+
+    #[test]
+    fn test_parse_tone() {
+        assert_eq!(parse_tone("1").unwrap(), 67.0);
+        assert_eq!(parse_tone("38").unwrap(), 250.3);
+        assert_eq!(parse_tone("88.5").unwrap(), 88.5);
+        assert_eq!(parse_tone("100").unwrap(), 100.0);
+
+        assert!(parse_tone("0").is_err());
+        assert!(parse_tone("89").is_err());
+        assert!(parse_tone("tone").is_err());
+    }
+
+    #[test]
+    fn test_decode() {
+        let sample_rate = SampleRate::from_hz(48000);
+        for &tone in [TONES[0], TONES[1], TONES[20], TONES[37]].iter() {
+            // A quiet tone under some louder audio
+            let samples = (0..48000)
+                .map(|i| {
+                    let t = i as f32 / 48000.0;
+                    (TAU * tone * t).sin() * 0.1 + (TAU * 1000.0 * t).sin() * 0.5
+                })
+                .collect::<Vec<_>>();
+
+            let mut decoder = CtcssDecoder::new(sample_rate, 0.05);
+            for chunk in samples.chunks(512) {
+                decoder.process(chunk);
+            }
+            assert_eq!(decoder.tone(), Some(tone));
+        }
+
+        let mut decoder = CtcssDecoder::new(sample_rate, 0.05);
+        decoder.process(&[0.0; 48000]);
+        assert_eq!(decoder.tone(), None);
+    }
+}
@@ -1,31 +1,71 @@
 //! Commodore Dataset like binary encoder / decoder.
+//! Every bit is sent as one cycle of a sine wave, a long cycle for a one and a short (and quieter) cycle for a zero.
+//! The decoder measures the time between the zero crossings at the start of each cycle to tell them apart.
 
 use std::f32::consts::PI;
 
 use bitvec::{order::Lsb0, vec::BitVec, view::BitView};
 
+use crate::misc::SampleRate;
+
+/// The frequency of the cycle used to send a one bit.
+const ONE_FREQUENCY: f32 = 720.0;
+/// The frequency of the cycle used to send a zero bit.
+const ZERO_FREQUENCY: f32 = 1440.0;
+/// Cycles shorter than this fraction of a zero cycle are ignored as noise.
+const MIN_CYCLE: f32 = 0.3;
+/// Cycles quieter than this are ignored, so silence and low noise aren't decoded as bits.
+const MIN_AMPLITUDE: f32 = 0.05;
+/// A transmission is over once there hasn't been a cycle for this many one cycles.
+const END_CYCLES: f32 = 4.0;
+
 /// Commodore Dataset like binary encoder.
 pub struct BinEncoder {
+    sample_rate: f32,
     data: BitVec<u8, Lsb0>,
     index: usize,
-    wave: f32,
+    /// How far through the current cycle the wave is, from 0 to 1.
+    phase: f32,
+}
+
+/// Commodore Dataset like binary decoder.
+pub struct BinDecoder {
+    // == Config ==
+    /// Cycles longer than this many samples are ones, shorter ones are zeros.
+    threshold: usize,
+    /// Cycles shorter than this many samples are ignored.
+    min_cycle: usize,
+    /// The number of samples without a cycle before the transmission is over.
+    end: usize,
+
+    // == Internal ==
+    /// The index of the current sample.
+    i: usize,
+    /// The sample the current cycle started at.
+    start: Option<usize>,
+    /// The sample the last bit was decoded at.
+    last_bit: usize,
+    /// The highest amplitude in the current cycle.
+    peak: f32,
+    last: f32,
+    data: BitVec<u8, Lsb0>,
 }
 
 impl BinEncoder {
     /// Create a new encoder from a slice of bytes.
-    pub fn _new(data: &[u8]) -> Self {
-        let mut out = BitVec::new();
-        data.iter().for_each(|x| out.extend(x.view_bits::<Lsb0>()));
-
-        Self {
-            data: out,
+    pub fn new(data: &[u8], sample_rate: SampleRate) -> Self {
+        let mut out = Self {
+            sample_rate: sample_rate.output as f32,
+            data: BitVec::new(),
             index: 0,
-            wave: 0.,
-        }
+            phase: 0.0,
+        };
+        out.add_data(data);
+        out
     }
 
     /// Add data to the encoder.
-    pub fn _add_data(&mut self, data: &[u8]) {
+    pub fn add_data(&mut self, data: &[u8]) {
         data.iter()
             .for_each(|x| self.data.extend(x.view_bits::<Lsb0>()));
     }
@@ -35,89 +75,119 @@ impl Iterator for BinEncoder {
     type Item = f32;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index + 1 >= self.data.len() {
-            return None;
-        }
+        // After the data, one extra zero cycle is sent, so the decoder can see where the last bit ends
+        let bit = match self.index {
+            i if i < self.data.len() => self.data[i],
+            i if i == self.data.len() => false,
+            _ => return None,
+        };
 
-        let bit = self.data[self.index];
-        self.wave += match bit {
-            true => 0.03,
-            false => 0.06,
+        let frequency = match bit {
+            true => ONE_FREQUENCY,
+            false => ZERO_FREQUENCY,
         };
+        self.phase += frequency / self.sample_rate;
 
-        if self.wave > 2. {
+        if self.phase >= 1.0 {
             self.index += 1;
-            self.wave = 0.03;
-        }
-
-        #[cfg(debug_assertions)]
-        if self.index % 1000 == 0 {
-            print!(
-                "\r{:.2}%",
-                self.index as f32 / self.data.len() as f32 * 100.0
-            );
-        }
-
-        let mut val = -(PI * self.wave).sin();
-        if !bit {
-            val /= 2.;
+            self.phase -= 1.0;
         }
 
-        Some(val)
+        // Each cycle starts by going negative, which is what the decoder looks for
+        let val = -(2.0 * PI * self.phase).sin();
+        Some(match bit {
+            true => val,
+            false => val / 2.0,
+        })
     }
 }
 
-/// Commodore Dataset like binary decoder.
-pub struct _BinDecoder {
-    i: usize,
-    start: usize,
-    last: Option<f32>,
-    pub data: BitVec<u8, Lsb0>,
-}
-
-impl _BinDecoder {
+impl BinDecoder {
     /// Create a new decoder.
-    pub fn _new() -> Self {
+    pub fn new(sample_rate: SampleRate) -> Self {
+        let sample_rate = sample_rate.input as f32;
+        let one = sample_rate / ONE_FREQUENCY;
+        let zero = sample_rate / ZERO_FREQUENCY;
+
         Self {
-            i: 1,
-            start: 0,
-            last: None,
+            threshold: ((one + zero) / 2.0) as usize,
+            min_cycle: (zero * MIN_CYCLE) as usize,
+            end: (one * END_CYCLES) as usize,
+
+            i: 0,
+            start: None,
+            last_bit: 0,
+            peak: 0.0,
+            last: 0.0,
             data: BitVec::new(),
         }
     }
 
+    /// Adds some samples to the decoder.
+    pub fn process(&mut self, samples: &[f32]) {
+        for &val in samples {
+            self.add(val);
+        }
+    }
+
     /// Adds a sample to the decoder.
-    pub fn _add(&mut self, mut val: f32) {
-        val += 0.1;
-        if self.last.is_none() {
-            self.last = Some(val);
+    /// A bit is decoded from the length of each cycle once the next one starts.
+    pub fn add(&mut self, val: f32) {
+        self.i += 1;
+        self.peak = self.peak.max(val.abs());
+        let crossing = val < 0.0 && self.last >= 0.0;
+        self.last = val;
+
+        if !crossing {
             return;
         }
 
-        if val < 0. && self.last.unwrap() >= 0. {
-            if self.i - self.start < 10 {
-                return;
+        match self.start {
+            // Too short to be a real cycle, so it is treated as part of the current one
+            Some(start) if self.i - start < self.min_cycle => return,
+            Some(start) if self.peak >= MIN_AMPLITUDE => {
+                self.data.push(self.i - start > self.threshold);
+                self.last_bit = self.i;
             }
-            self.data.push(self.i - self.start > 50);
-            self.start = self.i;
+            _ => {}
         }
 
-        self.i += 1;
-        self.last = Some(val);
-
-        println!(
-            "{}",
-            self.data
-                .clone()
-                .into_vec()
-                .iter()
-                .map(|x| format!("{x:b}"))
-                .collect::<String>()
-        );
+        self.start = Some(self.i);
+        self.peak = 0.0;
+    }
+
+    /// Checks if a transmission was received and has ended.
+    pub fn is_done(&self) -> bool {
+        !self.data.is_empty() && self.i - self.last_bit > self.end
     }
 
-    /// Gets the decoded data as a byte vec.
-    pub fn _done(self) -> Vec<u8> {
-        self.data.into_vec()
+    /// Takes the decoded data as a byte vec, clearing the decoder for the next transmission.
+    /// Any bits that don't make up a whole byte are dropped.
+    pub fn take(&mut self) -> Vec<u8> {
+        let mut data = std::mem::take(&mut self.data);
+        data.truncate(data.len() / 8 * 8);
+        data.into_vec()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BinDecoder, BinEncoder};
+    use crate::misc::SampleRate;
+
+    #[test]
+    fn test_round_trip() {
+        let data = b"Hello World\x00\xFF";
+        for sample_rate in [44100, 48000] {
+            let sample_rate = SampleRate::from_hz(sample_rate);
+            let mut samples = vec![0.0; 1000];
+            samples.extend(BinEncoder::new(data, sample_rate));
+            samples.extend(vec![0.0; 1000]);
+
+            let mut decoder = BinDecoder::new(sample_rate);
+            decoder.process(&samples);
+            assert!(decoder.is_done());
+            assert_eq!(decoder.take(), data);
+        }
     }
 }
@@ -1,39 +1,65 @@
 //! DTMF tone based binary encoder and decoder.
 //! The decoder is based on the [Goertzel algorithm](https://en.wikipedia.org/wiki/Goertzel_algorithm), and as I am writing this comment, over a month after implementing this, I don't remember how it works.
 
-use std::time::Instant;
+use std::sync::Arc;
 
 use bitvec::{order::Lsb0, vec::BitVec, view::BitView};
+use parking_lot::Mutex;
 
 use crate::{
-    audio::{algorithms::goertzel_mag, tone::Tone},
+    audio::{
+        algorithms::goertzel_mag_multi,
+        tone::Tone,
+        windows::{BoxedWindow, HannWindow},
+    },
     misc::SampleRate,
 };
 
 const COL: [f32; 4] = [1209.0, 1336.0, 1477.0, 1633.0];
 const ROW: [f32; 4] = [697.0, 770.0, 852.0, 941.0];
 const VAL: [u8; 16] = *b"123A456B789C*0#D";
-const MAGNITUDE_EPSILON: f32 = 0.05;
+/// The strongest row and column tones have to be above this amplitude to be decoded.
+const MAGNITUDE_EPSILON: f32 = 2e-4;
 const DATA_LENGTH: usize = 10;
+/// How long in milliseconds before the same character can be decoded again.
 const VALUE_INVALIDATE: usize = 1000;
+/// The number of samples given to the decoder at a time by [`decode`].
+const DECODE_CHUNK: usize = 512;
+
+/// The tone pairs used to send each DTMF character.
+#[derive(Debug, Clone)]
+pub struct DtmfConfig {
+    /// The low (row) frequencies.
+    pub row: [f32; 4],
+    /// The high (column) frequencies.
+    pub col: [f32; 4],
+    /// The character for each row and column pair, indexed by `row * 4 + col`.
+    pub values: [u8; 16],
+}
 
 /// Decode DTMF tones into binary data.
 /// Uses the [Goertzel algorithm](https://en.wikipedia.org/wiki/Goertzel_algorithm).
+/// Timing is measured in samples, so the decoder gives the same output no matter how fast the samples are given to it.
 pub struct DtmfDecoder {
     // == Config ==
     sample_rate: SampleRate,
+    config: DtmfConfig,
+    /// The window applied to each chunk before looking for tones.
+    window: Arc<BoxedWindow>,
 
     // == Internal ==
     data: Vec<u8>,
     last: Option<u8>,
-    last_timestamp: Instant,
-    callback: Box<dyn FnMut(u8) + Send + Sync + 'static>,
+    /// Number of samples since the last character was decoded.
+    elapsed: usize,
+    callback: Box<dyn FnMut(u8, f32) + Send + Sync + 'static>,
 }
 
 /// Encode binary data into DTMF tones.
 pub struct DtmfEncoder {
     // == Config ==
     sample_rate: SampleRate,
+    config: DtmfConfig,
     time: u32,
     sleep: u32,
 
@@ -45,14 +71,51 @@ pub struct DtmfEncoder {
     i: usize,
 }
 
+impl DtmfConfig {
+    /// The standard telephone keypad frequencies.
+    pub fn standard() -> Self {
+        Self {
+            row: ROW,
+            col: COL,
+            values: VAL,
+        }
+    }
+
+    /// Parses a comma-separated list of eight frequencies, the four row frequencies followed by the four column frequencies.
+    /// The standard character map is used.
+    pub fn parse(s: &str) -> anyhow::Result<Self> {
+        let freqs = s
+            .split(',')
+            .map(|x| x.trim().parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()?;
+        anyhow::ensure!(
+            freqs.len() == 8,
+            "Expected 8 frequencies (4 rows then 4 columns), got {}",
+            freqs.len()
+        );
+
+        let mut out = Self::standard();
+        out.row.copy_from_slice(&freqs[..4]);
+        out.col.copy_from_slice(&freqs[4..]);
+        Ok(out)
+    }
+
+    /// Gets the row and column index of a character.
+    fn position(&self, val: u8) -> Option<(usize, usize)> {
+        let i = self.values.iter().position(|&x| x == val)?;
+        Some((i / self.col.len(), i % self.col.len()))
+    }
+}
+
 impl DtmfEncoder {
     // 0-9, a, b, d, c, *, #
     /// Create a new encoder from a slice of bytes.
-    pub fn new(data: &[u8], sample_rate: SampleRate) -> Self {
+    pub fn new(data: &[u8], config: DtmfConfig, sample_rate: SampleRate) -> Self {
         Self {
             time: sample_rate.output / 2,
             sleep: sample_rate.output / 4,
             sample_rate,
+            config,
 
             low: Tone::new(0.0, sample_rate),
             high: Tone::new(0.0, sample_rate),
@@ -74,41 +137,58 @@ impl Iterator for DtmfEncoder {
 
         if self.i % self.time as usize == 0 {
             let val = self.data.get(self.i / self.time as usize)?;
-            let val = VAL.iter().enumerate().find(|x| x.1 == val).unwrap().0 as u8;
-            let col = val % COL.len() as u8;
-            let row = val / COL.len() as u8;
-            self.low = Tone::new(COL[col as usize], self.sample_rate);
-            self.high = Tone::new(ROW[row as usize], self.sample_rate);
+            let (row, col) = self.config.position(*val).unwrap();
+            self.low = Tone::new(self.config.col[col], self.sample_rate).amplitude(0.5);
+            self.high = Tone::new(self.config.row[row], self.sample_rate).amplitude(0.5);
             self.cooldown = self.sleep as usize;
         }
 
         self.i = self.i.wrapping_add(1);
-        let out = (self.low.next().unwrap() * 0.5) + (self.high.next().unwrap() * 0.5);
+        let out = self.low.next().unwrap() + self.high.next().unwrap();
         Some(out)
     }
 }
 
 impl DtmfDecoder {
-    /// Create a new decoder, the callback will be called when a byte is decoded.
-    pub fn new(sample_rate: SampleRate, callback: impl FnMut(u8) + Send + Sync + 'static) -> Self {
+    /// Create a new decoder, the callback will be called with the byte and its confidence when a byte is decoded.
+    pub fn new(
+        sample_rate: SampleRate,
+        config: DtmfConfig,
+        callback: impl FnMut(u8, f32) + Send + Sync + 'static,
+    ) -> Self {
         Self {
             sample_rate,
+            config,
+            window: Arc::new(Box::new(HannWindow)),
             data: Vec::with_capacity(DATA_LENGTH),
             callback: Box::new(callback),
-            last_timestamp: Instant::now(),
+            elapsed: 0,
             last: None,
         }
     }
 
+    /// Sets the window applied to each chunk of samples, defaults to a [`HannWindow`] like the `dtmf receive` command.
+    /// Tapered windows reduce how much energy from strong tones leaks into the other frequencies.
+    pub fn window(mut self, window: Arc<BoxedWindow>) -> Self {
+        self.window = window;
+        self
+    }
+
     /// Add some samples to the decoder.
     /// Will call the callback if a character is decoded.
+    ///
+    /// A character is only decoded once the last 10 chunks have all detected it, so a single noisy chunk is ignored.
+    /// The same character will not be decoded again until a second of samples has been processed since it was last decoded.
+    /// This is counted from the samples rather than the wall clock, so it works the same for live audio and audio already in memory.
     pub fn process(&mut self, data: &[f32]) {
-        let freqs = ROW
-            .iter()
-            .chain(COL.iter())
-            .map(|x| goertzel_mag(*x, data, self.sample_rate.input))
+        self.elapsed += data.len();
+        let freqs = [self.config.row, self.config.col].concat();
+        let gain = self.window.coherent_gain(data.len());
+        let freqs = goertzel_mag_multi(&freqs, &self.window.window(data), self.sample_rate.input)
+            .into_iter()
+            .map(|x| x / gain)
             .collect::<Vec<_>>();
-        let x = match frequencies_to_dtmf(&freqs) {
+        let (x, confidence) = match frequencies_to_dtmf_conf(&freqs, &self.config) {
             Some(i) => i,
             None => return,
         };
@@ -118,22 +198,51 @@ impl DtmfDecoder {
             self.data.remove(0);
         }
 
+        // Wait for a full buffer of matching chunks, so a single noisy chunk isn't decoded
+        if self.data.len() < DATA_LENGTH {
+            return;
+        }
+
         let first = self.data[0];
+        let elapsed_ms = self.elapsed * 1000 / self.sample_rate.input as usize;
         if self.data.iter().any(|x| *x != first)
-            || (Some(first) == self.last
-                && self.last_timestamp.elapsed().as_millis() <= VALUE_INVALIDATE as u128)
+            || (Some(first) == self.last && elapsed_ms <= VALUE_INVALIDATE)
         {
             return;
         }
 
-        self.last_timestamp = Instant::now();
-        (self.callback)(x);
+        self.elapsed = 0;
+        (self.callback)(x, confidence);
         self.last = Some(x);
     }
 }
 
-/// Converts a slice of frequencies to a DTMF characters from [`VAL`].
-pub fn frequencies_to_dtmf(freqs: &[f32]) -> Option<u8> {
+/// Decodes all the DTMF characters in a buffer of mono samples.
+/// This is a shortcut for running the samples through a [`DtmfDecoder`] and collecting its output, for when the audio is already in memory.
+/// Use the decoder directly to get the confidence of each character, or to decode a live stream.
+pub fn decode(samples: &[f32], sample_rate: SampleRate, config: DtmfConfig) -> String {
+    let out = Arc::new(Mutex::new(String::new()));
+    let out_ref = out.clone();
+    let mut decoder = DtmfDecoder::new(sample_rate, config, move |x, _| {
+        out_ref.lock().push(x as char)
+    });
+    for chunk in samples.chunks(DECODE_CHUNK) {
+        decoder.process(chunk);
+    }
+
+    let out = out.lock().to_owned();
+    out
+}
+
+/// Converts a slice of row then column frequency magnitudes to a DTMF character from the config.
+/// See [`frequencies_to_dtmf_conf`] to also get the confidence of the detection.
+pub fn frequencies_to_dtmf(freqs: &[f32], config: &DtmfConfig) -> Option<u8> {
+    frequencies_to_dtmf_conf(freqs, config).map(|x| x.0)
+}
+
+/// Converts a slice of row then column frequency magnitudes to a DTMF character from the config, along with a confidence value from 0 to 1.
+/// The confidence is based on how much stronger the strongest row and column frequencies are than the next-strongest ones.
+pub fn frequencies_to_dtmf_conf(freqs: &[f32], config: &DtmfConfig) -> Option<(u8, f32)> {
     let mut row = freqs[0..4].iter().enumerate().collect::<Vec<_>>();
     let mut col = freqs[4..8].iter().enumerate().collect::<Vec<_>>();
 
@@ -147,7 +256,13 @@ pub fn frequencies_to_dtmf(freqs: &[f32]) -> Option<u8> {
         return None;
     }
 
-    Some(VAL[row_max.0 * 4 + col_max.0])
+    let row_conf = 1.0 - row[row.len() - 2].1 / row_max.1;
+    let col_conf = 1.0 - col[col.len() - 2].1 / col_max.1;
+
+    Some((
+        config.values[row_max.0 * 4 + col_max.0],
+        row_conf.min(col_conf),
+    ))
 }
 
 /// Converts arbitrary binary data into a list of DTMF characters from [`VAL`] (0-15).
@@ -172,3 +287,162 @@ pub fn dtmf_to_bin(dtmf: &[u8]) -> Vec<u8> {
 
     bits.into_vec()
 }
+
+/// Calculates the CRC-16 (CCITT-FALSE) of the given data.
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = match crc & 0x8000 != 0 {
+                true => (crc << 1) ^ 0x1021,
+                false => crc << 1,
+            };
+        }
+    }
+
+    crc
+}
+
+/// Appends the CRC-16 of the data to the end of it.
+pub fn append_crc(data: &[u8]) -> Vec<u8> {
+    let mut out = data.to_vec();
+    out.extend_from_slice(&crc16(data).to_be_bytes());
+    out
+}
+
+/// Checks the CRC-16 at the end of the data, returning the payload if it matches.
+pub fn check_crc(data: &[u8]) -> Option<&[u8]> {
+    if data.len() < 2 {
+        return None;
+    }
+
+    let (payload, crc) = data.split_at(data.len() - 2);
+    (crc16(payload).to_be_bytes() == crc).then_some(payload)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{f32::consts::PI, sync::Arc};
+
+    use super::{
+        append_crc, bin_to_dtmf, check_crc, crc16, decode, dtmf_to_bin, frequencies_to_dtmf,
+        frequencies_to_dtmf_conf, DtmfConfig, DtmfDecoder, DtmfEncoder, DECODE_CHUNK,
+    };
+    use crate::{
+        audio::{tone::Tone, windows},
+        misc::{
+            test_util::{noise, Collector},
+            SampleRate,
+        },
+    };
+
+    #[test]
+    fn test_decode() {
+        let sample_rate = SampleRate::from_hz(48000);
+        let samples =
+            DtmfEncoder::new(b"A#1234#D", DtmfConfig::standard(), sample_rate).collect::<Vec<_>>();
+        assert_eq!(
+            decode(&samples, sample_rate, DtmfConfig::standard()),
+            "A#1234#D"
+        );
+    }
+
+    #[test]
+    fn test_decode_window() {
+        // A loud tone below the row frequencies and some noise, which leak into the rows without a window
+        let sample_rate = SampleRate::from_hz(48000);
+        let message = "A#0123456789ABCD*#D";
+        let encoded = DtmfEncoder::new(message.as_bytes(), DtmfConfig::standard(), sample_rate)
+            .collect::<Vec<_>>();
+        let samples = encoded
+            .iter()
+            .zip(noise(1, encoded.len()))
+            .enumerate()
+            .map(|(i, (x, noise))| x + (2.0 * PI * 400.0 * i as f32 / 48000.0).sin() + noise * 0.4)
+            .collect::<Vec<_>>();
+
+        let decode_with = |window: &str| {
+            let out = Collector::default();
+            let out_ref = out.clone();
+            let mut decoder = DtmfDecoder::new(sample_rate, DtmfConfig::standard(), move |x, _| {
+                out_ref.push(x as char)
+            })
+            .window(Arc::new(windows::parse(window).unwrap()));
+            samples
+                .chunks(DECODE_CHUNK)
+                .for_each(|x| decoder.process(x));
+
+            out.contents()
+        };
+
+        assert_ne!(decode_with("square"), message);
+        assert_eq!(decode_with("hann"), message);
+    }
+
+    #[test]
+    fn test_decoder_timing() {
+        // Holding a tone for 2.5 seconds decodes it again after each second of samples
+        let sample_rate = SampleRate::from_hz(48000);
+        let config = DtmfConfig::standard();
+        let mut low = Tone::new(config.row[0], sample_rate).amplitude(0.5);
+        let mut high = Tone::new(config.col[0], sample_rate).amplitude(0.5);
+        let samples = (0..120_000)
+            .map(|_| low.next().unwrap() + high.next().unwrap())
+            .collect::<Vec<_>>();
+
+        let decode_chunks = |samples: &[f32], chunk: usize| {
+            let out = Collector::default();
+            let out_ref = out.clone();
+            let mut decoder = DtmfDecoder::new(sample_rate, DtmfConfig::standard(), move |x, _| {
+                out_ref.push(x as char)
+            });
+            samples.chunks(chunk).for_each(|x| decoder.process(x));
+
+            out.contents()
+        };
+
+        // The repeats don't depend on how much audio is given at a time
+        assert_eq!(decode_chunks(&samples, DECODE_CHUNK), "111");
+        assert_eq!(decode_chunks(&samples, 1024), "111");
+
+        // Fewer than 10 chunks of a tone is treated as noise
+        assert_eq!(
+            decode_chunks(&samples[..DECODE_CHUNK * 9], DECODE_CHUNK),
+            ""
+        );
+    }
+
+    #[test]
+    fn test_frequencies_to_dtmf() {
+        let config = DtmfConfig::standard();
+        let freqs = [0.0, 0.8, 0.1, 0.0, 0.0, 0.1, 0.9, 0.0];
+        assert_eq!(frequencies_to_dtmf(&freqs, &config), Some(config.values[6]));
+
+        let (x, confidence) = frequencies_to_dtmf_conf(&freqs, &config).unwrap();
+        assert_eq!(x, config.values[6]);
+        assert!((confidence - 0.875).abs() < 1e-6);
+
+        assert_eq!(frequencies_to_dtmf(&[0.0; 8], &config), None);
+    }
+
+    #[test]
+    fn test_crc16() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+    }
+
+    #[test]
+    fn test_crc_round_trip() {
+        let payload = b"Hello World";
+        let dtmf = bin_to_dtmf(&append_crc(payload));
+        assert_eq!(check_crc(&dtmf_to_bin(&dtmf)), Some(&payload[..]));
+
+        // Swap one symbol for a different one, like a misheard tone
+        let mut corrupted = dtmf.clone();
+        corrupted[5] = match corrupted[5] {
+            b'0' => b'1',
+            _ => b'0',
+        };
+        assert_eq!(check_crc(&dtmf_to_bin(&corrupted)), None);
+    }
+}
@@ -0,0 +1,115 @@
+//! Text formats for binary data, so payloads that aren't text can be typed in and printed out.
+
+use clap::ValueEnum;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// How binary data is written as text.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataFormat {
+    /// The bytes of the text itself.
+    Utf8,
+    /// Two hex digits per byte, like `DEADBEEF`.
+    Hex,
+    /// Standard base64, with `=` padding.
+    Base64,
+}
+
+impl DataFormat {
+    /// Converts text in this format into bytes.
+    /// Whitespace is ignored in the hex and base64 formats.
+    pub fn decode(&self, text: &str) -> anyhow::Result<Vec<u8>> {
+        let digits = || text.chars().filter(|x| !x.is_whitespace());
+        match self {
+            Self::Utf8 => Ok(text.as_bytes().to_vec()),
+            Self::Hex => {
+                let digits = digits().collect::<Vec<_>>();
+                anyhow::ensure!(
+                    digits.len() % 2 == 0,
+                    "Hex data must have two digits per byte"
+                );
+                digits
+                    .chunks(2)
+                    .map(|x| {
+                        let byte = x.iter().collect::<String>();
+                        u8::from_str_radix(&byte, 16)
+                            .map_err(|_| anyhow::anyhow!("Invalid hex byte: {byte}"))
+                    })
+                    .collect()
+            }
+            Self::Base64 => {
+                let mut out = Vec::new();
+                let (mut bits, mut count) = (0_u32, 0);
+                for chr in digits().take_while(|&x| x != '=') {
+                    let value = BASE64
+                        .iter()
+                        .position(|&x| x as char == chr)
+                        .ok_or_else(|| anyhow::anyhow!("Invalid base64 character: {chr}"))?;
+
+                    bits = bits << 6 | value as u32;
+                    count += 6;
+                    if count >= 8 {
+                        count -= 8;
+                        out.push((bits >> count) as u8);
+                    }
+                }
+                Ok(out)
+            }
+        }
+    }
+
+    /// Converts bytes into text in this format.
+    /// Bytes that aren't valid UTF-8 are replaced in the UTF-8 format.
+    pub fn encode(&self, data: &[u8]) -> String {
+        match self {
+            Self::Utf8 => String::from_utf8_lossy(data).into_owned(),
+            Self::Hex => data.iter().map(|x| format!("{x:02X}")).collect(),
+            Self::Base64 => {
+                let mut out = String::new();
+                for chunk in data.chunks(3) {
+                    let bits = chunk
+                        .iter()
+                        .enumerate()
+                        .fold(0_u32, |acc, (i, &x)| acc | (x as u32) << (16 - i * 8));
+                    for i in 0..4 {
+                        match i <= chunk.len() {
+                            true => {
+                                out.push(BASE64[(bits >> (18 - i * 6)) as usize & 0x3F] as char)
+                            }
+                            false => out.push('='),
+                        }
+                    }
+                }
+                out
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DataFormat;
+
+    #[test]
+    fn test_hex() {
+        let data = DataFormat::Hex.decode("DEAD beef").unwrap();
+        assert_eq!(data, [0xDE, 0xAD, 0xBE, 0xEF]);
+        assert_eq!(DataFormat::Hex.encode(&data), "DEADBEEF");
+
+        assert!(DataFormat::Hex.decode("ABC").is_err());
+        assert!(DataFormat::Hex.decode("XY").is_err());
+    }
+
+    #[test]
+    fn test_base64() {
+        for (text, encoded) in [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v")] {
+            assert_eq!(DataFormat::Base64.encode(text.as_bytes()), encoded);
+            assert_eq!(DataFormat::Base64.decode(encoded).unwrap(), text.as_bytes());
+        }
+
+        let data = (0..=255).collect::<Vec<u8>>();
+        let encoded = DataFormat::Base64.encode(&data);
+        assert_eq!(DataFormat::Base64.decode(&encoded).unwrap(), data);
+        assert!(DataFormat::Base64.decode("Zm9v!").is_err());
+    }
+}
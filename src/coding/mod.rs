@@ -1,9 +1,19 @@
 //! Different encoding and decoding systems.
 //! Currently supported:
+//! - Bell 202 AFSK
+//! - CTCSS Tones
 //! - DTMF Tones
 //! - Morse Code
+//! - RTTY (Baudot)
 //! - Commodore Datasette
+//!
+//! The DTMF and Morse modules also have a `decode` function, for decoding audio that is already in memory without setting up a decoder and its callback.
+//! The [`format`] module converts binary payloads to and from text, like hex or base64.
 
+pub mod afsk;
+pub mod ctcss;
 pub mod dataset;
 pub mod dtmf;
+pub mod format;
 pub mod morse;
+pub mod rtty;
@@ -1,16 +1,37 @@
 //! Morse code encoding and decoding of text.
 
-use std::{collections::VecDeque, time::Instant};
+use std::{collections::VecDeque, sync::Arc};
+
+use parking_lot::Mutex;
 
 use crate::{
-    audio::{algorithms::goertzel_mag, tone::SmoothTone},
+    audio::{
+        algorithms::goertzel_mag,
+        tone::SmoothTone,
+        windows::{BoxedWindow, HannWindow},
+    },
     misc::SampleRate,
 };
 
-const MAGNITUDE_EPSILON: f32 = 6.0;
-// TODO: maybe use percentage of dit length instead of absolute value
-const DURATION_EPSILON: f32 = 1.5;
-const GAP_DURATION_EPSILON: f32 = 2.0;
+/// The tone threshold never goes below this amplitude, so digital silence isn't read as a tone.
+const MIN_AMPLITUDE: f32 = 1e-4;
+/// The default multiple of the noise floor a tone has to reach to be detected.
+const THRESHOLD_FACTOR: f32 = 6.0;
+/// How much of each new silent chunk is mixed into the noise floor estimate.
+const NOISE_SMOOTHING: f32 = 0.05;
+/// Tones longer than this many dits are read as a dah, shorter ones as a dit.
+const DAH_THRESHOLD: f32 = 2.0;
+/// Silences longer than this many dits end the current character.
+const LETTER_THRESHOLD: f32 = 2.0;
+/// Silences longer than this many dits are read as a space between words.
+const WORD_THRESHOLD: f32 = 5.0;
+/// After this many dits of silence the transmission is considered finished.
+const END_THRESHOLD: f32 = 20.0;
+/// The number of recent tone lengths used to estimate the dit length when using auto speed.
+const TONE_HISTORY: usize = 16;
+/// The number of chunks per dit that samples are split into by [`decode`].
+/// Too few and the start and end of each tone is blurred, too many and the tone detection gets noisy.
+const DECODE_CHUNKS_PER_DIT: u64 = 8;
 
 /// Encodes text into morse code.
 pub struct MorseEncoder {
@@ -22,21 +43,36 @@ pub struct MorseEncoder {
     state: EncodeState,
 }
 
+/// Decodes morse code from audio into text.
+/// Timing is measured in samples, so the decoder does not depend on when the audio callback happens to run.
 pub struct MorseDecoder {
     sample_rate: SampleRate,
-    dit_length: u64,
     frequency: f32,
-
+    threshold_factor: f32,
+    auto_speed: bool,
+    /// The window applied to each chunk before measuring the tone.
+    window: Arc<BoxedWindow>,
+
+    /// The length of a dit in samples.
+    /// Adapted to the received tones when using auto speed.
+    dit_samples: f32,
+    /// The lengths of recent tones, in samples.
+    tones: VecDeque<f32>,
+
+    /// Running average of the tone amplitude while no tone is present.
+    noise_floor: Option<f32>,
     data: Vec<Morse>,
     state: bool,
     sent_callback: bool,
-    last_timestamp: Instant,
+    /// Number of samples since the last tone state change
+    elapsed: usize,
     callback: Box<dyn Fn(&char) + Send + Sync + 'static>,
 }
 
 /// The different symbols that can be encoded in morse code.
 /// - A Dah is three times the length of a Dit.
-/// - A space is the length of a Dit.
+/// - The space between the elements of a character is the length of a Dit.
+/// - A gap between characters is the length of 3 Dits.
 /// - A word space is the length of a 7 Dits.
 ///
 /// (usually)
@@ -124,105 +160,196 @@ impl MorseEncoder {
 }
 
 impl MorseDecoder {
+    /// Create a new decoder listening for a tone at `frequency`.
+    /// The callback will be called with each decoded character, word spaces are sent as `' '`.
     pub fn new(
         sample_rate: SampleRate,
         frequency: f32,
         dit_length: u64,
         callback: impl Fn(&char) + Send + Sync + 'static,
     ) -> Self {
-        println!(
-            "SPACE LEN: {}s",
-            Morse::Space.duration(dit_length) as f32 / 1000.0
-        );
-        println!(
-            "GAP LEN: {}s",
-            Morse::Gap.duration(dit_length) as f32 / 1000.0
-        );
-        println!(
-            "DURATION EPSILON: {}s",
-            DURATION_EPSILON * (dit_length as f32 / 1000.0)
-        );
-
         Self {
             sample_rate,
             frequency,
-            dit_length,
+            threshold_factor: THRESHOLD_FACTOR,
+            auto_speed: false,
+            window: Arc::new(Box::new(HannWindow)),
+
+            dit_samples: sample_rate.input as f32 * dit_length as f32 / 1000.0,
+            tones: VecDeque::with_capacity(TONE_HISTORY + 1),
 
+            noise_floor: None,
             data: Vec::new(),
             sent_callback: true,
             state: false,
-            last_timestamp: Instant::now(),
+            elapsed: 0,
             callback: Box::new(callback),
         }
     }
 
+    /// Sets how many times louder than the noise floor a tone has to be to be detected.
+    pub fn threshold_factor(mut self, threshold_factor: f32) -> Self {
+        self.threshold_factor = threshold_factor;
+        self
+    }
+
+    /// Enables adapting the dit length to the speed of the received morse.
+    /// The dit length passed to [`MorseDecoder::new`] is used as the starting estimate.
+    pub fn auto_speed(mut self, auto_speed: bool) -> Self {
+        self.auto_speed = auto_speed;
+        self
+    }
+
+    /// Sets the window applied to each chunk of samples, defaults to a [`HannWindow`] like the `morse receive` command.
+    /// Tapered windows keep nearby signals from leaking into the tone frequency.
+    pub fn window(mut self, window: Arc<BoxedWindow>) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Add some samples to the decoder.
+    /// Will call the callback if a character is decoded.
     pub fn process(&mut self, data: &[f32]) {
-        let mag = goertzel_mag(self.frequency, data, self.sample_rate.input);
-        let val = mag > MAGNITUDE_EPSILON;
-
-        let last_timestamp = self.last_timestamp.elapsed().as_secs_f32();
-        if !val && !self.sent_callback && last_timestamp >= self.dit_length as f32 * 20.0 / 1000.0 {
-            // println!("END OF TRANSMISSION");
-            // println!("Got Char: {:?}", &self.data);
-            self.send_callback(&self.data);
-            self.sent_callback = true;
+        if data.is_empty() {
+            return;
         }
 
+        let amplitude = goertzel_mag(
+            self.frequency,
+            &self.window.window(data),
+            self.sample_rate.input,
+        ) / self.window.coherent_gain(data.len());
+        // The first chunk is only used to start the noise floor estimate
+        let val = self.noise_floor.is_some() && amplitude > self.threshold();
+        self.update_noise_floor(amplitude, val);
+
         if val != self.state {
-            let duration = self.last_timestamp.elapsed().as_secs_f32();
-            // println!("{} -> {} ({}s)", self.state, val, duration);
-            self.last_timestamp = Instant::now();
+            let elapsed = self.elapsed;
+            let dits = self.dits(elapsed);
+            let idle = self.sent_callback;
+            self.elapsed = 0;
             self.sent_callback = false;
             self.state = val;
 
+            // A tone just ended, so its length tells us if it was a dit or a dah
             if !val {
-                let morse = match Morse::from_duration(
-                    &[Morse::Dit, Morse::Dah],
-                    duration,
-                    self.dit_length,
-                    DURATION_EPSILON,
-                ) {
-                    Some(i) => i,
-                    None => return,
-                };
-
-                // println!(" > {duration}s | {morse:?}");
-                self.data.push(morse);
-                return;
-            }
+                if self.auto_speed {
+                    self.estimate_speed(elapsed);
+                }
 
-            if let Some(i) = Morse::from_duration(
-                &[Morse::Gap, Morse::Space],
-                duration,
-                self.dit_length,
-                GAP_DURATION_EPSILON,
-            ) {
-                // println!("\\ {duration}s | {i:?}");
-                if i == Morse::Space {
+                self.data.push(match self.dits(elapsed) < DAH_THRESHOLD {
+                    true => Morse::Dit,
+                    false => Morse::Dah,
+                });
+            }
+            // A tone just started, so the silence before it separates elements, letters or words.
+            // If the decoder was idle the silence is just the time before the transmission started.
+            else if !idle && dits >= LETTER_THRESHOLD {
+                self.flush();
+                if dits >= WORD_THRESHOLD {
                     (self.callback)(&' ');
-                    self.data.clear();
-                    return;
                 }
-
-                self.send_callback(&self.data);
-                self.data.clear();
-                return;
             }
+        }
 
-            // println!("\\ DELAY {duration}s");
+        self.elapsed += data.len();
+        if !self.state && !self.sent_callback && self.dits(self.elapsed) >= END_THRESHOLD {
+            self.flush();
+            self.sent_callback = true;
         }
     }
 
+    /// Checks if the decoder is idle, meaning the last transmission has finished.
     pub fn is_idle(&self) -> bool {
         self.sent_callback
     }
 
-    fn send_callback(&self, morse: &[Morse]) {
-        let chr = morse_decode(morse).unwrap_or('\0');
-        (self.callback)(&chr);
+    /// Gets the amplitude a tone has to be above to be detected.
+    fn threshold(&self) -> f32 {
+        (self.noise_floor.unwrap_or(0.0) * self.threshold_factor).max(MIN_AMPLITUDE)
+    }
+
+    /// Updates the noise floor estimate with the amplitude of a chunk.
+    /// The estimate is only averaged while there is no tone.
+    /// If the decoder starts in the middle of a tone, the estimate will fall back down during the next silence.
+    fn update_noise_floor(&mut self, amplitude: f32, tone: bool) {
+        self.noise_floor = match self.noise_floor {
+            None => Some(amplitude),
+            Some(noise) if !tone => Some(noise + (amplitude - noise) * NOISE_SMOOTHING),
+            Some(noise) => Some(noise),
+        };
+    }
+
+    /// Updates the dit length estimate with the length of a tone.
+    /// If the recent tones are a mix of dits and dahs, they are split into two groups at the geometric mean of the shortest and longest,
+    /// and the dit length is the average of the dits and a third of the dahs.
+    /// Otherwise they are read as all dits or all dahs, depending on which is closer to the current estimate.
+    fn estimate_speed(&mut self, samples: usize) {
+        self.tones.push_back(samples as f32);
+        if self.tones.len() > TONE_HISTORY {
+            self.tones.pop_front();
+        }
+
+        let min = self.tones.iter().copied().fold(f32::MAX, f32::min);
+        let max = self.tones.iter().copied().fold(0.0, f32::max);
+        let count = self.tones.len() as f32;
+
+        self.dit_samples = match max >= min * DAH_THRESHOLD {
+            true => {
+                let split = (min * max).sqrt();
+                let total = self.tones.iter().map(|&x| match x < split {
+                    true => x,
+                    false => x / 3.0,
+                });
+                total.sum::<f32>() / count
+            }
+            false => {
+                let avg = self.tones.iter().sum::<f32>() / count;
+                match avg < self.dit_samples * DAH_THRESHOLD {
+                    true => avg,
+                    false => avg / 3.0,
+                }
+            }
+        };
+    }
+
+    /// Converts a number of samples into a number of dits.
+    fn dits(&self, samples: usize) -> f32 {
+        samples as f32 / self.dit_samples
+    }
+
+    /// Decodes the buffered symbols and sends the character to the callback.
+    /// Patterns that are not valid morse code are dropped.
+    fn flush(&mut self) {
+        if let Some(chr) = morse_to_char(&self.data) {
+            (self.callback)(&chr);
+        }
+        self.data.clear();
     }
 }
 
+/// Decodes all the morse code in a buffer of mono samples, with the tone at `frequency` and a dit length in milliseconds.
+/// This is a shortcut for running the samples through a [`MorseDecoder`] and collecting its output, for when the audio is already in memory.
+/// A character still being received at the end of the samples is decoded too, even if the transmission didn't finish.
+pub fn decode(samples: &[f32], sample_rate: SampleRate, frequency: f32, dit_length: u64) -> String {
+    let out = Arc::new(Mutex::new(String::new()));
+    let out_ref = out.clone();
+    let mut decoder = MorseDecoder::new(sample_rate, frequency, dit_length, move |c| {
+        out_ref.lock().push(*c)
+    });
+    let chunk = sample_rate.input as u64 * dit_length / 1000 / DECODE_CHUNKS_PER_DIT;
+    for chunk in samples.chunks(chunk.max(1) as usize) {
+        decoder.process(chunk);
+    }
+
+    if !decoder.is_idle() {
+        decoder.flush();
+    }
+
+    let out = out.lock().to_owned();
+    out
+}
+
 impl Iterator for MorseEncoder {
     type Item = f32;
 
@@ -242,15 +369,21 @@ impl Iterator for MorseEncoder {
             EncodeState::Sending(s) => s,
         };
 
+        // Elements within a character are separated by a dit of silence.
+        // Gaps and spaces already include the silence they need.
         if sending.time == 0 {
-            self.state =
-                EncodeState::Waiting(self.dit_length * self.sample_rate.output as u64 / 1000);
+            let element = sending.data.is_element();
+            let wait = match element && self.data.front().is_some_and(Morse::is_element) {
+                true => self.dit_length * self.sample_rate.output as u64 / 1000,
+                false => 0,
+            };
+            self.state = EncodeState::Waiting(wait);
             return Some(0.0);
         }
 
         sending.time -= 1;
         let out = match sending.data {
-            Morse::Dit | Morse::Dah => self.tone.next().unwrap(),
+            Morse::Dit | Morse::Dah => self.tone.next().unwrap_or(0.0),
             Morse::Gap | Morse::Space => 0.0,
         };
 
@@ -261,13 +394,39 @@ impl Iterator for MorseEncoder {
 impl Morse {
     /// Converts a string into a vector of morse code symbols.
     /// Will return an error if the string contains invalid characters.
+    /// Prosigns can be written in brackets (like `[SK]`), their letters will be sent without gaps between them.
     fn from_str(s: &str) -> anyhow::Result<Vec<Self>> {
         let mut result = Vec::new();
+        let mut prosign = false;
         for c in s.chars() {
+            match c {
+                '[' if !prosign => {
+                    prosign = true;
+                    continue;
+                }
+                ']' if prosign => {
+                    prosign = false;
+                    if result.last().is_some_and(Morse::is_element) {
+                        result.push(Morse::Gap);
+                    }
+                    continue;
+                }
+                ' ' if prosign => anyhow::bail!("Spaces are not allowed in prosigns"),
+                _ => {}
+            }
+
+            // A word space replaces the gap after the previous character
+            if c == ' ' {
+                if result.last() == Some(&Morse::Gap) {
+                    result.pop();
+                }
+                result.push(Morse::Space);
+                continue;
+            }
+
             let index = match c.to_ascii_uppercase() {
                 e @ 'A'..='Z' => e as u8 - b'A',
                 e @ '0'..='9' => e as u8 - b'0' + 26,
-                ' ' => 56,
                 '.' => 36,
                 ',' => 37,
                 '?' => 38,
@@ -292,12 +451,23 @@ impl Morse {
             };
 
             result.extend_from_slice(MORSE_ENCODING[index as usize].1);
-            result.push(Morse::Gap);
+            if !prosign {
+                result.push(Morse::Gap);
+            }
+        }
+
+        if prosign {
+            anyhow::bail!("Unterminated prosign, missing `]`");
         }
 
         Ok(result)
     }
 
+    /// Checks if the symbol is a dit or dah, rather than some kind of silence.
+    fn is_element(&self) -> bool {
+        matches!(self, Self::Dit | Self::Dah)
+    }
+
     fn char_repr(&self) -> &str {
         match self {
             Self::Dit => ".",
@@ -315,30 +485,14 @@ impl Morse {
             Self::Space => dit_length * 7,
         }
     }
-
-    fn from_duration(
-        options: &[Self],
-        duration: f32,
-        dit_length: u64,
-        duration_epsilon: f32,
-    ) -> Option<Self> {
-        let epsilon = dit_length as f32 / 1000. * duration_epsilon;
-        for &i in options.iter() {
-            let delta = i.duration(dit_length) as f32 / 1000. - duration;
-            if delta.abs() < epsilon {
-                return Some(i);
-            }
-        }
-
-        None
-    }
 }
 
 fn morse_str(bits: &[Morse]) -> String {
     bits.iter().map(|i| i.char_repr()).collect()
 }
 
-fn morse_decode(data: &[Morse]) -> Option<char> {
+/// Looks up the character a sequence of dits and dahs represents.
+fn morse_to_char(data: &[Morse]) -> Option<char> {
     MORSE_ENCODING.iter().find(|(_, m)| m == &data).map(|x| x.0)
 }
 
@@ -350,13 +504,13 @@ const MORSE_ENCODING: [(char, &[Morse]); 57] = [
     ('C', &[Dah, Dit, Dah, Dit]),
     ('D', &[Dah, Dit, Dit]),
     ('E', &[Dit]),
-    ('F', &[Dit, Dit, Dah]),
+    ('F', &[Dit, Dit, Dah, Dit]),
     ('G', &[Dah, Dah, Dit]),
     ('H', &[Dit, Dit, Dit, Dit]),
     ('I', &[Dit, Dit]),
     ('J', &[Dit, Dah, Dah, Dah]),
     ('K', &[Dah, Dit, Dah]),
-    ('L', &[Dit, Dah, Dit]),
+    ('L', &[Dit, Dah, Dit, Dit]),
     ('M', &[Dah, Dah]),
     ('N', &[Dah, Dit]),
     ('O', &[Dah, Dah, Dah]),
@@ -368,38 +522,154 @@ const MORSE_ENCODING: [(char, &[Morse]); 57] = [
     ('U', &[Dit, Dit, Dah]),
     ('V', &[Dit, Dit, Dit, Dah]),
     ('W', &[Dit, Dah, Dah]),
-    ('X', &[Dah, Dit, Dit]),
+    ('X', &[Dah, Dit, Dit, Dah]),
     ('Y', &[Dah, Dit, Dah, Dah]),
-    ('Z', &[Dah, Dah, Dit]),
+    ('Z', &[Dah, Dah, Dit, Dit]),
     ('0', &[Dah, Dah, Dah, Dah, Dah]),
     ('1', &[Dit, Dah, Dah, Dah, Dah]),
     ('2', &[Dit, Dit, Dah, Dah, Dah]),
     ('3', &[Dit, Dit, Dit, Dah, Dah]),
     ('4', &[Dit, Dit, Dit, Dit, Dah]),
-    ('5', &[Dit, Dit, Dit, Dit]),
+    ('5', &[Dit, Dit, Dit, Dit, Dit]),
     ('6', &[Dah, Dit, Dit, Dit, Dit]),
     ('7', &[Dah, Dah, Dit, Dit, Dit]),
     ('8', &[Dah, Dah, Dah, Dit, Dit]),
-    ('9', &[Dah, Dah, Dah, Dah]),
+    ('9', &[Dah, Dah, Dah, Dah, Dit]),
     ('.', &[Dit, Dah, Dit, Dah, Dit, Dah]),
     (',', &[Dah, Dah, Dit, Dit, Dah, Dah]),
     ('?', &[Dit, Dit, Dah, Dah, Dit, Dit]),
     ('\'', &[Dit, Dah, Dah, Dah, Dah, Dit]),
     ('!', &[Dah, Dit, Dah, Dit, Dah, Dah]),
-    ('/', &[Dah, Dit, Dit, Dah]),
+    ('/', &[Dah, Dit, Dit, Dah, Dit]),
     ('(', &[Dah, Dit, Dah, Dah, Dit]),
     (')', &[Dah, Dit, Dah, Dah, Dit, Dah]),
     ('&', &[Dit, Dah, Dit, Dit, Dit]),
     (':', &[Dah, Dah, Dah, Dit, Dit, Dit]),
     (';', &[Dah, Dit, Dah, Dit, Dah, Dit]),
-    ('=', &[Dah, Dit, Dit, Dit]),
+    ('=', &[Dah, Dit, Dit, Dit, Dah]),
     ('+', &[Dit, Dah, Dit, Dah, Dit]),
     ('-', &[Dah, Dit, Dit, Dit, Dit, Dah]),
     ('_', &[Dit, Dit, Dah, Dah, Dit, Dah]),
     ('"', &[Dit, Dah, Dit, Dit, Dah, Dit]),
     ('$', &[Dit, Dit, Dit, Dah, Dit, Dit, Dah]),
-    ('@', &[Dit, Dah, Dah, Dit, Dah]),
+    ('@', &[Dit, Dah, Dah, Dit, Dah, Dit]),
     ('¿', &[Dit, Dit, Dah, Dit, Dah]),
-    ('¡', &[Dah, Dah, Dit, Dit, Dit]),
+    ('¡', &[Dah, Dah, Dit, Dit, Dit, Dah]),
     (' ', &[Space]),
 ];
+
+#[cfg(test)]
+mod test {
+    use super::{morse_str, Morse, MorseDecoder, MorseEncoder};
+    use crate::misc::{
+        test_util::{noise, Collector},
+        SampleRate,
+    };
+
+    const SAMPLE_RATE: u32 = 8000;
+    const FREQUENCY: f32 = 1000.0;
+    const DIT: u64 = 60;
+
+    /// Encodes `text` into samples at the given dit length, with no silence around it.
+    fn tones(text: &str, dit: u64) -> Vec<f32> {
+        let sample_rate = SampleRate::from_hz(SAMPLE_RATE);
+        let mut encoder = MorseEncoder::new(sample_rate, FREQUENCY, dit);
+        encoder.add_data(text).unwrap();
+
+        let mut samples = Vec::new();
+        while !encoder.is_idle() {
+            samples.push(encoder.next().unwrap());
+        }
+        samples
+    }
+
+    /// Encodes `text` into samples, with half a second of silence before and two seconds after.
+    /// The silence before lets the decoder find the noise floor.
+    fn encode(text: &str) -> Vec<f32> {
+        let mut samples = vec![0.0; SAMPLE_RATE as usize / 2];
+        samples.extend(tones(text, DIT));
+        samples.extend(vec![0.0; SAMPLE_RATE as usize * 2]);
+        samples
+    }
+
+    /// Runs samples through a decoder, returning the decoded text.
+    fn decode_with(samples: &[f32], dit: u64, auto_speed: bool) -> String {
+        let sample_rate = SampleRate::from_hz(SAMPLE_RATE);
+        let out = Collector::default();
+        let out_ref = out.clone();
+        let mut decoder = MorseDecoder::new(sample_rate, FREQUENCY, dit, move |c| out_ref.push(*c))
+            .auto_speed(auto_speed);
+        for chunk in samples.chunks(64) {
+            decoder.process(chunk);
+        }
+
+        assert!(decoder.is_idle());
+        out.contents()
+    }
+
+    /// Runs samples through a decoder with the same dit length as the encoder.
+    fn decode(samples: &[f32]) -> String {
+        decode_with(samples, DIT, false)
+    }
+
+    /// Encodes `text`, then runs the samples back through the decoder.
+    fn round_trip(text: &str) -> String {
+        decode(&encode(text))
+    }
+
+    #[test]
+    fn test_morse_round_trip() {
+        assert_eq!(round_trip("SOS"), "SOS");
+        assert_eq!(round_trip("hello world"), "HELLO WORLD");
+        assert_eq!(round_trip("CQ DE N0CALL 5 9"), "CQ DE N0CALL 5 9");
+    }
+
+    #[test]
+    fn test_morse_decode() {
+        let sample_rate = SampleRate::from_hz(SAMPLE_RATE);
+        let mut samples = vec![0.0; SAMPLE_RATE as usize / 2];
+        samples.extend(tones("CQ DE N0CALL", DIT));
+
+        // The last character is still decoded without the silence after it
+        let out = super::decode(&samples, sample_rate, FREQUENCY, DIT);
+        assert_eq!(out, "CQ DE N0CALL");
+    }
+
+    #[test]
+    fn test_morse_weak_signal() {
+        // A quiet tone over some noise, which a fixed threshold would have to be tuned for
+        let tones = encode("CQ CQ");
+        let samples = tones
+            .iter()
+            .zip(noise(1, tones.len()))
+            .map(|(x, noise)| x * 0.01 + noise * 0.004)
+            .collect::<Vec<_>>();
+
+        assert_eq!(decode(&samples), "CQ CQ");
+    }
+
+    #[test]
+    fn test_morse_auto_speed() {
+        // The sender speeds up then slows down, and the decoder starts with the wrong dit length
+        let gap = vec![0.0; SAMPLE_RATE as usize * 7 * 50 / 1000];
+        let mut samples = vec![0.0; SAMPLE_RATE as usize / 2];
+        for dit in [60, 45, 70] {
+            samples.extend(tones("PARIS", dit));
+            samples.extend(&gap);
+        }
+        samples.extend(vec![0.0; SAMPLE_RATE as usize * 4]);
+
+        assert_ne!(decode_with(&samples, 100, false), "PARIS PARIS PARIS");
+        assert_eq!(decode_with(&samples, 100, true), "PARIS PARIS PARIS");
+    }
+
+    #[test]
+    fn test_morse_prosign() {
+        let prosign = morse_str(&Morse::from_str("[SK]").unwrap());
+        let letters = morse_str(&Morse::from_str("S K").unwrap());
+
+        assert_eq!(prosign, "...-.- ");
+        assert_eq!(letters, "...  -.- ");
+        assert!(Morse::from_str("[SK").is_err());
+    }
+}
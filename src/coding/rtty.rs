@@ -0,0 +1,140 @@
+//! RTTY text encoding, using the 5-bit [Baudot (ITA2)](https://en.wikipedia.org/wiki/Baudot_code) code.
+//! The symbols are sent with [AFSK](super::afsk), normally with a 170Hz shift at 45.45 baud.
+//!
+//! ITA2 only has 32 codes, so there are two sets of characters (letters and figures).
+//! The LTRS and FIGS codes switch between them, and stay in effect until the other is sent.
+
+use super::afsk::AfskConfig;
+
+/// Switches to the letters set.
+const LTRS: u8 = 0x1F;
+/// Switches to the figures set.
+const FIGS: u8 = 0x1B;
+
+/// The letters set, indexed by code.
+/// Zero is used for codes without a character.
+const LETTERS: [u8; 32] = *b"\0E\nA SIU\rDRJNFCKTZLWHYPQOBG\0MXV\0";
+/// The figures set, indexed by code.
+/// Zero is used for codes without a character.
+const FIGURES: [u8; 32] = *b"\x003\n- '87\r\x054\x07,!:(5+)2\x006019?&\0./=\0";
+
+/// Creates an [`AfskConfig`] for RTTY.
+/// The space tone is `shift` Hz above the mark tone, and symbols end with 1.5 stop bits.
+pub fn config(mark: f32, shift: f32, baud: f32) -> AfskConfig {
+    AfskConfig {
+        mark,
+        space: mark + shift,
+        baud,
+        data_bits: 5,
+        stop_bits: 1.5,
+    }
+}
+
+/// Converts text to Baudot codes, adding LTRS and FIGS shifts where needed.
+/// Text is converted to uppercase, new lines are sent as CR LF, and characters that can't be sent are skipped.
+pub fn encode(text: &str) -> Vec<u8> {
+    // Start with a shift, so the receiver is in a known state
+    let mut out = vec![LTRS];
+    let mut figures = false;
+
+    for chr in text.to_ascii_uppercase().bytes() {
+        if chr == b'\n' {
+            out.extend(code(&LETTERS, b'\r'));
+        }
+
+        // Characters in both sets (space, CR and LF) don't need a shift
+        if let Some(i) = code(&LETTERS, chr).filter(|_| !figures) {
+            out.push(i);
+        } else if let Some(i) = code(&FIGURES, chr).filter(|_| figures) {
+            out.push(i);
+        } else if let Some(i) = code(&LETTERS, chr) {
+            out.extend([LTRS, i]);
+            figures = false;
+        } else if let Some(i) = code(&FIGURES, chr) {
+            out.extend([FIGS, i]);
+            figures = true;
+        }
+    }
+
+    out
+}
+
+/// Converts Baudot codes back to text, keeping track of the shift state.
+#[derive(Default)]
+pub struct BaudotDecoder {
+    figures: bool,
+}
+
+impl BaudotDecoder {
+    /// Decodes a single code.
+    /// Returns None for shifts and codes without a character.
+    pub fn decode(&mut self, code: u8) -> Option<char> {
+        match code & 0x1F {
+            LTRS => self.figures = false,
+            FIGS => self.figures = true,
+            i => {
+                let set = match self.figures {
+                    true => &FIGURES,
+                    false => &LETTERS,
+                };
+                return Some(set[i as usize] as char).filter(|&x| x != '\0');
+            }
+        }
+
+        None
+    }
+}
+
+/// Finds the code of a character in a character set.
+fn code(set: &[u8; 32], chr: u8) -> Option<u8> {
+    if chr == 0 {
+        return None;
+    }
+
+    set.iter().position(|&x| x == chr).map(|x| x as u8)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use super::{config, encode, BaudotDecoder, FIGS, LTRS};
+    use crate::{
+        coding::afsk::{AfskDecoder, AfskEncoder},
+        misc::SampleRate,
+    };
+
+    fn decode(codes: &[u8]) -> String {
+        let mut decoder = BaudotDecoder::default();
+        codes.iter().filter_map(|&x| decoder.decode(x)).collect()
+    }
+
+    #[test]
+    fn test_baudot_shifts() {
+        let codes = encode("CQ 73");
+        assert_eq!(codes, [LTRS, 0x0E, 0x17, 0x04, FIGS, 0x07, 0x01]);
+        assert_eq!(decode(&codes), "CQ 73");
+
+        assert_eq!(decode(&encode("de n0call, 5nn?\n")), "DE N0CALL, 5NN?\r\n");
+    }
+
+    #[test]
+    fn test_rtty_round_trip() {
+        let text = "RYRY THE QUICK BROWN FOX 1234567890";
+        let config = config(2125.0, 170.0, 45.45);
+        let sample_rate = SampleRate::from_hz(48000);
+        let samples = AfskEncoder::new(&encode(text), config, sample_rate).collect::<Vec<_>>();
+
+        let out = Arc::new(Mutex::new(String::new()));
+        let out_ref = out.clone();
+        let mut baudot = BaudotDecoder::default();
+        let mut decoder = AfskDecoder::new(config, sample_rate, move |x| {
+            out_ref.lock().unwrap().extend(baudot.decode(x))
+        });
+        for chunk in samples.chunks(512) {
+            decoder.process(chunk);
+        }
+
+        assert_eq!(*out.lock().unwrap(), text);
+    }
+}
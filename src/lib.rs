@@ -0,0 +1,8 @@
+//! The audio processing and encoding / decoding systems behind radio-data, for use in other projects.
+//! - [`audio`]: Tones, window functions, the Goertzel algorithm and WAV files.
+//! - [`coding`]: Encoders and decoders for DTMF, Morse code, AFSK, RTTY and more.
+//! - [`misc`]: Small utilities used by the rest of the crate.
+
+pub mod audio;
+pub mod coding;
+pub mod misc;
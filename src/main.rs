@@ -1,31 +1,64 @@
 //! Command-line ham radio utilities.
 
+use std::{borrow::Cow, path::PathBuf};
+
+use clap::ArgMatches;
 use cpal::traits::{DeviceTrait, StreamTrait};
+use radio_data::{audio, coding, misc};
 
-use crate::audio::devices::get_devices;
+use crate::{
+    audio::{
+        agc::Agc,
+        algorithms::DcBlocker,
+        devices::get_devices,
+        tone::{Tone, Waveform},
+        wav::{WavRecorder, WavSource},
+    },
+    misc::SampleRate,
+};
 
 mod args;
-pub mod audio;
-mod coding;
-mod misc;
 mod modules;
 
+/// The cutoff frequency of the `--dc-block` filter in Hz.
+const DC_BLOCK_CUTOFF: f32 = 10.0;
+
 fn main() {
     // Get and parse args
     let args = args::parse_args();
-    let devices = get_devices(&args);
+
+    // Tones are written straight to a file, so no audio devices are opened
+    if let Some(("tone", m)) = args.subcommand() {
+        write_tone(m);
+        return;
+    }
+
+    let mut devices = get_devices(&args);
+
+    // When reading from a WAV file, the input config is replaced with the file's format
+    let wav_source = args.get_one::<PathBuf>("wav-in").map(|path| {
+        let source =
+            WavSource::open(path, args.get_flag("wav-loop")).expect("Failed to open WAV file");
+        devices.input_config = source.config();
+        (path, source)
+    });
+
+    let input_name = match &wav_source {
+        Some((path, _)) => path.display().to_string(),
+        None => devices.input_device.name().unwrap(),
+    };
 
     // Get module
     let module = args::get_module(
         &args,
+        input_name.clone(),
         devices.input_config.clone(),
         devices.output_config.clone(),
     );
     println!("[*] Running module `{}`", module.name());
 
     println!(
-        "[*] Input  hooked into `{}` ({}) [{}]",
-        devices.input_device.name().unwrap(),
+        "[*] Input  hooked into `{input_name}` ({}) [{}]",
         devices.input_config.sample_rate().0,
         devices.input_config.channels()
     );
@@ -36,6 +69,15 @@ fn main() {
         devices.output_config.channels()
     );
 
+    // Optionally record the output to a WAV file
+    let mut recorder = args.get_one::<PathBuf>("wav-out").map(|path| {
+        println!("[*] Recording output to `{}`", path.display());
+        WavRecorder::create(path, &devices.output_config).expect("Failed to create WAV file")
+    });
+
+    // Ctrl-C stops the program the same way a finished module does, so the recording is still finalized
+    ctrlc::set_handler(modules::stop).expect("Error setting Ctrl-C handler");
+
     // Init module and IO streams
     module.init();
     let module_ref = module.clone();
@@ -44,27 +86,111 @@ fn main() {
         .build_output_stream(
             &devices.output_config.into(),
             move |data: &mut [f32], info: &cpal::OutputCallbackInfo| {
-                module_ref.output_raw(data, info, devices.output_gain)
+                module_ref.output_raw(data, info, devices.output_gain);
+                if let Some(recorder) = &mut recorder {
+                    recorder.write(data).expect("Failed to write to WAV file");
+                }
             },
             move |err| eprintln!("[-] Error: {err}"),
             None,
         )
         .unwrap();
 
-    let module_ref = module.clone();
-    let input_stream = devices
-        .input_device
-        .build_input_stream(
-            &devices.input_config.into(),
-            move |data: &[f32], info: &cpal::InputCallbackInfo| {
-                module_ref.input_raw(data, info, devices.input_gain)
-            },
-            |err| eprintln!("[-] Error: {err}"),
-            None,
+    // Optionally remove the DC offset of the input before the module gets it
+    let mut dc_block = args.get_flag("dc-block").then(|| {
+        DcBlocker::new(
+            devices.input_config.channels() as usize,
+            DC_BLOCK_CUTOFF,
+            devices.input_config.sample_rate().0,
         )
-        .unwrap();
+    });
+
+    // Optionally keep the input level near a target with automatic gain control
+    let mut agc = args.get_flag("agc").then(|| {
+        Agc::new(
+            devices.input_config.channels() as usize,
+            devices.input_config.sample_rate().0,
+            *args.get_one::<f32>("agc-target").unwrap(),
+        )
+        .gain_range(
+            *args.get_one::<f32>("agc-min-gain").unwrap(),
+            *args.get_one::<f32>("agc-max-gain").unwrap(),
+        )
+    });
+
+    let module_ref = module.clone();
+    let input_stream = match wav_source {
+        Some((_, source)) => {
+            source.start(
+                move |data| {
+                    let mut data = data
+                        .iter()
+                        .map(|x| x * devices.input_gain)
+                        .collect::<Vec<_>>();
+                    if let Some(filter) = &mut dc_block {
+                        filter.process(&mut data);
+                    }
+                    if let Some(agc) = &mut agc {
+                        agc.process(&mut data);
+                    }
+                    module_ref.input(&data)
+                },
+                modules::end_input,
+            );
+            None
+        }
+        None => Some(
+            devices
+                .input_device
+                .build_input_stream(
+                    &devices.input_config.into(),
+                    move |data: &[f32], info: &cpal::InputCallbackInfo| {
+                        let data = match &mut dc_block {
+                            Some(filter) => {
+                                let mut data = data.to_vec();
+                                filter.process(&mut data);
+                                Cow::Owned(data)
+                            }
+                            None => Cow::Borrowed(data),
+                        };
+                        module_ref.input_raw(&data, info, devices.input_gain, agc.as_mut())
+                    },
+                    |err| eprintln!("[-] Error: {err}"),
+                    None,
+                )
+                .unwrap(),
+        ),
+    };
 
     output_stream.play().unwrap();
-    input_stream.play().unwrap();
+    if let Some(input_stream) = &input_stream {
+        input_stream.play().unwrap();
+    }
+
+    // Returns once the module is finished, the streams are then stopped as they are dropped
     module.block();
+    println!("[*] Module finished");
+}
+
+/// Writes a tone to a mono WAV file, using the args of the `tone` subcommand.
+fn write_tone(args: &ArgMatches) {
+    let freq = *args.get_one::<f32>("freq").unwrap();
+    let duration = *args.get_one::<f32>("duration").unwrap();
+    let sample_rate = *args.get_one::<u32>("sample-rate").unwrap();
+    let path = args.get_one::<PathBuf>("out").unwrap();
+
+    let samples = Tone::new(freq, SampleRate::from_hz(sample_rate))
+        .duration((duration * sample_rate as f32) as usize)
+        .waveform(*args.get_one::<Waveform>("waveform").unwrap())
+        .amplitude(*args.get_one::<f32>("amplitude").unwrap())
+        .collect::<Vec<_>>();
+
+    let mut recorder = WavRecorder::new(path, 1, sample_rate).expect("Failed to create WAV file");
+    recorder
+        .write(&samples)
+        .expect("Failed to write to WAV file");
+    println!(
+        "[*] Wrote a {freq}Hz tone ({duration}s) to `{}`",
+        path.display()
+    );
 }
@@ -4,6 +4,9 @@ pub mod buf_writer;
 pub mod other;
 pub mod ring_buffer;
 pub mod soon;
+pub mod terminal;
+#[cfg(test)]
+pub mod test_util;
 pub mod value_repeat;
 
 pub use other::*;
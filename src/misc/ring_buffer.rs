@@ -23,6 +23,12 @@ impl<T: Default + Copy, const SIZE: usize> RingBuffer<T, SIZE> {
     }
 }
 
+impl<T: Default + Copy, const SIZE: usize> Default for RingBuffer<T, SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T, const SIZE: usize> RingBuffer<T, SIZE> {
     /// Adds a new value to the buffer
     pub fn push(&mut self, val: T) {
@@ -45,6 +51,23 @@ impl<T, const SIZE: usize> RingBuffer<T, SIZE> {
 
         &self.data[..self.index]
     }
+
+    /// Iterates over the values that have been set, from oldest to newest.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        let (old, new) = match self.filled {
+            true => (&self.data[self.index..], &self.data[..self.index]),
+            false => (&self.data[..self.index], &[][..]),
+        };
+        old.iter().chain(new)
+    }
+
+    /// Gets the values that have been set, from oldest to newest.
+    pub fn as_ordered(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        self.iter().cloned().collect()
+    }
 }
 
 impl<T: Float, const SIZE: usize> RingBuffer<T, SIZE> {
@@ -66,6 +89,23 @@ impl<T: Float, const SIZE: usize> RingBuffer<T, SIZE> {
         let sum = real.iter().fold(T::zero(), |a, &b| a + b);
         sum / T::from(real.len()).unwrap()
     }
+
+    /// Get the (population) variance of the values from the buffer.
+    /// Like [`RingBuffer::avg`], NaN is returned if there are no values.
+    pub fn variance(&self) -> T {
+        let real = self.real();
+        let avg = self.avg();
+        let sum = real
+            .iter()
+            .fold(T::zero(), |a, &b| a + (b - avg) * (b - avg));
+        sum / T::from(real.len()).unwrap()
+    }
+
+    /// Get the standard deviation of the values from the buffer.
+    /// NaN is returned if there are no values.
+    pub fn std_dev(&self) -> T {
+        self.variance().sqrt()
+    }
 }
 
 #[cfg(test)]
@@ -84,6 +124,38 @@ mod test {
         assert_eq!(ring.avg(), 3.0);
     }
 
+    #[test]
+    fn test_ring_buffer_std_dev() {
+        let mut ring = RingBuffer::<f32, 10>::new();
+        assert!(ring.variance().is_nan());
+        assert!(ring.std_dev().is_nan());
+
+        for i in [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0] {
+            ring.push(i);
+        }
+        assert_eq!(ring.avg(), 5.0);
+        assert_eq!(ring.variance(), 4.0);
+        assert_eq!(ring.std_dev(), 2.0);
+    }
+
+    #[test]
+    fn test_ring_buffer_ordered() {
+        let mut ring = RingBuffer::<u32, 10>::new();
+        assert!(ring.as_ordered().is_empty());
+
+        for i in 0..4 {
+            ring.push(i);
+        }
+        assert_eq!(ring.as_ordered(), (0..4).collect::<Vec<_>>());
+
+        // Once wrapped around, only the most recent values are kept, still in the order they were added
+        for i in 4..25 {
+            ring.push(i);
+        }
+        assert_eq!(ring.as_ordered(), (15..25).collect::<Vec<_>>());
+        assert!(ring.iter().copied().eq(15..25));
+    }
+
     #[test]
     fn test_ring_buffer_real_full() {
         let mut ring = RingBuffer::<f32, 10>::new();
@@ -1,35 +1,28 @@
-// On the off chance that somebody is actually looking at this.
-// DO NOT USE THIS. THERE IS NO GOOD REASON TO DO SOMETHING LIKE THIS.
-// i only wrote this because my reasons are bad.
-// also its been too long since ive used unsafe for no reason.
+//! A cell for setting values after creating a struct.
+//! Useful when a value needs a reference to the struct that holds it, like a callback or renderer.
 
-use std::{cell::UnsafeCell, mem::MaybeUninit, ops::Deref};
+use std::{ops::Deref, sync::OnceLock};
 
-/// A *VERY UNSAFE* way to set values after creating a struct.
-/// Like a RefCell without the borrow checking.
-/// You are expected to use it properly.
+/// A way to set values after creating a struct.
+/// Like a [`OnceLock`] that derefs to its value, as it is expected to be set before it is used.
 pub struct Soon<T> {
-    inner: MaybeUninit<UnsafeCell<T>>,
+    inner: OnceLock<T>,
 }
 
 impl<T> Soon<T> {
     /// Create a new `Soon` with out its value.
-    /// If it is dereferenced at this point, in debug mode it will panic
-    /// and in release mode you will get some sorta segfault.
-    /// **(very unsafe)**
+    /// If it is dereferenced before [`Soon::replace`] is called, it will panic.
     pub fn empty() -> Self {
         Self {
-            inner: MaybeUninit::zeroed(),
+            inner: OnceLock::new(),
         }
     }
 
-    /// Replace whatever is in the `Soon` with a specified value.
-    /// Please only call this once per soon object.
+    /// Sets the value of the `Soon`.
+    /// This can only be called once per soon object, and will panic if it already has a value.
     pub fn replace(&self, val: T) {
-        // SAFETY: nobody cares >:)
-        let cell = self.inner.as_ptr() as *mut T;
-        unsafe {
-            cell.write(val);
+        if self.inner.set(val).is_err() {
+            panic!("A `Soon` was given a value twice.");
         }
     }
 }
@@ -38,23 +31,35 @@ impl<T> Deref for Soon<T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
-        #[cfg(debug_assertions)]
-        if self.inner.as_ptr().is_null() {
-            panic!("A `Soon` was dereferenced before being givin a value.");
-        }
-
-        let cell = UnsafeCell::raw_get(self.inner.as_ptr());
-        unsafe { &*cell }
+        self.inner
+            .get()
+            .expect("A `Soon` was dereferenced before being givin a value.")
     }
 }
 
-impl<T> Drop for Soon<T> {
-    fn drop(&mut self) {
-        let cell = UnsafeCell::raw_get(self.inner.as_ptr());
-        unsafe { cell.drop_in_place() }
+#[cfg(test)]
+mod test {
+    use super::Soon;
+
+    #[test]
+    fn test_soon() {
+        let soon = Soon::<Vec<u32>>::empty();
+        soon.replace(vec![1, 2, 3]);
+        assert_eq!(soon.len(), 3);
     }
-}
 
-// shhhhh. its not really thread safe.
-unsafe impl<T> Send for Soon<T> {}
-unsafe impl<T> Sync for Soon<T> {}
+    #[test]
+    #[should_panic]
+    fn test_soon_empty() {
+        let soon = Soon::<u32>::empty();
+        let _ = *soon;
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_soon_replace_twice() {
+        let soon = Soon::<u32>::empty();
+        soon.replace(1);
+        soon.replace(2);
+    }
+}
@@ -0,0 +1,89 @@
+//! Sets up the terminal for the modules that draw over all of it or read the keyboard, and puts it back after.
+
+use std::{io::stdout, panic, process};
+
+use crossterm::{
+    cursor,
+    event::{KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags},
+    execute, terminal,
+};
+
+/// Keeps the terminal in raw mode until it is dropped.
+/// The terminal is also restored if the program panics, otherwise it would be left in a state where you can't close the program.
+pub struct TerminalGuard {
+    mode: Mode,
+}
+
+/// What the terminal is set up for, besides raw mode.
+#[derive(Clone, Copy)]
+enum Mode {
+    /// Drawing on the alternate screen, with the cursor hidden.
+    Screen,
+    /// Reading key presses and releases, while still printing to the normal screen.
+    Keys,
+}
+
+impl TerminalGuard {
+    /// Enables raw mode, enters the alternate screen and hides the cursor.
+    pub fn enter() -> Self {
+        Self::start(Mode::Screen)
+    }
+
+    /// Enables raw mode and asks the terminal to report key releases.
+    /// Not all terminals support this, see [`terminal::supports_keyboard_enhancement`].
+    pub fn keys() -> Self {
+        Self::start(Mode::Keys)
+    }
+
+    fn start(mode: Mode) -> Self {
+        // The previous hook is run after the terminal is restored, so the panic message and backtrace are still shown
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            restore(mode);
+            hook(info);
+            process::exit(1)
+        }));
+
+        terminal::enable_raw_mode().unwrap();
+        match mode {
+            Mode::Screen => execute!(
+                stdout(),
+                terminal::EnterAlternateScreen,
+                terminal::DisableLineWrap,
+                cursor::Hide,
+            )
+            .unwrap(),
+            Mode::Keys => {
+                let _ = execute!(
+                    stdout(),
+                    PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+                );
+            }
+        }
+
+        Self { mode }
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore(self.mode);
+    }
+}
+
+/// Undoes what was set up for the mode, then disables raw mode.
+fn restore(mode: Mode) {
+    match mode {
+        Mode::Screen => execute!(
+            stdout(),
+            terminal::LeaveAlternateScreen,
+            terminal::EnableLineWrap,
+            cursor::Show
+        )
+        .unwrap(),
+        Mode::Keys => {
+            let _ = execute!(stdout(), PopKeyboardEnhancementFlags);
+        }
+    }
+    terminal::disable_raw_mode().unwrap();
+}
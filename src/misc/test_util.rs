@@ -0,0 +1,34 @@
+//! Helpers shared by the tests of the encoders and decoders.
+
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+/// Generates `len` samples of uniform noise between -0.5 and 0.5, using a simple LCG.
+/// The same seed always gives the same noise, so tests stay deterministic.
+pub fn noise(seed: u32, len: usize) -> Vec<f32> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            state as f32 / u32::MAX as f32 - 0.5
+        })
+        .collect()
+}
+
+/// Collects the characters sent to a decoder callback.
+/// Clone it into the callback, then read the [`Collector::contents`] once decoding is done.
+#[derive(Clone, Default)]
+pub struct Collector(Arc<Mutex<String>>);
+
+impl Collector {
+    /// Adds a decoded character.
+    pub fn push(&self, chr: char) {
+        self.0.lock().push(chr);
+    }
+
+    /// Gets everything collected so far.
+    pub fn contents(&self) -> String {
+        self.0.lock().to_owned()
+    }
+}
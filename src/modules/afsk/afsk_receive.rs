@@ -0,0 +1,62 @@
+//! Receive AFSK tones and decode them into binary data.
+
+use std::{
+    io::{self, Write},
+    sync::Arc,
+};
+
+use parking_lot::Mutex;
+
+use crate::{
+    coding::afsk::{AfskConfig, AfskDecoder},
+    modules::{InitContext, Module},
+};
+
+const AFSK_CHUNK: usize = 512;
+
+pub struct AfskReceive {
+    ctx: InitContext,
+    decode: Mutex<AfskDecoder>,
+    work: Mutex<Vec<f32>>,
+}
+
+impl AfskReceive {
+    pub fn new(ctx: InitContext) -> Arc<Self> {
+        // Print each byte as it is decoded
+        let decode = AfskDecoder::new(AfskConfig::BELL_202, ctx.sample_rate(), |x| {
+            let mut stdout = io::stdout();
+            stdout.write_all(&[x]).unwrap();
+            stdout.flush().unwrap();
+        });
+
+        Arc::new(Self {
+            ctx,
+            decode: Mutex::new(decode),
+            work: Mutex::new(Vec::new()),
+        })
+    }
+}
+
+impl Module for AfskReceive {
+    fn name(&self) -> &'static str {
+        "AfskReceive"
+    }
+
+    fn input(&self, input: &[f32]) {
+        // Add samples from the first channel to the work buffer
+        let mut work = self.work.lock();
+        work.extend(
+            input
+                .iter()
+                .enumerate()
+                .filter(|x| x.0 % self.ctx.input.channels() as usize == 0)
+                .map(|x| *x.1),
+        );
+
+        // If the data is at least AFSK_CHUNK long, process it
+        for _ in 0..work.len() / AFSK_CHUNK {
+            let chunk = work.drain(..AFSK_CHUNK).collect::<Vec<_>>();
+            self.decode.lock().process(&chunk);
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use parking_lot::Mutex;
+
+use crate::{
+    coding::afsk::{AfskConfig, AfskEncoder},
+    modules::{InitContext, Module},
+};
+
+pub struct AfskSend {
+    ctx: InitContext,
+    encode: Mutex<AfskEncoder>,
+    finished: AtomicBool,
+}
+
+impl AfskSend {
+    pub fn new(ctx: InitContext) -> Arc<Self> {
+        let data = ctx
+            .args
+            .subcommand()
+            .unwrap()
+            .1
+            .get_one::<String>("data")
+            .unwrap();
+        let encode = AfskEncoder::new(data.as_bytes(), AfskConfig::BELL_202, ctx.sample_rate());
+
+        Arc::new(Self {
+            encode: Mutex::new(encode),
+            finished: AtomicBool::new(false),
+            ctx,
+        })
+    }
+}
+
+impl Module for AfskSend {
+    fn name(&self) -> &'static str {
+        "AfskSend"
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+
+    fn output(&self, output: &mut [f32]) {
+        let mut encode = self.encode.lock();
+        let mut last = 0.0;
+
+        for (i, e) in output.iter_mut().enumerate() {
+            if i % self.ctx.output.channels() as usize == 0 {
+                let val = encode.next();
+                if val.is_none() {
+                    self.finished.store(true, Ordering::Relaxed);
+                }
+                last = val.unwrap_or(0.0);
+            }
+
+            *e = last;
+        }
+    }
+}
@@ -0,0 +1,2 @@
+pub mod afsk_receive;
+pub mod afsk_send;
@@ -0,0 +1,100 @@
+//! Detects incoming CTCSS tones.
+//! With a tone set, this works as a squelch, only passing the audio through while that tone is heard.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use parking_lot::Mutex;
+
+use crate::{
+    audio::{algorithms::to_mono, passthrough::PassThrough},
+    coding::ctcss::{CtcssDecoder, TONES},
+    modules::{passthrough, InitContext, Module},
+};
+
+pub struct CtcssReceive {
+    ctx: InitContext,
+    /// The tone that opens the squelch, any tone does if not set.
+    tone: Option<f32>,
+    decode: Mutex<CtcssDecoder>,
+    /// The tone heard by the last measurement.
+    last: Mutex<Option<f32>>,
+    open: AtomicBool,
+    passthrough: Option<Mutex<PassThrough>>,
+}
+
+impl CtcssReceive {
+    pub fn new(ctx: InitContext) -> Arc<Self> {
+        let args = ctx.args.subcommand().unwrap().1;
+        let tone = args.get_one::<f32>("tone").copied();
+        let threshold = *args.get_one::<f32>("threshold").unwrap();
+        let passthrough = passthrough(args, &ctx, args.get_flag("passthrough"));
+
+        Arc::new(Self {
+            decode: Mutex::new(CtcssDecoder::new(ctx.sample_rate(), threshold)),
+            tone,
+            last: Mutex::new(None),
+            open: AtomicBool::new(false),
+            passthrough,
+            ctx,
+        })
+    }
+}
+
+impl Module for CtcssReceive {
+    fn name(&self) -> &'static str {
+        "ctcss-receive"
+    }
+
+    fn init(&self) {
+        match self.tone {
+            Some(tone) => println!("[I] Squelch opens on {tone}Hz"),
+            None => println!("[I] Listening for any CTCSS tone"),
+        }
+    }
+
+    fn input(&self, input: &[f32]) {
+        if let Some(i) = &self.passthrough {
+            i.lock().add_samples(input);
+        }
+
+        let mut decode = self.decode.lock();
+        if !decode.process(&to_mono(input, self.ctx.input.channels() as usize)) {
+            return;
+        }
+
+        let heard = decode.tone();
+        let mut last = self.last.lock();
+        if heard == *last {
+            return;
+        }
+        *last = heard;
+
+        match heard {
+            Some(tone) => {
+                let code = TONES.iter().position(|&x| x == tone).unwrap() + 1;
+                println!("[*] Heard {tone}Hz (code {code})");
+            }
+            None => println!("[-] Tone lost"),
+        }
+
+        let open = match self.tone {
+            Some(tone) => heard == Some(tone),
+            None => heard.is_some(),
+        };
+        self.open.store(open, Ordering::Relaxed);
+    }
+
+    fn output(&self, output: &mut [f32]) {
+        // Writes the output from the pass-through, muted while the squelch is closed
+        if let Some(i) = &self.passthrough {
+            i.lock().write_output(output);
+
+            if !self.open.load(Ordering::Relaxed) {
+                output.fill(0.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,60 @@
+//! Sends a continuous CTCSS tone, optionally mixed under audio passed through from the input.
+
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::{
+    audio::{passthrough::PassThrough, tone::Tone},
+    modules::{passthrough, InitContext, Module},
+};
+
+pub struct CtcssSend {
+    ctx: InitContext,
+    tone: Mutex<Tone>,
+    passthrough: Option<Mutex<PassThrough>>,
+}
+
+impl CtcssSend {
+    pub fn new(ctx: InitContext) -> Arc<Self> {
+        let args = ctx.args.subcommand().unwrap().1;
+        let freq = *args.get_one::<f32>("tone").unwrap();
+        let amplitude = *args.get_one::<f32>("amplitude").unwrap();
+        let passthrough = passthrough(args, &ctx, args.get_flag("passthrough"));
+
+        println!("[*] Sending a {freq}Hz CTCSS tone");
+        let tone = Tone::new(freq, ctx.sample_rate()).amplitude(amplitude);
+
+        Arc::new(Self {
+            tone: Mutex::new(tone),
+            passthrough,
+            ctx,
+        })
+    }
+}
+
+impl Module for CtcssSend {
+    fn name(&self) -> &'static str {
+        "ctcss-send"
+    }
+
+    fn input(&self, input: &[f32]) {
+        if let Some(i) = &self.passthrough {
+            i.lock().add_samples(input);
+        }
+    }
+
+    fn output(&self, output: &mut [f32]) {
+        match &self.passthrough {
+            Some(i) => i.lock().write_output(output),
+            None => output.fill(0.0),
+        }
+
+        // Mix the tone under the audio on each channel
+        let mut tone = self.tone.lock();
+        for frame in output.chunks_mut(self.ctx.output.channels() as usize) {
+            let val = tone.next().unwrap_or(0.0);
+            frame.iter_mut().for_each(|x| *x += val);
+        }
+    }
+}
@@ -0,0 +1,2 @@
+pub mod ctcss_receive;
+pub mod ctcss_send;
@@ -0,0 +1,55 @@
+//! Receives binary data sent with the Commodore Dataset like encoder.
+
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::{
+    coding::{dataset::BinDecoder, format::DataFormat},
+    modules::{InitContext, Module},
+};
+
+pub struct DatasetReceive {
+    ctx: InitContext,
+    decode: Mutex<BinDecoder>,
+    format: DataFormat,
+}
+
+impl DatasetReceive {
+    pub fn new(ctx: InitContext) -> Arc<Self> {
+        let format = *ctx
+            .args
+            .subcommand()
+            .unwrap()
+            .1
+            .get_one::<DataFormat>("format")
+            .unwrap();
+        let decode = BinDecoder::new(ctx.sample_rate());
+
+        Arc::new(Self {
+            decode: Mutex::new(decode),
+            format,
+            ctx,
+        })
+    }
+}
+
+impl Module for DatasetReceive {
+    fn name(&self) -> &'static str {
+        "dataset-receive"
+    }
+
+    fn input(&self, input: &[f32]) {
+        let mut decode = self.decode.lock();
+        for sample in input.iter().step_by(self.ctx.input.channels() as usize) {
+            decode.add(*sample);
+        }
+
+        // Print the data once the transmission is over
+        if decode.is_done() {
+            let data = decode.take();
+            println!("[*] Transmission Complete ({} bytes)", data.len());
+            println!("{}", self.format.encode(&data));
+        }
+    }
+}
@@ -0,0 +1,63 @@
+//! Sends binary data with the Commodore Dataset like encoder.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use parking_lot::Mutex;
+
+use crate::{
+    coding::{dataset::BinEncoder, format::DataFormat},
+    modules::{InitContext, Module},
+};
+
+pub struct DatasetSend {
+    ctx: InitContext,
+    encode: Mutex<BinEncoder>,
+    finished: AtomicBool,
+}
+
+impl DatasetSend {
+    pub fn new(ctx: InitContext) -> Arc<Self> {
+        let args = ctx.args.subcommand().unwrap().1;
+        let format = args.get_one::<DataFormat>("format").unwrap();
+        let data = format
+            .decode(args.get_one::<String>("data").unwrap())
+            .expect("Invalid data");
+        let encode = BinEncoder::new(&data, ctx.sample_rate());
+
+        Arc::new(Self {
+            encode: Mutex::new(encode),
+            finished: AtomicBool::new(false),
+            ctx,
+        })
+    }
+}
+
+impl Module for DatasetSend {
+    fn name(&self) -> &'static str {
+        "dataset-send"
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+
+    fn output(&self, output: &mut [f32]) {
+        let mut encode = self.encode.lock();
+        let mut last = 0.0;
+
+        for (i, e) in output.iter_mut().enumerate() {
+            if i % self.ctx.output.channels() as usize == 0 {
+                let val = encode.next();
+                if val.is_none() {
+                    self.finished.store(true, Ordering::Relaxed);
+                }
+                last = val.unwrap_or(0.0);
+            }
+
+            *e = last;
+        }
+    }
+}
@@ -0,0 +1,2 @@
+pub mod dataset_receive;
+pub mod dataset_send;
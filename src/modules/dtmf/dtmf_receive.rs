@@ -1,16 +1,20 @@
 //! Receive DTMF tones and decode them into binary data.
 
-use std::{
-    io::{self, Write},
-    sync::Arc,
-};
+use std::sync::Arc;
 
 use parking_lot::Mutex;
 
 use crate::{
-    coding::dtmf::{self, DtmfDecoder},
+    audio::{
+        algorithms::{ChannelSelect, Notch},
+        windows::BoxedWindow,
+    },
+    coding::{
+        dtmf::{self, DtmfConfig, DtmfDecoder},
+        format::DataFormat,
+    },
     misc::soon::Soon,
-    modules::{InitContext, Module},
+    modules::{self, InitContext, Module},
 };
 
 const DTMF_CHUNK: usize = 512;
@@ -19,35 +23,61 @@ pub struct DtmfReceive {
     ctx: InitContext,
     decode: Soon<Mutex<DtmfDecoder>>,
     work: Mutex<Vec<f32>>,
+    channel: ChannelSelect,
+    notches: Mutex<Vec<Notch>>,
     history: Mutex<Vec<u8>>,
+    min_confidence: f32,
+    crc: bool,
+    format: DataFormat,
 }
 
 impl DtmfReceive {
     pub fn new(ctx: InitContext) -> Arc<Self> {
+        let args = ctx.args.subcommand().unwrap().1;
+        let min_confidence = *args.get_one::<f32>("min-confidence").unwrap();
+        let crc = args.get_flag("crc");
+        let format = *args.get_one::<DataFormat>("format").unwrap();
+        let config = args
+            .get_one::<DtmfConfig>("freq-table")
+            .cloned()
+            .unwrap_or_else(DtmfConfig::standard);
+        let window = args.get_one::<Arc<BoxedWindow>>("window").unwrap().clone();
+        let channel = modules::input_channel(args, &ctx);
+        let notches = modules::notch_filters(args, &ctx);
+
         let out = Arc::new(Self {
             decode: Soon::empty(),
             work: Mutex::new(Vec::new()),
+            channel,
+            notches,
             history: Mutex::new(Vec::new()),
+            min_confidence,
+            crc,
+            format,
             ctx,
         });
 
         // Create a new DTMF decoder and set its callback to self.callback
         let this = out.clone();
-        out.decode.replace(Mutex::new(DtmfDecoder::new(
-            out.ctx.sample_rate(),
-            move |x| this.callback(x as char),
-        )));
+        out.decode.replace(Mutex::new(
+            DtmfDecoder::new(out.ctx.sample_rate(), config, move |x, conf| {
+                this.callback(x as char, conf)
+            })
+            .window(window),
+        ));
 
         out
     }
 
     /// THis function is called when a byte is decoded.
-    fn callback(&self, chr: char) {
-        let mut stdout = io::stdout();
-        stdout
-            .write_all(chr.encode_utf8(&mut [0; 4]).as_bytes())
-            .unwrap();
-        stdout.flush().unwrap();
+    fn callback(&self, chr: char, conf: f32) {
+        // Ignore codes that were not detected cleanly enough
+        if conf < self.min_confidence {
+            println!("[-] Ignored code: {chr} (conf {conf:.2})");
+            return;
+        }
+
+        println!("[*] Got code: {chr} (conf {conf:.2})");
 
         // Add the byte to the history
         let mut history = self.history.lock();
@@ -56,7 +86,7 @@ impl DtmfReceive {
         // If the history is long enough try to find the start and end codes
         // If these are found, decode the data and print it
         if history.len() > 2 && &history[history.len() - 2..] == b"#D" {
-            println!("\n[*] Transmission Complete");
+            println!("[*] Transmission Complete");
             let start = match history.windows(2).rposition(|x| x == b"A#") {
                 Some(i) => i,
                 None => {
@@ -66,8 +96,23 @@ impl DtmfReceive {
             };
 
             let raw = dtmf::dtmf_to_bin(&history[start + 2..&history.len() - 2]);
-            println!("{}", raw.iter().map(|x| *x as char).collect::<String>());
             history.clear();
+
+            // Verify the checksum at the end of the data
+            let data = match self.crc {
+                true => match dtmf::check_crc(&raw) {
+                    Some(i) => {
+                        println!("[*] CRC check passed");
+                        i
+                    }
+                    None => {
+                        println!("[-] CRC check failed");
+                        &raw
+                    }
+                },
+                false => &raw,
+            };
+            println!("{}", self.format.encode(data));
         }
     }
 }
@@ -78,14 +123,12 @@ impl Module for DtmfReceive {
     }
 
     fn input(&self, input: &[f32]) {
-        // Add the input to the work buffer
+        // Add the filtered input to the work buffer
+        let input = modules::apply_notches(&self.notches, input);
         let mut work = self.work.lock();
         work.extend(
-            input
-                .iter()
-                .enumerate()
-                .filter(|x| x.0 % self.ctx.input.channels() as usize == 0)
-                .map(|x| *x.1),
+            self.channel
+                .select(&input, self.ctx.input.channels() as usize),
         );
 
         // If the data is at least DTMF_CHUNK long, process it
@@ -1,31 +1,35 @@
-use std::{
-    process,
-    sync::{
-        atomic::{AtomicUsize, Ordering},
-        Arc,
-    },
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
 };
 
 use parking_lot::Mutex;
 
 use crate::{
     audio::tone::Tone,
-    coding::dtmf::{self, DtmfEncoder},
+    coding::{
+        dtmf::{self, DtmfConfig, DtmfEncoder},
+        format::DataFormat,
+    },
     modules::{InitContext, Module},
 };
 
+/// The frequency of the head tone, in Hz.
+const HEAD_FREQUENCY: f32 = 440.0;
+
 pub struct DtmfSend {
     ctx: InitContext,
     state: Mutex<State>,
     encode: Mutex<DtmfEncoder>,
-    i: AtomicUsize,
+    /// Set once the encoder has run out of data.
+    finished: AtomicBool,
 }
 
 /// The state of the DTMF sender
 enum State {
     /// Sending data from the [`DtmfEncoder`]
     Transmitting,
-    /// Sending a start tone.
+    /// Sending a start tone for one second.
     /// This is needed because the VOX setting on my radio takes a second to activate.
     /// So this tone allows the radio to activate before sending the data.
     Head(Tone),
@@ -36,14 +40,19 @@ impl DtmfSend {
         let sr = ctx.sample_rate();
 
         // Convert the data to DTMF
-        let to_send = ctx
-            .args
-            .subcommand()
-            .unwrap()
-            .1
-            .get_one::<String>("data")
-            .unwrap();
-        let mut to_send = dtmf::bin_to_dtmf(to_send.as_bytes());
+        let args = ctx.args.subcommand().unwrap().1;
+        let format = args.get_one::<DataFormat>("format").unwrap();
+        let to_send = format
+            .decode(args.get_one::<String>("data").unwrap())
+            .expect("Invalid data");
+        let config = args
+            .get_one::<DtmfConfig>("freq-table")
+            .cloned()
+            .unwrap_or_else(DtmfConfig::standard);
+        let mut to_send = match args.get_flag("crc") {
+            true => dtmf::bin_to_dtmf(&dtmf::append_crc(&to_send)),
+            false => dtmf::bin_to_dtmf(&to_send),
+        };
 
         // Add the start and end codes
         // I don't remember what these codes mean in binary, but they should probably be changed
@@ -52,17 +61,24 @@ impl DtmfSend {
         to_send.push(b'#');
         to_send.push(b'D');
 
+        // Send the whole message, with its start and end codes, multiple times
+        let repeat = *args.get_one::<u32>("repeat").unwrap() as usize;
+        let to_send = to_send.repeat(repeat);
+
         // Prints the DTMF encoded data
         println!(
             "[D] {}",
             to_send.iter().map(|x| *x as char).collect::<String>()
         );
 
+        // The head tone is timed with the output sample rate, as that is what it is played at
+        let head = Tone::new(HEAD_FREQUENCY, sr).duration(sr.output as usize);
+
         Arc::new(Self {
             ctx,
-            i: AtomicUsize::new(0),
-            state: Mutex::new(State::Head(Tone::new(440.0, sr))),
-            encode: Mutex::new(DtmfEncoder::new(&to_send, sr)),
+            finished: AtomicBool::new(false),
+            state: Mutex::new(State::Head(head)),
+            encode: Mutex::new(DtmfEncoder::new(&to_send, config, sr)),
         })
     }
 }
@@ -72,26 +88,29 @@ impl Module for DtmfSend {
         "DtmfSend"
     }
 
+    fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+
     fn output(&self, output: &mut [f32]) {
         let mut last = 0.0;
-        let mut exit = false;
         for (i, e) in output.iter_mut().enumerate() {
             if i % self.ctx.output.channels() as usize == 0 {
-                // After one second of sending the HEAD tone, start sending the data
-                // Note: This probably shouldn't be run if the state is already transmitting
-                if self.i.fetch_add(1, Ordering::Relaxed)
-                    > self.ctx.input.sample_rate().0 as usize * 2
-                {
-                    *self.state.lock() = State::Transmitting;
-                }
-
-                // Get the next sample from either the HEAD tone or the DTMF encoder
-                let mut enc = self.state.lock();
-                last = match &mut *enc {
-                    State::Head(i) => i.next().unwrap(),
-                    State::Transmitting => {
+                // Get the next sample from the HEAD tone, then the DTMF encoder once it has finished
+                let mut state = self.state.lock();
+                let head = match &mut *state {
+                    State::Head(tone) => tone.next(),
+                    State::Transmitting => None,
+                };
+
+                last = match head {
+                    Some(val) => val,
+                    None => {
+                        *state = State::Transmitting;
                         let val = self.encode.lock().next();
-                        exit |= val.is_none();
+                        if val.is_none() {
+                            self.finished.store(true, Ordering::Relaxed);
+                        }
                         val.unwrap_or(0.0)
                     }
                 };
@@ -99,9 +118,49 @@ impl Module for DtmfSend {
 
             *e = last;
         }
+    }
+}
 
-        if exit {
-            process::exit(0);
-        }
+#[cfg(test)]
+mod test {
+    use cpal::{SampleFormat, SupportedBufferSize, SupportedStreamConfig};
+
+    use super::{DtmfSend, State, HEAD_FREQUENCY};
+    use crate::{
+        args,
+        audio::tone::Tone,
+        misc::SampleRate,
+        modules::{InitContext, Module},
+    };
+
+    fn config(sample_rate: u32) -> SupportedStreamConfig {
+        SupportedStreamConfig::new(
+            1,
+            cpal::SampleRate(sample_rate),
+            SupportedBufferSize::Unknown,
+            SampleFormat::F32,
+        )
+    }
+
+    #[test]
+    fn test_head_tone_mismatched_rates() {
+        let args = args::command().get_matches_from(["radio-data", "dtmf", "send", "Hi"]);
+        let ctx = InitContext {
+            args: args.subcommand_matches("dtmf").unwrap().clone(),
+            input_name: "test".to_owned(),
+            input: config(44100),
+            output: config(48000),
+        };
+        let send = DtmfSend::new(ctx);
+
+        // The head tone should last exactly one second at the output rate
+        let mut output = vec![0.0; 48000];
+        send.output(&mut output);
+        assert!(matches!(*send.state.lock(), State::Head(_)));
+        let head = Tone::new(HEAD_FREQUENCY, SampleRate::from_hz(48000));
+        assert!(output.iter().copied().eq(head.take(48000)));
+
+        send.output(&mut [0.0]);
+        assert!(matches!(*send.state.lock(), State::Transmitting));
     }
 }
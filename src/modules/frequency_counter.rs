@@ -0,0 +1,153 @@
+//! Frequency counter module that prints the dominant frequency of the input a few times per second.
+//! Useful for checking that a transmitter is on frequency.
+//!
+//! A rough estimate is made by timing the zero-crossings of the signal.
+//! The Goertzel algorithm is then run on the bins around that estimate, and the strongest bin is interpolated for sub-bin accuracy.
+
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use super::{InitContext, Module};
+use crate::audio::algorithms::{goertzel_mag, to_db, to_mono, DB_MIN};
+
+/// The number of bins on each side of the zero-crossing estimate that are checked with the Goertzel algorithm.
+const SEARCH_BINS: i32 = 2;
+
+pub struct FrequencyCounter {
+    ctx: InitContext,
+    /// The number of samples used for each measurement.
+    window: usize,
+    /// Amplitudes below this are reported as no signal.
+    threshold: f32,
+    samples: Mutex<Vec<f32>>,
+}
+
+impl FrequencyCounter {
+    pub fn new(ctx: InitContext) -> Arc<Self> {
+        let rate = *ctx.args.get_one::<f32>("rate").unwrap();
+        let threshold = *ctx.args.get_one("threshold").unwrap();
+        let window = (ctx.sample_rate().input as f32 / rate) as usize;
+
+        Arc::new(Self {
+            ctx,
+            window,
+            threshold,
+            samples: Mutex::new(Vec::with_capacity(window)),
+        })
+    }
+}
+
+impl Module for FrequencyCounter {
+    fn name(&self) -> &'static str {
+        "frequency_counter"
+    }
+
+    fn init(&self) {
+        let sample_rate = self.ctx.sample_rate().input;
+        println!(
+            "[I] Window: {} samples, Goertzel resolution: {:.2}Hz",
+            self.window,
+            sample_rate as f32 / self.window as f32
+        );
+    }
+
+    fn input(&self, input: &[f32]) {
+        let mut samples = self.samples.lock();
+        samples.extend(to_mono(input, self.ctx.input.channels() as usize));
+
+        while samples.len() >= self.window {
+            let window = samples.drain(..self.window).collect::<Vec<_>>();
+            let sample_rate = self.ctx.sample_rate().input;
+
+            let measurement = zero_crossing_freq(&window, sample_rate)
+                .map(|x| refine_freq(&window, sample_rate, x))
+                .filter(|x| x.1 >= self.threshold);
+            match measurement {
+                Some((freq, amplitude)) => println!(
+                    "[*] {freq:.2}Hz, amplitude {amplitude:.3} ({:.1} dBFS)",
+                    to_db(amplitude, DB_MIN)
+                ),
+                None => println!("[-] No signal"),
+            }
+        }
+    }
+}
+
+/// Estimates the frequency of a signal by timing its rising zero-crossings.
+/// The crossing times are linearly interpolated between samples, so the estimate isn't limited to whole samples.
+/// Returns None if there are less than two crossings.
+fn zero_crossing_freq(samples: &[f32], sample_rate: u32) -> Option<f32> {
+    // Remove any DC offset, otherwise the crossings would be uneven
+    let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+
+    let mut crossings = samples.windows(2).enumerate().filter_map(|(i, x)| {
+        let (a, b) = (x[0] - mean, x[1] - mean);
+        (a < 0.0 && b >= 0.0).then(|| i as f32 + a / (a - b))
+    });
+
+    let first = crossings.next()?;
+    let (count, last) = crossings.fold((0, first), |(n, _), x| (n + 1, x));
+    if count == 0 {
+        return None;
+    }
+
+    Some(count as f32 * sample_rate as f32 / (last - first))
+}
+
+/// Refines a frequency estimate by finding the strongest Goertzel bin near it.
+/// The peak is found between bins by fitting a parabola through it and its neighbors.
+/// Returns the frequency and the amplitude of the peak.
+fn refine_freq(samples: &[f32], sample_rate: u32, estimate: f32) -> (f32, f32) {
+    let resolution = sample_rate as f32 / samples.len() as f32;
+    let bin = (estimate / resolution).round() as i32;
+    let mag = |bin: i32| goertzel_mag(bin as f32 * resolution, samples, sample_rate);
+
+    let peak = (bin - SEARCH_BINS..=bin + SEARCH_BINS)
+        .filter(|&x| x > 0)
+        .max_by(|&a, &b| mag(a).total_cmp(&mag(b)))
+        .unwrap_or(1);
+
+    let (a, b, c) = (mag(peak - 1), mag(peak), mag(peak + 1));
+    let denominator = a - 2.0 * b + c;
+    let offset = match denominator == 0.0 {
+        true => 0.0,
+        false => (0.5 * (a - c) / denominator).clamp(-0.5, 0.5),
+    };
+
+    ((peak as f32 + offset) * resolution, b)
+}
+
+#[cfg(test)]
+mod test {
+    use std::f32::consts::TAU;
+
+    use super::{refine_freq, zero_crossing_freq};
+
+    fn sine(freq: f32, amplitude: f32, sample_rate: u32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| (TAU * freq * i as f32 / sample_rate as f32).sin() * amplitude + 0.1)
+            .collect()
+    }
+
+    #[test]
+    fn test_zero_crossing_freq() {
+        for freq in [440.0, 1000.0, 1234.5] {
+            let samples = sine(freq, 0.5, 48000, 12000);
+            let estimate = zero_crossing_freq(&samples, 48000).unwrap();
+            assert!((estimate - freq).abs() < 0.5, "{estimate} != {freq}");
+        }
+
+        assert_eq!(zero_crossing_freq(&[0.0; 100], 48000), None);
+    }
+
+    #[test]
+    fn test_refine_freq() {
+        for freq in [440.0, 1000.0, 1234.5] {
+            let samples = sine(freq, 0.5, 48000, 12000);
+            let (refined, amplitude) = refine_freq(&samples, 48000, freq + 6.0);
+            assert!((refined - freq).abs() < 1.0, "{refined} != {freq}");
+            assert!((amplitude - 0.5).abs() < 0.15, "{amplitude} != 0.5");
+        }
+    }
+}
@@ -0,0 +1,254 @@
+//! Level meter module that shows the input level as a bar in the terminal.
+//! Much lighter than the spectrum analyzer, and all you need when setting the input gain.
+//! The bar is colored green, yellow or red by level, with a peak-hold marker and a clip indicator.
+
+use std::{
+    io::{stdout, Write},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crossterm::{cursor, queue, style, terminal};
+use parking_lot::Mutex;
+
+use super::{passthrough, InitContext, Module};
+use crate::audio::{
+    algorithms::{to_db, to_mono},
+    passthrough::PassThrough,
+};
+
+/// The lowest level shown on the bar.
+/// This is higher than [`crate::audio::algorithms::DB_MIN`], so more of the bar is spent on the levels worth adjusting.
+const DB_MIN: f32 = -60.0;
+/// Levels above this are drawn yellow.
+const DB_YELLOW: f32 = -18.0;
+/// Levels above this are drawn red.
+const DB_RED: f32 = -6.0;
+/// Samples at or above this (absolute) value are considered clipped.
+const CLIP_LEVEL: f32 = 0.999;
+/// How often the meters are redrawn.
+const RENDER_INTERVAL: Duration = Duration::from_millis(50);
+
+pub struct Meter {
+    ctx: InitContext,
+    peak_hold: Duration,
+    passthrough: Option<Mutex<PassThrough>>,
+    input: Mutex<Level>,
+    output: Mutex<Level>,
+    last_render: Mutex<Instant>,
+}
+
+/// The level of a signal, updated every buffer.
+#[derive(Debug, Clone, Copy)]
+struct Level {
+    rms: f32,
+    peak: f32,
+    /// The highest peak in the last `peak_hold`.
+    held: f32,
+    held_at: Instant,
+    /// When the signal last clipped.
+    clipped_at: Option<Instant>,
+}
+
+impl Meter {
+    pub fn new(ctx: InitContext) -> Arc<Self> {
+        let peak_hold = Duration::from_millis(*ctx.args.get_one::<u64>("peak-hold-ms").unwrap());
+        let passthrough = passthrough(&ctx.args, &ctx, ctx.args.get_flag("passthrough"));
+
+        Arc::new(Self {
+            ctx,
+            peak_hold,
+            passthrough,
+            input: Mutex::new(Level::new()),
+            output: Mutex::new(Level::new()),
+            last_render: Mutex::new(Instant::now()),
+        })
+    }
+
+    /// Redraws the meters in place, at most every [`RENDER_INTERVAL`].
+    fn render(&self) {
+        let mut last_render = self.last_render.lock();
+        if last_render.elapsed() < RENDER_INTERVAL {
+            return;
+        }
+        *last_render = Instant::now();
+
+        let mut meters = vec![("IN", *self.input.lock())];
+        if self.passthrough.is_some() {
+            meters.push(("OUT", *self.output.lock()));
+        }
+
+        let width = terminal::size().map(|x| x.0 as usize).unwrap_or(80);
+        let bar_width = width.saturating_sub(45).max(10);
+        let mut stdout = stdout();
+        queue!(stdout, cursor::MoveToPreviousLine(meters.len() as u16)).unwrap();
+
+        for (name, level) in meters {
+            queue!(
+                stdout,
+                terminal::Clear(terminal::ClearType::CurrentLine),
+                style::Print(format!("{name:<3} [")),
+            )
+            .unwrap();
+
+            // Draw the bar one character at a time, so each one can be colored by its level
+            let fill = bar_position(level.rms, DB_MIN, bar_width);
+            let held = bar_position(level.held, DB_MIN, bar_width);
+            for i in 0..bar_width {
+                let db = DB_MIN - DB_MIN * i as f32 / bar_width as f32;
+                let chr = match i {
+                    _ if i < fill => "█",
+                    _ if i + 1 == held => "|",
+                    _ => " ",
+                };
+                queue!(
+                    stdout,
+                    style::SetForegroundColor(level_color(db)),
+                    style::Print(chr)
+                )
+                .unwrap();
+            }
+
+            let clipped = level
+                .clipped_at
+                .map(|x| x.elapsed() < self.peak_hold.max(RENDER_INTERVAL))
+                .unwrap_or(false);
+            queue!(
+                stdout,
+                style::ResetColor,
+                style::Print(format!(
+                    "] {:>6.1} dBFS RMS, peak {:>6.1} dBFS ",
+                    to_db(level.rms, DB_MIN),
+                    to_db(level.held, DB_MIN)
+                )),
+                style::SetForegroundColor(style::Color::Red),
+                style::Print(match clipped {
+                    true => "CLIP",
+                    false => "    ",
+                }),
+                style::ResetColor,
+                style::Print("\n"),
+            )
+            .unwrap();
+        }
+
+        stdout.flush().unwrap();
+    }
+}
+
+impl Level {
+    fn new() -> Self {
+        Self {
+            rms: 0.0,
+            peak: 0.0,
+            held: 0.0,
+            held_at: Instant::now(),
+            clipped_at: None,
+        }
+    }
+
+    /// Updates the level from a buffer of mono samples.
+    /// The held peak is replaced once it is older than `hold`, or if the new peak is higher.
+    fn update(&mut self, samples: &[f32], hold: Duration) {
+        if samples.is_empty() {
+            return;
+        }
+
+        self.rms = (samples.iter().map(|x| x * x).sum::<f32>() / samples.len() as f32).sqrt();
+        self.peak = samples.iter().fold(0.0, |max, x| x.abs().max(max));
+
+        if self.peak >= self.held || self.held_at.elapsed() >= hold {
+            self.held = self.peak;
+            self.held_at = Instant::now();
+        }
+
+        if self.peak >= CLIP_LEVEL {
+            self.clipped_at = Some(Instant::now());
+        }
+    }
+}
+
+impl Module for Meter {
+    fn name(&self) -> &'static str {
+        "meter"
+    }
+
+    fn init(&self) {
+        println!("[I] Peak hold: {}ms", self.peak_hold.as_millis());
+
+        // Make room for the meters, as they are redrawn over the previous lines
+        let lines = 1 + self.passthrough.is_some() as usize;
+        print!("{}", "\n".repeat(lines));
+    }
+
+    fn input(&self, input: &[f32]) {
+        // Add the buffer to the pass-through
+        if let Some(i) = &self.passthrough {
+            i.lock().add_samples(input);
+        }
+
+        let samples = to_mono(input, self.ctx.input.channels() as usize);
+        self.input.lock().update(&samples, self.peak_hold);
+        self.render();
+    }
+
+    fn output(&self, output: &mut [f32]) {
+        // Writes the output from the pass-through and measures it
+        if let Some(i) = &self.passthrough {
+            i.lock().write_output(output);
+
+            let samples = to_mono(output, self.ctx.output.channels() as usize);
+            self.output.lock().update(&samples, self.peak_hold);
+        }
+    }
+}
+
+/// Gets how many characters of a bar an amplitude fills, where the bar goes from `floor` up to full scale in dBFS.
+/// Shared with the other modules that draw level bars.
+pub fn bar_position(amplitude: f32, floor: f32, width: usize) -> usize {
+    let fraction = (to_db(amplitude, floor) - floor) / -floor;
+    ((fraction * width as f32).round() as usize).min(width)
+}
+
+/// Picks the color of the bar at a level.
+fn level_color(db: f32) -> style::Color {
+    match db {
+        _ if db >= DB_RED => style::Color::Red,
+        _ if db >= DB_YELLOW => style::Color::Yellow,
+        _ => style::Color::Green,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{bar_position, Level, DB_MIN};
+
+    #[test]
+    fn test_level() {
+        let mut level = Level::new();
+        level.update(&[0.5, -0.5, 0.5, -0.5], Duration::from_secs(60));
+        assert_eq!(level.rms, 0.5);
+        assert_eq!(level.peak, 0.5);
+        assert!(level.clipped_at.is_none());
+
+        // The held peak stays until the hold time is up
+        level.update(&[1.0, 0.0], Duration::from_secs(60));
+        level.update(&[0.1, -0.1], Duration::from_secs(60));
+        assert_eq!(level.peak, 0.1);
+        assert_eq!(level.held, 1.0);
+        assert!(level.clipped_at.is_some());
+
+        level.update(&[0.1, -0.1], Duration::ZERO);
+        assert_eq!(level.held, 0.1);
+    }
+
+    #[test]
+    fn test_bar_position() {
+        assert_eq!(bar_position(0.0, DB_MIN, 60), 0);
+        assert_eq!(bar_position(1.0, DB_MIN, 60), 60);
+        assert_eq!(bar_position(0.1, DB_MIN, 60), 40);
+        assert_eq!(bar_position(2.0, DB_MIN, 60), 60);
+    }
+}
@@ -1,17 +1,52 @@
 //! The different modules (subcommands) that can be used in the program.
 
-use std::{borrow::Cow, thread};
+use std::{
+    borrow::Cow,
+    process,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Duration,
+};
 
 use clap::ArgMatches;
 use cpal::{InputCallbackInfo, OutputCallbackInfo, SupportedStreamConfig};
+use parking_lot::Mutex;
 
-use crate::misc::SampleRate;
+use crate::{
+    audio::{
+        agc::Agc,
+        algorithms::{ChannelSelect, Notch},
+        passthrough::PassThrough,
+    },
+    misc::SampleRate,
+};
 
+/// How often the main thread checks if the module has finished.
+const FINISHED_POLL: Duration = Duration::from_millis(10);
+/// The quality factor of the `--notch` filters, the notch is the frequency over this wide.
+const NOTCH_Q: f32 = 10.0;
+
+/// Set once the input has nothing left to give, like at the end of a WAV file.
+static INPUT_ENDED: AtomicBool = AtomicBool::new(false);
+/// Set once the program is asked to stop, by pressing Ctrl-C or escape.
+static STOPPED: AtomicBool = AtomicBool::new(false);
+
+pub mod afsk;
+pub mod ctcss;
+pub mod dataset;
 pub mod dtmf;
+pub mod frequency_counter;
+pub mod meter;
 pub mod morse;
 pub mod range_test;
+pub mod rtty;
+pub mod scope;
+pub mod sequence;
 pub mod spectrum_analyzer;
+pub mod sweep;
+pub mod tones;
 pub mod true_random;
+pub mod tune;
 
 /// The trait implemented by all modules that allows handling audio input and output.
 pub trait Module {
@@ -19,14 +54,19 @@ pub trait Module {
     fn name(&self) -> &'static str;
     /// Called when the module is initialized.
     fn init(&self) {}
+    /// Checks if the module has nothing left to do, like when a transmission is done.
+    /// Once this returns true the streams are stopped and the program exits.
+    /// By default modules finish once the input has ended, see [`input_ended`].
+    fn is_finished(&self) -> bool {
+        input_ended()
+    }
     /// Runs after all the setup stuff had been done in the main thread.
     /// Lets the module take contraol of it.
     /// This is useful if a module is opening a window, which is an operation that should be preformed on the main thread because of macos compatibility.
     /// (i think)
-    fn block(&self) -> ! {
-        loop {
-            thread::park()
-        }
+    /// By default this just waits for the module to finish, see [`wait_until`].
+    fn block(&self) {
+        wait_until(|| self.is_finished());
     }
     /// Input callback.
     /// The different channels are interleaved, so if there are two channels the format will be `[L, R, L, R, ...]`.
@@ -39,12 +79,21 @@ pub trait Module {
 
     /// Raw input callback.
     /// This takes in the raw input data, without any gain applied.
-    /// Will call `self.input` by default.
-    fn input_raw(&self, input: &[f32], _info: &InputCallbackInfo, gain: f32) {
-        let input = match gain {
+    /// Will apply the input gain, then the AGC if it is enabled, and call `self.input` by default.
+    fn input_raw(
+        &self,
+        input: &[f32],
+        _info: &InputCallbackInfo,
+        gain: f32,
+        agc: Option<&mut Agc>,
+    ) {
+        let mut input = match gain {
             i if i == 1.0 => Cow::Borrowed(input),
             _ => Cow::Owned(input.iter().map(|&x| x * gain).collect()),
         };
+        if let Some(agc) = agc {
+            agc.process(input.to_mut());
+        }
         self.input(&input);
     }
 
@@ -67,6 +116,8 @@ pub trait Module {
 #[derive(Clone)]
 pub struct InitContext {
     pub args: ArgMatches,
+    /// The name of the input device, or the path of the WAV file being read from.
+    pub input_name: String,
     pub input: SupportedStreamConfig,
     pub output: SupportedStreamConfig,
 }
@@ -76,3 +127,94 @@ impl InitContext {
         SampleRate::new(self.input.sample_rate().0, self.output.sample_rate().0)
     }
 }
+
+/// Marks the input as ended, so the modules waiting on it can finish.
+pub fn end_input() {
+    INPUT_ENDED.store(true, Ordering::Relaxed);
+}
+
+/// Checks if the input has ended, which only happens when reading from a WAV file without looping.
+pub fn input_ended() -> bool {
+    INPUT_ENDED.load(Ordering::Relaxed)
+}
+
+/// Asks the program to stop, so it shuts down just like when the module finishes.
+/// The streams are dropped on the way out, which also finalizes a `--wav-out` recording.
+pub fn stop() {
+    STOPPED.store(true, Ordering::Relaxed);
+}
+
+/// Checks if the program has been asked to stop with [`stop`].
+pub fn stopped() -> bool {
+    STOPPED.load(Ordering::Relaxed)
+}
+
+/// Waits until `finished` returns true or the program is asked to stop with [`stop`].
+pub fn wait_until(finished: impl Fn() -> bool) {
+    while !finished() && !stopped() {
+        thread::sleep(FINISHED_POLL);
+    }
+}
+
+/// Creates the pass-through of a module if `enabled`, using the `--channel-map` and `--resample-quality` args.
+/// Exits if the channel map uses a channel the input or output doesn't have.
+pub fn passthrough(
+    args: &ArgMatches,
+    ctx: &InitContext,
+    enabled: bool,
+) -> Option<Mutex<PassThrough>> {
+    if !enabled {
+        return None;
+    }
+
+    let quality = *args.get_one("resample-quality").unwrap();
+    let channel_map = args.get_one("channel-map").cloned();
+    match PassThrough::new(&ctx.input, &ctx.output, channel_map, 1024, quality) {
+        Ok(passthrough) => Some(Mutex::new(passthrough)),
+        Err(e) => {
+            println!("[E] Invalid channel map: {e}");
+            process::exit(1);
+        }
+    }
+}
+
+/// Creates a notch filter for each `--notch` frequency, for the interleaved channels of the input.
+pub fn notch_filters(args: &ArgMatches, ctx: &InitContext) -> Mutex<Vec<Notch>> {
+    let channels = ctx.input.channels() as usize;
+    let sample_rate = ctx.sample_rate().input;
+    Mutex::new(
+        args.get_many::<f32>("notch")
+            .into_iter()
+            .flatten()
+            .map(|&freq| Notch::new(channels, freq, NOTCH_Q, sample_rate))
+            .collect(),
+    )
+}
+
+/// Gets the `--channel` a decoder listens to.
+/// Exits if the channel doesn't exist on the input.
+pub fn input_channel(args: &ArgMatches, ctx: &InitContext) -> ChannelSelect {
+    let select = *args.get_one::<ChannelSelect>("channel").unwrap();
+    let channels = ctx.input.channels() as usize;
+    if let ChannelSelect::Index(channel) = select {
+        if channel >= channels {
+            println!("[E] Channel {channel} does not exist, the input has {channels} channels");
+            process::exit(1);
+        }
+    }
+    select
+}
+
+/// Runs the input through the notch filters, only copying it if there are any.
+pub fn apply_notches<'a>(filters: &Mutex<Vec<Notch>>, input: &'a [f32]) -> Cow<'a, [f32]> {
+    let mut filters = filters.lock();
+    if filters.is_empty() {
+        return Cow::Borrowed(input);
+    }
+
+    let mut input = input.to_vec();
+    for filter in filters.iter_mut() {
+        filter.process(&mut input);
+    }
+    Cow::Owned(input)
+}
@@ -1,2 +1,14 @@
+use clap::ArgMatches;
+
+pub mod morse_key;
 pub mod morse_receive;
 pub mod morse_send;
+
+/// Gets the dit length in milliseconds from the command line arguments.
+/// If a speed in words per minute is given, it is converted using the PARIS standard.
+fn dit_length(args: &ArgMatches) -> u64 {
+    match args.get_one::<u64>("wpm") {
+        Some(wpm) => 1200 / wpm,
+        None => *args.get_one::<u64>("dit").unwrap(),
+    }
+}
@@ -0,0 +1,209 @@
+//! Turns the keyboard into a straight key.
+//! A tone is played while the key is held, and what is sent is decoded live so you can check your timing.
+
+use std::{
+    io::{self, Write},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use parking_lot::Mutex;
+
+use crate::{
+    audio::tone::Tone,
+    coding::morse::MorseDecoder,
+    misc::{terminal::TerminalGuard, SampleRate},
+    modules::{InitContext, Module},
+};
+
+/// How long the tone takes to fade in and out, in milliseconds, to prevent popping.
+const RAMP_MS: f32 = 5.0;
+/// How often the keyboard is checked for events.
+const KEY_POLL: Duration = Duration::from_millis(5);
+/// How many chunks each dit is split into for the decoder.
+const DECODE_CHUNKS_PER_DIT: usize = 8;
+
+pub struct MorseKey {
+    // == Settings ==
+    ctx: InitContext,
+    key: char,
+    /// The number of samples given to the decoder at a time.
+    chunk: usize,
+
+    // == Data ==
+    /// If the key is currently held down.
+    keyed: AtomicBool,
+    tone: Mutex<KeyedTone>,
+    decoder: Mutex<MorseDecoder>,
+    /// The sent samples waiting to be decoded.
+    buffer: Mutex<Vec<f32>>,
+    terminal: Mutex<Option<TerminalGuard>>,
+}
+
+/// A tone that fades in and out as the key is pressed and released.
+/// Unlike a [`crate::audio::tone::SmoothTone`], the length of the tone doesn't need to be known ahead of time.
+struct KeyedTone {
+    tone: Tone,
+    /// The current volume, from 0 to 1.
+    volume: f32,
+    /// How much the volume changes each sample.
+    step: f32,
+}
+
+impl MorseKey {
+    pub fn new(ctx: InitContext) -> Arc<Self> {
+        // Without key releases, a key can't be told apart from one held until it starts repeating
+        if !reports_releases() {
+            println!("[E] This terminal doesn't report key releases, which are needed for keying");
+            println!("[E] Try one with the kitty keyboard protocol, like kitty or WezTerm");
+            process::exit(1);
+        }
+
+        // Load command line arguments
+        let dit = super::dit_length(&ctx.args);
+        let frequency = *ctx.args.get_one::<f32>("frequency").unwrap();
+        let key = *ctx
+            .args
+            .subcommand()
+            .unwrap()
+            .1
+            .get_one::<char>("key")
+            .unwrap();
+        let sample_rate = ctx.sample_rate().output;
+
+        // The sent audio is decoded, so the decoder runs at the output sample rate.
+        // Hand keyed morse is never perfectly timed, so the speed is adapted to the sender.
+        let decoder = MorseDecoder::new(
+            SampleRate::new(sample_rate, sample_rate),
+            frequency,
+            dit,
+            |c| {
+                let mut stdout = io::stdout();
+                stdout
+                    .write_all(c.encode_utf8(&mut [0; 4]).as_bytes())
+                    .unwrap();
+                stdout.flush().unwrap();
+            },
+        )
+        .auto_speed(true);
+
+        let chunk = sample_rate as usize * dit as usize / 1000 / DECODE_CHUNKS_PER_DIT;
+        let tone = KeyedTone {
+            tone: Tone::new(frequency, ctx.sample_rate()),
+            volume: 0.0,
+            step: 1000.0 / (RAMP_MS * sample_rate as f32),
+        };
+
+        Arc::new(Self {
+            ctx,
+            key,
+            chunk: chunk.max(1),
+            keyed: AtomicBool::new(false),
+            tone: Mutex::new(tone),
+            decoder: Mutex::new(decoder),
+            buffer: Mutex::new(Vec::new()),
+            terminal: Mutex::new(None),
+        })
+    }
+}
+
+impl KeyedTone {
+    /// Gets the next sample, fading towards full volume if `keyed` or silence if not.
+    fn next(&mut self, keyed: bool) -> f32 {
+        self.volume = match keyed {
+            true => (self.volume + self.step).min(1.0),
+            false => (self.volume - self.step).max(0.0),
+        };
+
+        let sample = self.tone.next().unwrap();
+        match self.volume == 0.0 {
+            // Restart the tone while silent, so every tone starts at the same phase
+            true => {
+                self.tone.reset();
+                0.0
+            }
+            false => sample * self.volume,
+        }
+    }
+}
+
+impl Module for MorseKey {
+    fn name(&self) -> &'static str {
+        "morse-key"
+    }
+
+    fn init(&self) {
+        let key = match self.key {
+            ' ' => "SPACE".to_owned(),
+            i => i.to_uppercase().to_string(),
+        };
+        println!("[*] Hold {key} to key, ESC to quit\n");
+
+        // Ask the terminal to report key releases, support was checked when the module was created
+        *self.terminal.lock() = Some(TerminalGuard::keys());
+    }
+
+    /// Reads the keyboard until escape is pressed.
+    fn block(&self) {
+        loop {
+            if !event::poll(KEY_POLL).unwrap() {
+                continue;
+            }
+
+            if let Event::Key(e) = event::read().unwrap() {
+                match e.code {
+                    KeyCode::Esc => break,
+                    KeyCode::Char(c) if c.to_ascii_lowercase() == self.key => {
+                        let keyed = e.kind != KeyEventKind::Release;
+                        self.keyed.store(keyed, Ordering::Relaxed);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        drop(self.terminal.lock().take());
+        println!();
+    }
+
+    fn output(&self, output: &mut [f32]) {
+        let keyed = self.keyed.load(Ordering::Relaxed);
+        let mut tone = self.tone.lock();
+        let mut buffer = self.buffer.lock();
+
+        let mut last = 0.0;
+        for (i, e) in output.iter_mut().enumerate() {
+            if i % self.ctx.output.channels() as usize == 0 {
+                last = tone.next(keyed);
+                buffer.push(last);
+            }
+
+            *e = last;
+        }
+
+        // Decode what was sent
+        let mut decoder = self.decoder.lock();
+        for _ in 0..buffer.len() / self.chunk {
+            decoder.process(&buffer[..self.chunk]);
+            buffer.drain(..self.chunk);
+        }
+    }
+}
+
+/// Checks if the terminal reports when keys are released.
+/// Terminals need to support the kitty keyboard protocol for this.
+#[cfg(unix)]
+fn reports_releases() -> bool {
+    crossterm::terminal::supports_keyboard_enhancement().unwrap_or(false)
+}
+
+/// The Windows console always reports when keys are released.
+#[cfg(not(unix))]
+fn reports_releases() -> bool {
+    true
+}
@@ -1,6 +1,5 @@
 use std::{
     io::{self, Write},
-    process,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
@@ -10,8 +9,12 @@ use std::{
 use parking_lot::Mutex;
 
 use crate::{
+    audio::{
+        algorithms::{ChannelSelect, Notch},
+        windows::BoxedWindow,
+    },
     coding::morse::MorseDecoder,
-    modules::{InitContext, Module},
+    modules::{self, InitContext, Module},
 };
 
 const MORSE_CHUNK: usize = 512;
@@ -20,30 +23,45 @@ pub struct MorseReceive {
     ctx: InitContext,
     decoder: Mutex<MorseDecoder>,
     buffer: Mutex<Vec<f32>>,
+    channel: ChannelSelect,
+    notches: Mutex<Vec<Notch>>,
     last_state: AtomicBool,
+    /// Set when the decoder goes idle after receiving a message.
+    finished: AtomicBool,
 }
 
 impl MorseReceive {
     pub fn new(ctx: InitContext) -> Arc<Self> {
         // Load command line arguments
-        let dit = *ctx.args.get_one::<u64>("dit").unwrap();
+        let dit = super::dit_length(&ctx.args);
         let frequency = *ctx.args.get_one::<f32>("frequency").unwrap();
+        let args = ctx.args.subcommand().unwrap().1;
+        let threshold_factor = *args.get_one::<f32>("threshold-factor").unwrap();
+        let auto_speed = args.get_flag("auto-speed");
+        let window = args.get_one::<Arc<BoxedWindow>>("window").unwrap().clone();
+        let channel = modules::input_channel(args, &ctx);
+        let notches = modules::notch_filters(args, &ctx);
 
         // Create the morse decoder
         let decoder = MorseDecoder::new(ctx.sample_rate(), frequency, dit, |c| {
-            let mut bytes = [0; 4];
-            c.encode_utf8(&mut bytes);
-
             let mut stdout = io::stdout();
-            stdout.write_all(&bytes).unwrap();
+            stdout
+                .write_all(c.encode_utf8(&mut [0; 4]).as_bytes())
+                .unwrap();
             stdout.flush().unwrap();
-        });
+        })
+        .threshold_factor(threshold_factor)
+        .auto_speed(auto_speed)
+        .window(window);
 
         Arc::new(Self {
             ctx,
             decoder: Mutex::new(decoder),
             buffer: Mutex::new(Vec::new()),
+            channel,
+            notches,
             last_state: AtomicBool::new(true),
+            finished: AtomicBool::new(false),
         })
     }
 }
@@ -57,20 +75,21 @@ impl Module for MorseReceive {
         println!();
     }
 
+    fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed) || modules::input_ended()
+    }
+
     fn input(&self, input: &[f32]) {
         let is_idle = self.decoder.lock().is_idle();
         if !self.last_state.swap(is_idle, Ordering::Relaxed) && is_idle {
-            process::exit(0);
+            self.finished.store(true, Ordering::Relaxed);
         }
 
-        let channels = self.ctx.input.channels() as usize;
+        let input = modules::apply_notches(&self.notches, input);
         let mut buffer = self.buffer.lock();
         buffer.extend(
-            input
-                .iter()
-                .enumerate()
-                .filter(|(i, _)| i % channels == 0)
-                .map(|(_, e)| e),
+            self.channel
+                .select(&input, self.ctx.input.channels() as usize),
         );
 
         let mut decoder = self.decoder.lock();
@@ -1,7 +1,15 @@
 //! Morse code module.
 //! Currently a work in progress.
 
-use std::{process, sync::Arc};
+use std::{
+    fs, io,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+};
 
 use parking_lot::Mutex;
 
@@ -13,29 +21,58 @@ use crate::{
 pub struct MorseSend {
     ctx: InitContext,
     encoder: Mutex<MorseEncoder>,
+    /// Set once all the text has been read, which is right away unless reading from stdin.
+    read_all: AtomicBool,
 }
 
 impl MorseSend {
     pub fn new(ctx: InitContext) -> Arc<Self> {
         // Load command line arguments
-        let dit = *ctx.args.get_one::<u64>("dit").unwrap();
+        let dit = super::dit_length(&ctx.args);
         let frequency = *ctx.args.get_one::<f32>("frequency").unwrap();
-        let text = ctx
-            .args
-            .subcommand()
-            .unwrap()
-            .1
-            .get_one::<String>("text")
-            .unwrap();
-
-        // Create the morse encoder and add the data
-        let mut encoder = MorseEncoder::new(ctx.sample_rate(), frequency, dit);
-        encoder.add_data(text).unwrap();
-
-        Arc::new(Self {
+        let args = ctx.args.subcommand().unwrap().1;
+        let text = match args.get_one::<PathBuf>("file") {
+            Some(path) => Some(fs::read_to_string(path).expect("Failed to read text file")),
+            None => args
+                .get_one::<String>("text")
+                .filter(|x| *x != "-")
+                .cloned(),
+        };
+
+        // Create the morse encoder
+        let encoder = MorseEncoder::new(ctx.sample_rate(), frequency, dit);
+        let this = Arc::new(Self {
             ctx,
             encoder: Mutex::new(encoder),
-        })
+            read_all: AtomicBool::new(text.is_some()),
+        });
+
+        // Add the text, or read it from stdin as it comes in so it can be sent live
+        match text {
+            Some(text) => text
+                .lines()
+                .enumerate()
+                .for_each(|(i, line)| this.add_line(i, line)),
+            None => {
+                let this = this.clone();
+                thread::spawn(move || {
+                    for (i, line) in io::stdin().lines().enumerate() {
+                        this.add_line(i, &line.expect("Failed to read stdin"));
+                    }
+                    this.read_all.store(true, Ordering::Relaxed);
+                });
+            }
+        }
+
+        this
+    }
+
+    /// Adds a line of text to the encoder, followed by a word space.
+    /// Lines with invalid characters are skipped with an error, so the rest of the message is still sent.
+    fn add_line(&self, line: usize, text: &str) {
+        if let Err(e) = self.encoder.lock().add_data(&format!("{text} ")) {
+            println!("[E] Skipping line {}: {e}", line + 1);
+        }
     }
 }
 
@@ -44,12 +81,13 @@ impl Module for MorseSend {
         "morse-code"
     }
 
+    fn is_finished(&self) -> bool {
+        self.read_all.load(Ordering::Relaxed) && self.encoder.lock().is_idle()
+    }
+
     fn output(&self, output: &mut [f32]) {
         // Just pass the data from the encoder to the output of each channel
         let mut encoder = self.encoder.lock();
-        if encoder.is_idle() {
-            process::exit(0);
-        }
 
         let mut last = 0.0;
         for (i, e) in output.iter_mut().enumerate() {
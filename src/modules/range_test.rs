@@ -1,43 +1,89 @@
 //! Lets you test the range if your radio system.
-//! If it receives the DTMF tones defined in the const below,
-//! it will play back a tone.
-
-const CODE: &[u8] = b"ABCD";
+//! If it receives the DTMF code passed in with `--code`,
+//! it will play back a response tone or sequence.
+
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
-use std::sync::Arc;
+use chrono::Local;
 
 use parking_lot::Mutex;
 
 use super::{InitContext, Module};
 use crate::{
-    audio::{sequence::Sequence, tone::Tone},
-    coding::dtmf::DtmfDecoder,
+    audio::{algorithms::ChannelSelect, sequence::Sequence, tone::Tone},
+    coding::dtmf::{DtmfConfig, DtmfDecoder, DtmfEncoder},
 };
 
 const DTMF_CHUNK: usize = 512;
 
 pub struct RangeTest {
     ctx: InitContext,
+    code: Vec<u8>,
+    /// The response tones, which are copied into `tone` each time the code is heard.
+    response_tone: Sequence<Tone>,
+    log: Option<PathBuf>,
+    channel: ChannelSelect,
     dtmf: Mutex<Option<DtmfDecoder>>,
     tone: Mutex<Sequence<Tone>>,
+    /// The sequence number sent in DTMF after the response tone.
+    response: Mutex<Option<DtmfEncoder>>,
+    sequence: AtomicU32,
     work: Mutex<Vec<f32>>,
-    history: Mutex<Vec<u8>>,
+    /// The received DTMF characters and when they were heard.
+    history: Mutex<Vec<(u8, Instant)>>,
 }
 
 impl RangeTest {
     pub fn new(ctx: InitContext) -> Arc<Self> {
         let sr = ctx.sample_rate();
+        let waveform = *ctx.args.get_one("waveform").unwrap();
+        let code = ctx
+            .args
+            .get_one::<String>("code")
+            .unwrap()
+            .as_bytes()
+            .to_vec();
+        let response_seq = match ctx.args.get_one::<String>("response") {
+            Some(seq) => seq.to_owned(),
+            None => format!(
+                "{};{}",
+                ctx.args.get_one::<f32>("response-freq").unwrap(),
+                ctx.args.get_one::<f32>("response-duration").unwrap()
+            ),
+        };
+        let response_tone = Sequence::from_seq(&response_seq, sr)
+            .expect("Invalid response")
+            .waveform(waveform);
+        let log = ctx.args.get_one::<PathBuf>("log").cloned();
+        let channel = super::input_channel(&ctx.args, &ctx);
         let out = Arc::new(Self {
             ctx,
+            code,
+            response_tone,
+            log,
+            channel,
             dtmf: Mutex::new(None),
             tone: Mutex::new(Sequence::new()),
+            response: Mutex::new(None),
+            sequence: AtomicU32::new(0),
             work: Mutex::new(Vec::new()),
             history: Mutex::new(Vec::new()),
         });
 
         // Create a new DTMF decoder and set its callback to self.callback
         let this = out.clone();
-        *out.dtmf.lock() = Some(DtmfDecoder::new(sr, move |x| this.callback(x as char)));
+        *out.dtmf.lock() = Some(DtmfDecoder::new(sr, DtmfConfig::standard(), move |x, _| {
+            this.callback(x as char)
+        }));
 
         out
     }
@@ -46,17 +92,73 @@ impl RangeTest {
         // Print the raw DTMF character and add it to a history buffer
         println!("[*] Got code: {chr}");
         let mut history = self.history.lock();
-        history.push(chr as u8);
+        history.push((chr as u8, Instant::now()));
 
         // If the history is long enough, check if the last few bytes match the code
-        // If so play a tone (440Hz for 5 seconds) and clear the history
-        if history.len() >= CODE.len() && &history[history.len() - CODE.len()..] == CODE {
-            println!("GOT CODE");
+        // If so play the response, then the sequence number in DTMF and clear the history
+        let code = &self.code;
+        let start = match history.len().checked_sub(code.len()) {
+            Some(i) => i,
+            None => return,
+        };
+
+        if history[start..]
+            .iter()
+            .map(|x| x.0)
+            .eq(code.iter().copied())
+        {
+            let latency = history[start].1.elapsed();
+            let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+            println!(
+                "[*] Got trigger code in {:.2}s, responding with #{sequence}",
+                latency.as_secs_f32()
+            );
+
             let sr = self.ctx.sample_rate();
-            *self.tone.lock() = Sequence::from_seq("440;5", sr);
+            let response = format!("*{sequence}#");
+            *self.tone.lock() = self.response_tone.clone();
+            *self.response.lock() = Some(DtmfEncoder::new(
+                response.as_bytes(),
+                DtmfConfig::standard(),
+                sr,
+            ));
+
+            self.log_exchange(sequence, latency);
             history.clear();
         }
     }
+
+    /// Appends an exchange to the CSV log file, if one was given.
+    fn log_exchange(&self, sequence: u32, latency: Duration) {
+        let path = match &self.log {
+            Some(i) => i,
+            None => return,
+        };
+
+        let mut file = match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(i) => i,
+            Err(e) => {
+                println!("[E] Failed to open log file: {e}");
+                return;
+            }
+        };
+
+        // Write the header if the file is new
+        let mut line = String::new();
+        if file.metadata().map(|x| x.len() == 0).unwrap_or(false) {
+            line.push_str("timestamp,sequence,latency_ms\n");
+        }
+
+        line.push_str(&format!(
+            "{},{sequence},{}\n",
+            Local::now().to_rfc3339(),
+            latency.as_millis()
+        ));
+
+        if let Err(e) = file.write_all(line.as_bytes()) {
+            println!("[E] Failed to write to log file: {e}");
+        }
+    }
 }
 
 impl Module for RangeTest {
@@ -65,14 +167,11 @@ impl Module for RangeTest {
     }
 
     fn input(&self, input: &[f32]) {
-        // Add samples from the first channel to the work buffer
+        // Add samples from the selected channel to the work buffer
         let mut work = self.work.lock();
         work.extend(
-            input
-                .iter()
-                .enumerate()
-                .filter(|x| x.0 % self.ctx.input.channels() as usize == 0)
-                .map(|x| *x.1),
+            self.channel
+                .select(input, self.ctx.input.channels() as usize),
         );
 
         // If the buffer is long enough, process it with the DTMF decoder
@@ -83,13 +182,17 @@ impl Module for RangeTest {
     }
 
     fn output(&self, output: &mut [f32]) {
-        // If the tone is playing, send it to the output
+        // If the tone or sequence number is playing, send it to the output
         let mut tone = self.tone.lock();
+        let mut response = self.response.lock();
         let mut last = 0.0;
 
         for (i, e) in output.iter_mut().enumerate() {
             if i % self.ctx.output.channels() as usize == 0 {
-                last = tone.next().unwrap_or(0.);
+                last = tone
+                    .next()
+                    .or_else(|| response.as_mut()?.next())
+                    .unwrap_or(0.);
             }
 
             *e = last;
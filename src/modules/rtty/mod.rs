@@ -0,0 +1,15 @@
+use clap::ArgMatches;
+
+use crate::coding::{afsk::AfskConfig, rtty};
+
+pub mod rtty_receive;
+pub mod rtty_send;
+
+/// Gets the AFSK config from the command line arguments.
+fn config(args: &ArgMatches) -> AfskConfig {
+    rtty::config(
+        *args.get_one::<f32>("mark").unwrap(),
+        *args.get_one::<f32>("shift").unwrap(),
+        *args.get_one::<f32>("baud").unwrap(),
+    )
+}
@@ -0,0 +1,65 @@
+use std::{
+    io::{self, Write},
+    sync::Arc,
+};
+
+use parking_lot::Mutex;
+
+use crate::{
+    coding::{afsk::AfskDecoder, rtty::BaudotDecoder},
+    modules::{InitContext, Module},
+};
+
+const RTTY_CHUNK: usize = 512;
+
+pub struct RttyReceive {
+    ctx: InitContext,
+    decode: Mutex<AfskDecoder>,
+    buffer: Mutex<Vec<f32>>,
+}
+
+impl RttyReceive {
+    pub fn new(ctx: InitContext) -> Arc<Self> {
+        // Decode the codes to text and print it as it comes in
+        let mut baudot = BaudotDecoder::default();
+        let decode = AfskDecoder::new(super::config(&ctx.args), ctx.sample_rate(), move |x| {
+            if let Some(c) = baudot.decode(x) {
+                let mut stdout = io::stdout();
+                stdout
+                    .write_all(c.encode_utf8(&mut [0; 4]).as_bytes())
+                    .unwrap();
+                stdout.flush().unwrap();
+            }
+        });
+
+        Arc::new(Self {
+            ctx,
+            decode: Mutex::new(decode),
+            buffer: Mutex::new(Vec::new()),
+        })
+    }
+}
+
+impl Module for RttyReceive {
+    fn name(&self) -> &'static str {
+        "rtty-receive"
+    }
+
+    fn input(&self, input: &[f32]) {
+        let channels = self.ctx.input.channels() as usize;
+        let mut buffer = self.buffer.lock();
+        buffer.extend(
+            input
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| i % channels == 0)
+                .map(|(_, e)| e),
+        );
+
+        let mut decode = self.decode.lock();
+        for _ in 0..buffer.len() / RTTY_CHUNK {
+            decode.process(&buffer[..RTTY_CHUNK]);
+            buffer.drain(..RTTY_CHUNK);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use parking_lot::Mutex;
+
+use crate::{
+    coding::{afsk::AfskEncoder, rtty},
+    modules::{InitContext, Module},
+};
+
+pub struct RttySend {
+    ctx: InitContext,
+    encode: Mutex<AfskEncoder>,
+    finished: AtomicBool,
+}
+
+impl RttySend {
+    pub fn new(ctx: InitContext) -> Arc<Self> {
+        let text = ctx
+            .args
+            .subcommand()
+            .unwrap()
+            .1
+            .get_one::<String>("text")
+            .unwrap();
+        let codes = rtty::encode(text);
+        let encode = AfskEncoder::new(&codes, super::config(&ctx.args), ctx.sample_rate());
+
+        Arc::new(Self {
+            encode: Mutex::new(encode),
+            finished: AtomicBool::new(false),
+            ctx,
+        })
+    }
+}
+
+impl Module for RttySend {
+    fn name(&self) -> &'static str {
+        "rtty-send"
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+
+    fn output(&self, output: &mut [f32]) {
+        let mut encode = self.encode.lock();
+        let mut last = 0.0;
+
+        for (i, e) in output.iter_mut().enumerate() {
+            if i % self.ctx.output.channels() as usize == 0 {
+                let val = encode.next();
+                if val.is_none() {
+                    self.finished.store(true, Ordering::Relaxed);
+                }
+                last = val.unwrap_or(0.0);
+            }
+
+            *e = last;
+        }
+    }
+}
@@ -0,0 +1,255 @@
+//! Oscilloscope module that draws the raw input waveform in the terminal.
+//! A trigger level and edge are used to line up each frame, so periodic waveforms appear stable.
+//! If no trigger is found in a frame, the waveform is drawn untriggered so the display never freezes.
+
+use std::{
+    io::{stdout, Write},
+    sync::Arc,
+    time::Duration,
+};
+
+use clap::ValueEnum;
+use crossterm::{
+    cursor,
+    event::{self, KeyCode},
+    execute, queue, style, terminal,
+};
+use parking_lot::Mutex;
+
+use super::{stop, wait_until, InitContext, Module};
+use crate::{
+    audio::algorithms::to_mono,
+    misc::{buf_writer::BufWriter, terminal::TerminalGuard},
+};
+
+/// The direction the signal has to cross the trigger level in.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Rising,
+    Falling,
+}
+
+pub struct Scope {
+    // == Settings ==
+    ctx: InitContext,
+    level: f32,
+    edge: Edge,
+    gain: f32,
+    /// The length of the time window in milliseconds.
+    window_ms: f32,
+    /// The number of samples in each frame.
+    window: usize,
+
+    // == Data ==
+    samples: Mutex<Vec<f32>>,
+    terminal: Mutex<Option<TerminalGuard>>,
+}
+
+impl Scope {
+    pub fn new(ctx: InitContext) -> Arc<Self> {
+        // Load command line arguments
+        let level = *ctx.args.get_one("level").unwrap();
+        let edge = *ctx.args.get_one("edge").unwrap();
+        let gain = *ctx.args.get_one("gain").unwrap();
+        let window_ms = *ctx.args.get_one::<f32>("window").unwrap();
+        let window = ((ctx.sample_rate().input as f32 * window_ms / 1000.0) as usize).max(2);
+
+        Arc::new(Self {
+            ctx,
+            level,
+            edge,
+            gain,
+            window_ms,
+            window,
+            samples: Mutex::new(Vec::with_capacity(window * 2)),
+            terminal: Mutex::new(None),
+        })
+    }
+
+    /// Draws a frame of samples over the whole terminal.
+    /// Each column shows the range of samples that fall into it, so no peaks are lost when zoomed out.
+    fn render(&self, frame: &[f32], triggered: bool) {
+        let mut stdout = BufWriter::new(stdout());
+        let (width, height) = terminal::size().unwrap();
+        let (width, rows) = (width as usize, height.saturating_sub(2) as usize);
+        if width == 0 || rows == 0 {
+            return;
+        }
+
+        // Converts a sample into a row, with +1 at the top and -1 at the bottom
+        let to_row = |x: f32| {
+            let x = ((x * self.gain).clamp(-1.0, 1.0) + 1.0) / 2.0;
+            ((1.0 - x) * (rows - 1) as f32).round() as usize
+        };
+
+        let mut grid = vec![vec![' '; width]; rows];
+        grid[to_row(0.0)].fill('─');
+        grid[to_row(self.level)]
+            .iter_mut()
+            .step_by(2)
+            .for_each(|x| *x = '┄');
+
+        for (x, column) in grid_columns(frame, width).enumerate() {
+            let (min, max) = column.iter().fold((f32::MAX, f32::MIN), |(min, max), &i| {
+                (min.min(i), max.max(i))
+            });
+            for row in grid.iter_mut().take(to_row(min) + 1).skip(to_row(max)) {
+                row[x] = '█';
+            }
+        }
+
+        queue!(
+            stdout,
+            cursor::MoveTo(0, 0),
+            style::Print(self.top_line(width, triggered)),
+        )
+        .unwrap();
+        for (i, row) in grid.into_iter().enumerate() {
+            queue!(
+                stdout,
+                cursor::MoveTo(0, i as u16 + 1),
+                style::Print(row.into_iter().collect::<String>()),
+            )
+            .unwrap();
+        }
+
+        // Prints the time labels along the bottom, about every 12 characters
+        queue!(
+            stdout,
+            cursor::MoveTo(0, height.saturating_sub(1)),
+            terminal::Clear(terminal::ClearType::CurrentLine)
+        )
+        .unwrap();
+        for x in (0..width.saturating_sub(10)).step_by(12) {
+            let time = x as f32 / width as f32 * self.window_ms;
+            queue!(
+                stdout,
+                cursor::MoveToColumn(x as u16),
+                style::Print(format!("└{time:.1}ms")),
+            )
+            .unwrap();
+        }
+
+        stdout.flush().unwrap();
+    }
+
+    /// Defines the top status line.
+    /// It shows the time window, the trigger settings and the gain.
+    /// The trigger is shown as `Auto` when no edge was found in the last frame.
+    fn top_line(&self, width: usize, triggered: bool) -> String {
+        let start = "[RADIO-DATA SCOPE]";
+        let end = format!(
+            "{{Window: {:.1}ms, Trigger: {:.2} {:?} ({}), Gain: {:.1}}} [ESC: Quit]",
+            self.window_ms,
+            self.level,
+            self.edge,
+            match triggered {
+                true => "Locked",
+                false => "Auto",
+            },
+            self.gain
+        );
+
+        let diff = width.saturating_sub(start.len() + end.len());
+        format!("{}{}{}", start, " ".repeat(diff), end)
+    }
+
+    fn handle_events(&self) {
+        // Returns if there are no events to process
+        if !event::poll(Duration::ZERO).unwrap() {
+            return;
+        }
+
+        match event::read().unwrap() {
+            // Stop the program if escape is pressed
+            event::Event::Key(e) => {
+                if e.code == KeyCode::Esc {
+                    stop();
+                }
+            }
+            // Clear the screen if the terminal is resized
+            event::Event::Resize(..) => {
+                execute!(stdout(), terminal::Clear(terminal::ClearType::All)).unwrap()
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Module for Scope {
+    fn name(&self) -> &'static str {
+        "scope"
+    }
+
+    fn init(&self) {
+        *self.terminal.lock() = Some(TerminalGuard::enter());
+    }
+
+    /// Waits until the input has ended or escape is pressed, then restores the terminal.
+    fn block(&self) {
+        wait_until(|| self.is_finished());
+        drop(self.terminal.lock().take());
+    }
+
+    fn input(&self, input: &[f32]) {
+        let mut samples = self.samples.lock();
+        samples.extend(to_mono(input, self.ctx.input.channels() as usize));
+
+        // Wait for two windows worth of samples, so a trigger anywhere in the first one has a full frame after it
+        while samples.len() >= self.window * 2 {
+            let trigger = find_trigger(&samples[..=self.window], self.level, self.edge);
+            let start = trigger.unwrap_or(0);
+
+            self.render(&samples[start..start + self.window], trigger.is_some());
+            samples.drain(..start + self.window);
+        }
+
+        self.handle_events();
+    }
+}
+
+/// Finds the index of the first sample where the signal crosses `level` in the direction of `edge`.
+fn find_trigger(samples: &[f32], level: f32, edge: Edge) -> Option<usize> {
+    samples
+        .windows(2)
+        .position(|x| match edge {
+            Edge::Rising => x[0] < level && x[1] >= level,
+            Edge::Falling => x[0] > level && x[1] <= level,
+        })
+        .map(|x| x + 1)
+}
+
+/// Splits a frame into `width` roughly equal columns.
+/// If there are fewer samples than columns, samples are repeated over multiple columns.
+fn grid_columns(frame: &[f32], width: usize) -> impl Iterator<Item = &[f32]> {
+    (0..width).map(move |x| {
+        let start = x * frame.len() / width;
+        let end = ((x + 1) * frame.len() / width).max(start + 1);
+        &frame[start..end.min(frame.len())]
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{find_trigger, grid_columns, Edge};
+
+    #[test]
+    fn test_find_trigger() {
+        let samples = [0.5, -0.5, -0.2, 0.3, 0.8, 0.1, -0.4];
+        assert_eq!(find_trigger(&samples, 0.0, Edge::Rising), Some(3));
+        assert_eq!(find_trigger(&samples, 0.0, Edge::Falling), Some(1));
+        assert_eq!(find_trigger(&samples, 0.5, Edge::Rising), Some(4));
+        assert_eq!(find_trigger(&samples, 0.9, Edge::Rising), None);
+    }
+
+    #[test]
+    fn test_grid_columns() {
+        let frame = [1.0, 2.0, 3.0, 4.0, 5.0];
+        let columns = grid_columns(&frame, 2).collect::<Vec<_>>();
+        assert_eq!(columns, [&frame[..2], &frame[2..]]);
+
+        let columns = grid_columns(&frame[..2], 4).collect::<Vec<_>>();
+        assert_eq!(columns.len(), 4);
+        assert!(columns.iter().all(|x| x.len() == 1));
+    }
+}
@@ -0,0 +1,66 @@
+//! Plays a sequence of tones loaded from a file, then exits.
+//! See [`Sequence::from_seq`] for the format of the file.
+
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use parking_lot::Mutex;
+
+use super::{InitContext, Module};
+use crate::audio::{sequence::Sequence, tone::SmoothTone};
+
+pub struct SequencePlayer {
+    ctx: InitContext,
+    sequence: Mutex<Sequence<SmoothTone>>,
+    finished: AtomicBool,
+}
+
+impl SequencePlayer {
+    pub fn new(ctx: InitContext) -> Arc<Self> {
+        // Load the sequence file
+        let path = ctx.args.get_one::<PathBuf>("file").unwrap();
+        let seq = fs::read_to_string(path).expect("Failed to read sequence file");
+        let sequence = Sequence::from_seq(&seq, ctx.sample_rate()).expect("Invalid sequence file");
+
+        println!("[*] Playing sequence `{}`", path.display());
+        Arc::new(Self {
+            ctx,
+            sequence: Mutex::new(sequence),
+            finished: AtomicBool::new(false),
+        })
+    }
+}
+
+impl Module for SequencePlayer {
+    fn name(&self) -> &'static str {
+        "sequence"
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+
+    fn output(&self, output: &mut [f32]) {
+        // Pass the sequence to the output of each channel, exiting once it has finished
+        let mut sequence = self.sequence.lock();
+        let mut last = 0.0;
+
+        for (i, e) in output.iter_mut().enumerate() {
+            if i % self.ctx.output.channels() as usize == 0 {
+                let val = sequence.next();
+                if val.is_none() {
+                    self.finished.store(true, Ordering::Relaxed);
+                }
+                last = val.unwrap_or(0.0);
+            }
+
+            *e = last;
+        }
+    }
+}
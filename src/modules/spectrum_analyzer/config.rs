@@ -0,0 +1,95 @@
+//! Saving and loading the analyzer settings with TOML files.
+//! Anything set on the command line overrides the config, so a saved setup can still be tweaked.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::{parser::ValueSource, ArgMatches};
+use serde::{Deserialize, Serialize};
+
+use super::{ColorScheme, DisplayType};
+
+/// The settings that can be saved in a config file.
+/// Every field is optional, so a config only needs to have the settings it changes.
+/// The window and display range are kept in the same format as their command line args, like `kaiser:8.0` and `15..14000`.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    pub fft_size: Option<usize>,
+    pub display_range: Option<String>,
+    pub window: Option<String>,
+    pub gain: Option<f32>,
+    pub color_scheme: Option<ColorScheme>,
+    pub display_type: Option<DisplayType>,
+    pub passthrough: Option<bool>,
+}
+
+impl Config {
+    /// Loads the config file passed with `--config`, or an empty config if there isn't one.
+    /// The settings are then resolved against the command line args with [`Config::resolve`].
+    pub fn load(args: &ArgMatches) -> anyhow::Result<Self> {
+        let path = match args.get_one::<PathBuf>("config") {
+            Some(i) => i,
+            None => return Ok(Self::default()),
+        };
+
+        let raw = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config `{}`", path.display()))?;
+        let config = toml::from_str(&raw)
+            .with_context(|| format!("Failed to parse config `{}`", path.display()))?;
+        println!("[*] Loaded settings from `{}`", path.display());
+        Ok(config)
+    }
+
+    /// Fills in every setting, using the command line arg if it was given, then the config, then the arg's default.
+    /// The display type has no default, so it can still be None.
+    pub fn resolve(self, args: &ArgMatches) -> Self {
+        Self {
+            fft_size: pick(args, "fft-size", self.fft_size),
+            display_range: pick_raw(args, "display-range", self.display_range),
+            window: pick_raw(args, "window", self.window),
+            gain: pick(args, "gain", self.gain),
+            color_scheme: pick(args, "color-scheme", self.color_scheme),
+            display_type: pick(args, "display-type", self.display_type),
+            passthrough: pick(args, "passthrough", self.passthrough),
+        }
+    }
+
+    /// Writes the config to a TOML file, so it can be loaded later with `--config`.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        fs::write(path, toml::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write config `{}`", path.display()))?;
+        println!("[*] Saved settings to `{}`", path.display());
+        Ok(())
+    }
+}
+
+/// Picks the value of an arg, preferring the command line, then the config, then the default.
+fn pick<T: Clone + Send + Sync + 'static>(
+    args: &ArgMatches,
+    id: &str,
+    config: Option<T>,
+) -> Option<T> {
+    match args.value_source(id) {
+        Some(ValueSource::CommandLine) => args.get_one::<T>(id).cloned(),
+        _ => config.or_else(|| args.get_one::<T>(id).cloned()),
+    }
+}
+
+/// Like [`pick`], but uses the text of the arg before it was parsed.
+/// Used for args where the parsed value can't be turned back into text, like the window.
+fn pick_raw(args: &ArgMatches, id: &str, config: Option<String>) -> Option<String> {
+    let raw = || {
+        args.get_raw(id)
+            .and_then(|mut x| x.next())
+            .map(|x| x.to_string_lossy().into_owned())
+    };
+
+    match args.value_source(id) {
+        Some(ValueSource::CommandLine) => raw(),
+        _ => config.or_else(raw),
+    }
+}
@@ -1,15 +1,16 @@
 use std::{
+    fs,
     io::{stdout, Write},
-    panic, process,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use crate::{
-    misc::{buf_writer::BufWriter, soon::Soon},
-    modules::spectrum_analyzer::{get_color, COLOR_SCHEME},
-};
+use crate::misc::{buf_writer::BufWriter, soon::Soon, terminal::TerminalGuard};
+use chrono::Local;
 use crossbeam::channel::{self, Sender};
 use crossterm::{
     cursor,
@@ -18,44 +19,50 @@ use crossterm::{
 };
 use parking_lot::Mutex;
 
-use super::{nice_freq, Renderer, SpectrumAnalyzer};
+use super::{nice_freq, LabelInterval, Renderer, SpectrumAnalyzer, RENDER_BACKLOG};
+use crate::modules::{input_ended, stop, wait_until};
 
 const HALF_CHAR: &str = "▀";
+/// How long status messages are shown in the top line.
+const STATUS_TIME: Duration = Duration::from_secs(3);
+/// How much the gain changes with each press of the up and down arrow keys.
+const GAIN_STEP: f32 = 0.1;
+/// The range the gain can be adjusted in with the arrow keys.
+const GAIN_RANGE: (f32, f32) = (0.0, 10.0);
 
 pub struct ConsoleRenderer {
     analyzer: Arc<SpectrumAnalyzer>,
     last_samples: Mutex<Option<Vec<f32>>>,
+    /// While frozen, new rows are dropped instead of drawn
+    frozen: AtomicBool,
+    /// The most recent FFT frame, saved when capturing
+    last_frame: Mutex<Vec<f32>>,
+    /// A message shown in the top line, and when it was set
+    status: Mutex<Option<(String, Instant)>>,
+    terminal: Mutex<Option<TerminalGuard>>,
     render_thread: Soon<Sender<Vec<f32>>>,
 }
 
 impl Renderer for ConsoleRenderer {
     fn init(&self) {
-        // Sets a panic hook
-        // This is important because the terminal will be in a weird state if the program panics
-        // and you wont be able to close the program.
-        panic::set_hook(Box::new(|info| {
-            exit();
-            eprintln!("{info}");
-            process::exit(0)
-        }));
-
-        // Enables raw mode and enters the alternate screen
-        terminal::enable_raw_mode().unwrap();
+        // Enables raw mode and enters the alternate screen, until the renderer exits
+        *self.terminal.lock() = Some(TerminalGuard::enter());
 
         let height = terminal::size().unwrap().1;
-        execute!(
-            stdout(),
-            terminal::EnterAlternateScreen,
-            terminal::DisableLineWrap,
-            cursor::Hide,
-            cursor::MoveToRow(height)
-        )
-        .unwrap();
+        execute!(stdout(), cursor::MoveToRow(height)).unwrap();
     }
 
     fn render(&self, data: Vec<f32>) {
-        self.print_row(data);
-        self.handle_events();
+        // Hand the frame off to the render thread, so drawing never holds up the audio thread
+        if self.render_thread.try_send(data).is_err() {
+            self.analyzer.drop_frame();
+        }
+    }
+
+    /// Waits until the input has ended or escape is pressed, then restores the terminal.
+    fn block(&self) {
+        wait_until(input_ended);
+        drop(self.terminal.lock().take());
     }
 }
 
@@ -64,14 +71,18 @@ impl ConsoleRenderer {
         let this = Arc::new(Self {
             analyzer,
             last_samples: Mutex::new(None),
+            frozen: AtomicBool::new(false),
+            last_frame: Mutex::new(Vec::new()),
+            status: Mutex::new(None),
+            terminal: Mutex::new(None),
             render_thread: Soon::empty(),
         });
 
         let this_ref = this.clone();
-        let (tx, rx) = channel::unbounded();
+        let (tx, rx) = channel::bounded(RENDER_BACKLOG);
         thread::spawn(move || {
             for i in rx {
-                this_ref.print_row(i)
+                this_ref.draw(i)
             }
         });
         this.render_thread.replace(tx);
@@ -79,6 +90,15 @@ impl ConsoleRenderer {
         this
     }
 
+    /// Draws a frame and handles any terminal events, called from the render thread.
+    fn draw(&self, data: Vec<f32>) {
+        if !self.frozen.load(Ordering::Relaxed) {
+            *self.last_frame.lock() = data.clone();
+            self.print_row(data);
+        }
+        self.handle_events();
+    }
+
     fn print_row(&self, data: Vec<f32>) {
         // To double the vertical resolution, we use a box drawing character (▀) that is half filled.
         // This means by setting the foreground and background color to different values, we can draw more data on line.
@@ -144,8 +164,8 @@ impl ConsoleRenderer {
 
                 queue!(
                     stdout,
-                    style::SetForegroundColor(get_color(&vals, |x| x.1).into()),
-                    style::SetBackgroundColor(get_color(&vals, |x| x.0).into()),
+                    style::SetForegroundColor(self.analyzer.get_color(&vals, |x| x.1).into()),
+                    style::SetBackgroundColor(self.analyzer.get_color(&vals, |x| x.0).into()),
                     style::Print(bar),
                 )
                 .unwrap();
@@ -155,35 +175,62 @@ impl ConsoleRenderer {
 
         // // If we don't print a full line, we need to fill the rest with black.
         if console_size.0 as usize > full_size {
+            let background = self.analyzer.color_scheme.colors()[0];
             queue!(
                 stdout,
-                style::SetForegroundColor(COLOR_SCHEME[0].into()),
-                style::SetBackgroundColor(COLOR_SCHEME[0].into()),
+                style::SetForegroundColor(background.into()),
+                style::SetBackgroundColor(background.into()),
                 style::Print(HALF_CHAR.repeat(console_size.0 as usize - full_size)),
             )
             .unwrap();
         }
 
         // Prints the frequency labels on the bottom of the screen.
+        // Labels that would overlap the one before them are skipped, whatever the interval is.
         queue!(stdout, style::ResetColor, cursor::MoveDown(1)).unwrap();
-        let mut i = 0;
-        while i < freq_labels.len() {
-            let val = &freq_labels[i];
-            let freq = nice_freq(val.1);
-            i += ((freq.len() as f32 + 3.0) / bar_width) as usize;
+        let mut next_free = 0;
+        let mut next_char = 0;
+        let mut next_freq = 0.0;
+        let mut last_freq = 0.0;
+        for &(column, freq) in freq_labels.iter() {
+            let label = nice_freq(freq);
+            let width = label.len() + 1;
 
-            if val.0 + freq.len() >= console_size.0 as usize {
+            // Split spectrums start again from 0Hz for each channel
+            if freq < last_freq {
+                next_freq = 0.0;
+            }
+            last_freq = freq;
+
+            let wanted = match self.analyzer.label_interval {
+                LabelInterval::Auto => true,
+                LabelInterval::Chars(_) => column >= next_char,
+                LabelInterval::Hz(_) => freq >= next_freq,
+            };
+            if !wanted || column < next_free {
+                continue;
+            }
+
+            if column + width >= console_size.0 as usize {
                 break;
             }
 
             queue!(
                 stdout,
-                cursor::MoveToColumn(val.0 as u16),
-                style::Print(format!("└{freq}")),
+                cursor::MoveToColumn(column as u16),
+                style::Print(format!("└{label}")),
             )
             .unwrap();
 
-            i += 1;
+            next_free = match self.analyzer.label_interval {
+                LabelInterval::Auto => column + width + 2,
+                _ => column + width + 1,
+            };
+            match self.analyzer.label_interval {
+                LabelInterval::Chars(chars) => next_char = column + chars,
+                LabelInterval::Hz(hz) => next_freq = ((freq / hz).floor() + 1.0) * hz,
+                LabelInterval::Auto => {}
+            }
         }
 
         stdout.flush().unwrap();
@@ -198,13 +245,18 @@ impl ConsoleRenderer {
         }
 
         match event::read().unwrap() {
-            // Exit if escape is pressed
-            event::Event::Key(e) => {
-                if e.code == KeyCode::Esc {
-                    exit();
-                    process::exit(0);
+            // Exit if escape is pressed, freeze or unfreeze the display if space is pressed,
+            // capture the last frame if c is pressed and change the gain with the arrow keys
+            event::Event::Key(e) => match e.code {
+                KeyCode::Esc => stop(),
+                KeyCode::Char(' ') => {
+                    self.frozen.fetch_xor(true, Ordering::Relaxed);
                 }
-            }
+                KeyCode::Char('c') => self.capture(),
+                KeyCode::Up => self.change_gain(GAIN_STEP),
+                KeyCode::Down => self.change_gain(-GAIN_STEP),
+                _ => {}
+            },
             // Clear the screen if the terminal is resized
             event::Event::Resize(..) => {
                 execute!(stdout(), terminal::Clear(terminal::ClearType::All)).unwrap()
@@ -213,39 +265,65 @@ impl ConsoleRenderer {
         }
     }
 
+    /// Adds to the gain, keeping it within [`GAIN_RANGE`].
+    fn change_gain(&self, change: f32) {
+        let mut gain = self.analyzer.gain.write();
+        *gain = (*gain + change).clamp(GAIN_RANGE.0, GAIN_RANGE.1);
+    }
+
+    /// Saves the most recent FFT frame to a CSV file, with the frequency, magnitude and dBFS of each bin.
+    /// The gain is not applied, so captures can be compared with each other.
+    fn capture(&self) {
+        let mut csv = String::from("frequency_hz,magnitude,dbfs\n");
+        for (i, &mag) in self.last_frame.lock().iter().enumerate() {
+            csv.push_str(&format!(
+                "{},{mag},{}\n",
+                self.analyzer.index_to_freq(i),
+                self.analyzer.magnitude_db(mag)
+            ));
+        }
+
+        let name = format!("capture-{}.csv", Local::now().format("%Y-%m-%d-%H-%M-%S"));
+        let status = match fs::write(&name, csv) {
+            Ok(_) => format!("[Saved capture to `{name}`]"),
+            Err(e) => format!("[Failed to save capture: {e}]"),
+        };
+        *self.status.lock() = Some((status, Instant::now()));
+    }
+
     /// Defines the top status line.
     /// This line contains some stats about the current state of the program:
     /// - FFT size &mdash; The number of samples that are used for each FFT.
     /// - Domain &mdash; The frequency range that is currently displayed.
     /// - Gain &mdash; The gain that is applied to the data when displaying.
     /// - Res &mdash; The frequency resolution of each character used to display the spectrum.
-    /// - RMS &mdash; The Root Mean Square value of the current FFT data.
+    /// - RMS &mdash; The Root Mean Square value of the current FFT data (in dBFS when using the decibel scale).
     fn top_line(&self, size: (u16, u16), points_per_char: f32, rms: f32) -> String {
-        let start = "[RADIO-DATA SPECTRUM ANALYZER]";
+        let start = match &*self.status.lock() {
+            Some((status, time)) if time.elapsed() < STATUS_TIME => status.to_owned(),
+            _ => "[RADIO-DATA SPECTRUM ANALYZER]".to_owned(),
+        };
+        let mut extra = String::new();
+        if let Some(i) = self.analyzer.passthrough_underruns() {
+            extra.push_str(&format!(", Underruns: {i}"));
+        }
+        match self.analyzer.dropped_frames() {
+            0 => {}
+            i => extra.push_str(&format!(", Dropped: {i}")),
+        }
         let end = format!(
-            "{{FFT size: {}, Window: {}, Domain: {}..{}, Gain: {:.1}, Res: {}, RMS: {:.1}}} [ESC: Quit]",
-            self.analyzer.fft_size,
+            "{{FFT size: {}, Window: {}, Domain: {}..{}, Gain: {:.1}, Res: {}, RMS: {}{}}} [UP/DOWN: Gain, SPACE: Freeze, C: Capture, ESC: Quit]",
+            self.analyzer.fft_size(),
             self.analyzer.window.name(),
             nice_freq(self.analyzer.display_range.start as f32),
             nice_freq(self.analyzer.display_range.end as f32),
             self.analyzer.gain.read(),
-            nice_freq(self.analyzer.resolution * points_per_char),
-            rms
+            nice_freq(self.analyzer.resolution() * points_per_char),
+            self.analyzer.rms_label(rms),
+            extra
         );
 
         let diff = (size.0 as usize).saturating_sub(start.len() + end.len());
         format!("{}{}{}", start, " ".repeat(diff), end)
     }
 }
-
-/// Cleans up the terminal and disables raw mode before exiting.
-fn exit() {
-    execute!(
-        stdout(),
-        terminal::LeaveAlternateScreen,
-        terminal::EnableLineWrap,
-        cursor::Show
-    )
-    .unwrap();
-    terminal::disable_raw_mode().unwrap();
-}
@@ -0,0 +1,51 @@
+//! Writing the spectrum to a CSV file, so it can be analyzed with other programs.
+//! Each line is one frame, with a timestamp and then the magnitude of every bin.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+};
+
+use chrono::Local;
+
+/// Appends every frame of magnitudes to a CSV file.
+pub struct DataOut {
+    file: File,
+    /// The length of the rows the header was written for.
+    /// A new header is written if this changes, like when the FFT size is changed.
+    row_len: usize,
+}
+
+impl DataOut {
+    /// Opens the file at `path`, adding to the end of it if it already exists.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            file: OpenOptions::new().create(true).append(true).open(path)?,
+            row_len: 0,
+        })
+    }
+
+    /// Writes a frame of magnitudes, after the header if it is needed.
+    /// The header has the frequency of each bin, which are gotten with `freq`.
+    /// Each line is written all at once, so the file is still complete if the program is exited.
+    pub fn write(&mut self, row: &[f32], freq: impl Fn(usize) -> f32) -> io::Result<()> {
+        let mut line = String::new();
+        if row.len() != self.row_len {
+            self.row_len = row.len();
+            line.push_str("timestamp");
+            for i in 0..row.len() {
+                line.push_str(&format!(",{}", freq(i)));
+            }
+            line.push('\n');
+        }
+
+        line.push_str(&Local::now().to_rfc3339());
+        for x in row {
+            line.push_str(&format!(",{x}"));
+        }
+        line.push('\n');
+
+        self.file.write_all(line.as_bytes())
+    }
+}
@@ -0,0 +1,120 @@
+//! Headless renderer that writes the waterfall to PNG files instead of drawing it.
+//! Lets the spectrum analyzer run on a server with no display, like over SSH, and be looked over later.
+
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use chrono::Local;
+use image::{ImageBuffer, Rgba};
+use parking_lot::Mutex;
+
+use super::{color, Renderer, SpectrumAnalyzer};
+use crate::modules::{input_ended, wait_until};
+
+pub struct FileRenderer {
+    analyzer: Arc<SpectrumAnalyzer>,
+    /// How often a new file is written.
+    interval: Duration,
+    /// The number of rows kept, which is the height of each file.
+    history_len: usize,
+    history: Mutex<VecDeque<Vec<f32>>>,
+    last_write: Mutex<Instant>,
+    /// The thread writing the latest file, waited on before the final one is written.
+    saving: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl FileRenderer {
+    pub fn new(analyzer: Arc<SpectrumAnalyzer>) -> Self {
+        let interval = *analyzer.ctx.args.get_one::<u64>("interval").unwrap();
+        let history_len = *analyzer.ctx.args.get_one::<u32>("history").unwrap() as usize;
+
+        Self {
+            analyzer,
+            interval: Duration::from_secs(interval),
+            history_len,
+            history: Mutex::new(VecDeque::with_capacity(history_len)),
+            last_write: Mutex::new(Instant::now()),
+            saving: Mutex::new(None),
+        }
+    }
+
+    /// Waits for the file being written to finish, then writes one last file with the current rows.
+    fn finish(&self) {
+        let saving = self.saving.lock().take();
+        if let Some(handle) = saving {
+            let _ = handle.join();
+        }
+
+        let history = self.history.lock();
+        if !history.is_empty() {
+            save(&self.analyzer, &history);
+        }
+    }
+}
+
+/// Draws the rows into an image, with one pixel per bin and the newest row at the bottom.
+fn draw(
+    analyzer: &SpectrumAnalyzer,
+    history: &VecDeque<Vec<f32>>,
+) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let gain = *analyzer.gain.read();
+    let colors = analyzer.color_scheme.colors();
+    let width = history.back().map(|x| x.len()).unwrap_or(0);
+
+    ImageBuffer::from_fn(width as u32, history.len() as u32, |x, y| {
+        let val = analyzer.normalize(history[y as usize][x as usize] * gain);
+        Rgba(color(val, colors).to_slice())
+    })
+}
+
+/// Draws the rows and writes them to a file named after the current time.
+fn save(analyzer: &SpectrumAnalyzer, history: &VecDeque<Vec<f32>>) {
+    let image = draw(analyzer, history);
+    let name = format!("waterfall-{}.png", Local::now().format("%Y-%m-%d-%H-%M-%S"));
+    match image.save(&name) {
+        Ok(_) => println!("[*] Saving waterfall to `{}`", name),
+        Err(e) => println!("[E] Failed to save waterfall to `{}`: {e}", name),
+    }
+}
+
+impl Renderer for FileRenderer {
+    fn init(&self) {
+        println!(
+            "[I] Writing the waterfall to a PNG file every {}s",
+            self.interval.as_secs()
+        );
+    }
+
+    fn render(&self, data: Vec<f32>) {
+        // Scroll the new row in, dropping the oldest one once there are enough to fill a file
+        let mut history = self.history.lock();
+        if matches!(history.back(), Some(x) if x.len() != data.len()) {
+            history.clear();
+        }
+        if history.len() >= self.history_len {
+            history.pop_front();
+        }
+        history.push_back(data);
+
+        let mut last_write = self.last_write.lock();
+        if last_write.elapsed() < self.interval {
+            return;
+        }
+        *last_write = Instant::now();
+
+        // Drawing and encoding a large image is slow, so both are done off of the input thread
+        let (analyzer, history) = (self.analyzer.clone(), history.clone());
+        let handle = thread::spawn(move || save(&analyzer, &history));
+        *self.saving.lock() = Some(handle);
+    }
+
+    /// Waits until the input has ended or the program is stopped, then writes the rows since the last file.
+    fn block(&self) {
+        wait_until(input_ended);
+        self.finish();
+    }
+}
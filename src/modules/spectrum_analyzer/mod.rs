@@ -6,26 +6,55 @@
 //! - <https://www.youtube.com/watch?v=dCeHOf4cJE0>
 //! - <https://docs.rs/spectrum-analyzer/latest/src/spectrum_analyzer/windows.rs.html>
 
-use std::{f32::consts::E, ops::Range, sync::Arc, thread};
-
-use clap::ValueEnum;
+use std::{
+    f32::consts::E,
+    ops::{Range, RangeInclusive},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use clap::{ArgMatches, ValueEnum};
 use crossterm::style;
 use num_complex::Complex;
 use parking_lot::{Mutex, RwLock};
-use rustfft::FftPlanner;
-
-use super::{InitContext, Module};
-use crate::audio::{algorithms::to_mono, passthrough::PassThrough, windows::BoxedWindow};
+use rustfft::{Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    apply_notches, input_ended, notch_filters, passthrough, wait_until, InitContext, Module,
+};
+use crate::audio::{
+    algorithms::{extract_channel, to_db, to_mono, Notch},
+    passthrough::PassThrough,
+    windows::{self, BoxedWindow},
+};
 use crate::misc::soon::Soon;
+use config::Config;
+use data_out::DataOut;
 
+mod config;
 mod console;
+mod data_out;
 #[cfg(feature = "gui")]
 mod egui;
+mod file;
 #[cfg(feature = "gui")]
 mod window;
+#[cfg(feature = "gui")]
+mod window_state;
 
 const FREQUENCY_UNITS: &[&str] = &["Hz", "kHz", "MHz", "GHz", "THz"];
-const COLOR_SCHEME: &[Color] = &[
+/// The floor of the decibel scale, so a magnitude of zero isn't negative infinity.
+const DB_MIN: f32 = -200.0;
+/// The default ceiling of the exponential scale, where `1 - e^(-x)` is already close to the top of the color scale.
+const EXP_CEILING: f32 = 5.0;
+/// The most frames that can be waiting to be drawn.
+/// If the renderer falls further behind than this, new frames are dropped.
+const RENDER_BACKLOG: usize = 32;
+const MAGMA: &[Color] = &[
     Color::hex(0x000000),
     Color::hex(0x742975),
     Color::hex(0xDD562E),
@@ -33,66 +62,238 @@ const COLOR_SCHEME: &[Color] = &[
     Color::hex(0xFFD76B),
     Color::hex(0xFFFFFF),
 ];
+const GRAYSCALE: &[Color] = &[Color::hex(0x000000), Color::hex(0xFFFFFF)];
+const VIRIDIS: &[Color] = &[
+    Color::hex(0x440154),
+    Color::hex(0x482878),
+    Color::hex(0x3E4A89),
+    Color::hex(0x31688E),
+    Color::hex(0x26828E),
+    Color::hex(0x1F9E89),
+    Color::hex(0x35B779),
+    Color::hex(0x6DCD59),
+    Color::hex(0xB4DE2C),
+    Color::hex(0xFDE725),
+];
+const INFERNO: &[Color] = &[
+    Color::hex(0x000004),
+    Color::hex(0x1B0C41),
+    Color::hex(0x4A0C6B),
+    Color::hex(0x781C6D),
+    Color::hex(0xA52C60),
+    Color::hex(0xCF4446),
+    Color::hex(0xED6925),
+    Color::hex(0xFB9B06),
+    Color::hex(0xF7D13D),
+    Color::hex(0xFCFFA4),
+];
 
 pub struct SpectrumAnalyzer {
     // == Settings ==
     ctx: InitContext,
-    fft_size: usize,
-    resolution: f32,
     gain: RwLock<f32>,
     display_range: Range<usize>,
     window: Arc<BoxedWindow>,
+    coherent_gain: f32,
+    scale: MagnitudeScale,
+    levels: RwLock<Levels>,
+    average: u32,
+    color_scheme: ColorScheme,
+    channel_mode: ChannelMode,
+    label_interval: LabelInterval,
 
     // == Data ==
+    /// The FFT size, kept outside of [`FftState`] so it can be read without waiting on a running FFT.
+    fft_size: AtomicUsize,
+    /// A new FFT size requested by the window, or 0 if there is none.
+    /// It is applied by the input thread before processing the next buffer.
+    pending_fft_size: AtomicUsize,
+    fft: Mutex<FftState>,
     planner: Mutex<FftPlanner<f32>>,
-    samples: Mutex<Vec<f32>>,
+    /// The sample buffer of each analyzed channel.
+    samples: Mutex<Vec<Vec<f32>>>,
+    averaged: Mutex<Vec<f32>>,
+    /// The number of frames dropped because the renderer fell behind.
+    dropped_frames: AtomicUsize,
 
     // == Systems ==
     passthrough: Option<Mutex<PassThrough>>,
+    notches: Mutex<Vec<Notch>>,
+    data_out: Option<Mutex<DataOut>>,
     renderer: Soon<Box<Arc<dyn Renderer + Send + Sync + 'static>>>,
 }
 
-#[derive(ValueEnum, Clone, Copy)]
+/// How magnitudes are mapped onto the color scale.
+#[derive(Clone, Copy)]
+enum MagnitudeScale {
+    /// Uses `1 - e^(-x)`, which is pretty arbitrary but looks nice.
+    Exponential,
+    /// Uses decibels relative to full scale, anything below the floor is cut off.
+    Decibel,
+}
+
+/// The range of values spread across the color scale, which works like a brightness and contrast control.
+/// They are in the units of the magnitude scale, so decibels when using the decibel scale.
+#[derive(Clone, Copy)]
+struct Levels {
+    floor: f32,
+    ceiling: f32,
+}
+
+impl Levels {
+    /// Gets the levels from the `--floor` and `--ceiling` args, defaulting to the range of the magnitude scale.
+    /// Errors if the floor isn't below the ceiling.
+    fn from_args(args: &ArgMatches) -> anyhow::Result<Self> {
+        let (floor, ceiling) = match args.get_flag("db") {
+            true => (*args.get_one("db-floor").unwrap(), 0.0),
+            false => (0.0, EXP_CEILING),
+        };
+        let levels = Self {
+            floor: args.get_one("floor").copied().unwrap_or(floor),
+            ceiling: args.get_one("ceiling").copied().unwrap_or(ceiling),
+        };
+
+        anyhow::ensure!(
+            levels.floor < levels.ceiling,
+            "The floor ({}) must be below the ceiling ({})",
+            levels.floor,
+            levels.ceiling
+        );
+        Ok(levels)
+    }
+}
+
+/// Which input channels are analyzed.
+#[derive(Clone, Copy)]
+enum ChannelMode {
+    /// Averages all the channels together.
+    Mono,
+    /// Only analyzes one channel.
+    Single(usize),
+    /// Analyzes every channel, with their spectrums drawn side by side.
+    Split,
+}
+
+/// The gradients that can be used to color the spectrum.
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorScheme {
+    Magma,
+    Grayscale,
+    Viridis,
+    Inferno,
+}
+
+/// How far apart the frequency labels of the console renderer are.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LabelInterval {
+    /// Spaced out based on the length of the labels.
+    Auto,
+    /// A label every this many characters.
+    Chars(usize),
+    /// A label every time the frequency passes a multiple of this many Hz.
+    Hz(f32),
+}
+
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
 pub enum DisplayType {
     Console,
-    #[cfg(feature = "gui")]
+    // Only available when built with the `gui` feature.
+    // It is still accepted without it, so that choosing it gives a clear error instead of clap's invalid value.
     Window,
+    // Writes the waterfall to PNG files every `--interval` seconds, without any display.
+    File,
+}
+
+/// Everything that depends on the FFT size, which is rebuilt when it changes.
+struct FftState {
+    fft: Arc<dyn Fft<f32>>,
+    /// The buffer the FFT is run in, and the scratch space it uses.
+    /// Both are reused between frames so they don't have to be allocated every time.
+    buffer: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+    coherent_gain: f32,
+    /// The FFT bins in the display range.
+    bins: RangeInclusive<usize>,
+    /// The number of bins in the spectrum of each channel.
+    row_len: usize,
 }
 
 trait Renderer {
     fn init(&self) {}
     fn render(&self, data: Vec<f32>);
-    fn block(&self) -> ! {
-        loop {
-            thread::park()
-        }
+    /// Waits until the input has ended, the window renderer instead runs its event loop until it is closed.
+    fn block(&self) {
+        wait_until(input_ended);
     }
 }
 
 impl SpectrumAnalyzer {
     pub fn new(ctx: InitContext) -> Arc<Self> {
-        // Load command line arguments
-        let fft_size = *ctx.args.get_one("fft-size").unwrap();
-        let display_range = ctx
-            .args
-            .get_one::<Range<usize>>("display-range")
-            .unwrap()
-            .to_owned();
-        let passthrough = ctx
-            .args
-            .get_flag("passthrough")
-            .then(|| Mutex::new(PassThrough::new(ctx.clone(), 1024)));
-        let window = ctx
-            .args
-            .get_one::<Arc<BoxedWindow>>("window")
-            .unwrap()
-            .to_owned();
-        let gain = *ctx.args.get_one("gain").unwrap();
+        // Load the settings, with command line arguments overriding the config file
+        let config = Config::load(&ctx.args)
+            .expect("Failed to load config")
+            .resolve(&ctx.args);
+        if let Some(path) = ctx.args.get_one::<PathBuf>("save-config") {
+            config.save(path).expect("Failed to save config");
+        }
 
-        let renderer = *ctx
+        let fft_size = config.fft_size.unwrap();
+        assert!(fft_size > 0, "The FFT size must be greater than zero");
+        let display_range = parse_display_range(config.display_range.as_deref().unwrap())
+            .expect("Invalid display range");
+        let passthrough = passthrough(&ctx.args, &ctx, config.passthrough.unwrap());
+        let window =
+            Arc::new(windows::parse(config.window.as_deref().unwrap()).expect("Invalid window"));
+        let gain = config.gain.unwrap();
+        let average = *ctx.args.get_one("average").unwrap();
+        let color_scheme = config.color_scheme.unwrap();
+        let scale = match ctx.args.get_flag("db") {
+            true => MagnitudeScale::Decibel,
+            false => MagnitudeScale::Exponential,
+        };
+        // Checked by `check_args` before the module is created
+        let levels = Levels::from_args(&ctx.args).unwrap();
+
+        let channels = ctx.input.channels() as usize;
+        let channel_mode = match ctx.args.get_one::<usize>("channel") {
+            Some(&channel) => {
+                assert!(
+                    channel < channels,
+                    "Channel {channel} does not exist, the input has {channels} channels"
+                );
+                ChannelMode::Single(channel)
+            }
+            None if ctx.args.get_flag("split") => ChannelMode::Split,
+            None => ChannelMode::Mono,
+        };
+        let streams = match channel_mode {
+            ChannelMode::Split => channels,
+            _ => 1,
+        };
+
+        // Non powers of two work, but are a lot slower to process
+        if !fft_size.is_power_of_two() {
+            println!("[-] FFT size {fft_size} is not a power of two, which will be slow");
+        }
+
+        let renderer = config.display_type.unwrap_or(DisplayType::Console);
+        #[cfg(not(feature = "gui"))]
+        if let DisplayType::Window = renderer {
+            println!("[E] Window display requires building with --features gui");
+            std::process::exit(1);
+        }
+        let label_interval = ctx
             .args
-            .get_one::<DisplayType>("display-type")
-            .unwrap_or(&DisplayType::Console);
+            .get_one("label-interval")
+            .copied()
+            .unwrap_or(LabelInterval::Auto);
+        let notches = notch_filters(&ctx.args, &ctx);
+        let data_out = ctx.args.get_one::<PathBuf>("data-out").map(|path| {
+            println!("[*] Writing spectrum data to `{}`", path.display());
+            Mutex::new(DataOut::open(path).expect("Failed to open data file"))
+        });
 
         if passthrough.is_some() {
             println!("[I] Pass-through enabled, setting process priority to high");
@@ -101,17 +302,37 @@ impl SpectrumAnalyzer {
             }
         }
 
+        let mut planner = FftPlanner::new();
+        let fft = FftState::new(
+            &mut planner,
+            &window,
+            &display_range,
+            fft_size,
+            ctx.sample_rate().input,
+        );
+
         let this = Arc::new(Self {
-            resolution: 1. / fft_size as f32 * ctx.sample_rate().input as f32,
             ctx,
-            fft_size,
             display_range,
             window,
+            scale,
+            levels: RwLock::new(levels),
+            average,
+            color_scheme,
+            channel_mode,
+            label_interval,
             gain: RwLock::new(gain),
 
             passthrough,
-            planner: Mutex::new(FftPlanner::<f32>::new()),
-            samples: Mutex::new(Vec::with_capacity(fft_size)),
+            notches,
+            data_out,
+            fft_size: AtomicUsize::new(fft_size),
+            pending_fft_size: AtomicUsize::new(0),
+            fft: Mutex::new(fft),
+            planner: Mutex::new(planner),
+            samples: Mutex::new(vec![Vec::with_capacity(fft_size); streams]),
+            averaged: Mutex::new(Vec::new()),
+            dropped_frames: AtomicUsize::new(0),
 
             renderer: Soon::empty(),
         });
@@ -120,14 +341,209 @@ impl SpectrumAnalyzer {
             DisplayType::Console => Box::new(console::ConsoleRenderer::new(this.clone())),
             #[cfg(feature = "gui")]
             DisplayType::Window => Box::new(Arc::new(window::WindowRenderer::new(this.clone()))),
+            #[cfg(not(feature = "gui"))]
+            DisplayType::Window => unreachable!("The window display was checked for above"),
+            DisplayType::File => Box::new(Arc::new(file::FileRenderer::new(this.clone()))),
         };
 
         this.renderer.replace(renderer);
         this
     }
 
+    /// Gets the frequency of a bin in a (possibly split) spectrum row.
+    /// When split, the bins of each channel are placed one after another, so the index wraps around every channel.
+    /// Rows only hold the bins in the display range, so they are offset by the first bin.
     fn index_to_freq(&self, idx: usize) -> f32 {
-        idx as f32 * self.ctx.sample_rate().input as f32 / self.fft_size as f32
+        let fft = self.fft.lock();
+        (idx % fft.row_len + fft.bins.start()) as f32 * self.resolution()
+    }
+
+    /// Gets the position of a frequency in a spectrum row, in bins from the start of the display range.
+    /// This can be negative or past the end of the row if the frequency is outside the display range.
+    fn freq_to_index(&self, freq: f32) -> f32 {
+        freq / self.resolution() - *self.fft.lock().bins.start() as f32
+    }
+
+    fn fft_size(&self) -> usize {
+        self.fft_size.load(Ordering::Relaxed)
+    }
+
+    /// Gets the width of each FFT bin in Hz.
+    fn resolution(&self) -> f32 {
+        self.ctx.sample_rate().input as f32 / self.fft_size() as f32
+    }
+
+    /// Requests a new FFT size, which is applied before the next buffer of samples is processed.
+    /// This is done by the input thread, as the renderer can't wait on the sample buffers while they are being drawn.
+    #[cfg(feature = "gui")]
+    fn request_fft_size(&self, fft_size: usize) {
+        self.pending_fft_size.store(fft_size, Ordering::Relaxed);
+    }
+
+    /// Switches to a new FFT size, re-planning the FFT.
+    /// Any buffered samples and averaged frames are thrown away, as they were for the old size.
+    fn set_fft_size(&self, fft_size: usize) {
+        *self.fft.lock() = FftState::new(
+            &mut self.planner.lock(),
+            &self.window,
+            &self.display_range,
+            fft_size,
+            self.ctx.sample_rate().input,
+        );
+        self.fft_size.store(fft_size, Ordering::Relaxed);
+
+        self.samples.lock().iter_mut().for_each(Vec::clear);
+        self.averaged.lock().clear();
+    }
+
+    /// Counts a frame that was dropped because the renderer fell behind.
+    fn drop_frame(&self) {
+        self.dropped_frames.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Gets the number of frames dropped because the renderer fell behind.
+    fn dropped_frames(&self) -> usize {
+        self.dropped_frames.load(Ordering::Relaxed)
+    }
+
+    /// Gets the number of pass-through underruns, or None if the pass-through is disabled.
+    fn passthrough_underruns(&self) -> Option<usize> {
+        self.passthrough.as_ref().map(|x| x.lock().underruns())
+    }
+
+    /// Writes a frame to the data file, if one was given with `--data-out`.
+    fn write_data(&self, row: &[f32]) {
+        if let Some(data_out) = &self.data_out {
+            let result = data_out.lock().write(row, |i| self.index_to_freq(i));
+            if let Err(e) = result {
+                println!("[E] Failed to write spectrum data: {e}");
+            }
+        }
+    }
+
+    /// Runs the FFT on a window of samples, adding the magnitudes of the bins in the display range to `out`.
+    fn spectrum(&self, samples: &[f32], out: &mut Vec<f32>) {
+        let mut state = self.fft.lock();
+        let FftState {
+            fft,
+            buffer: buf,
+            scratch,
+            coherent_gain,
+            bins,
+            ..
+        } = &mut *state;
+
+        // Applies the windowing function and converts the samples to complex numbers
+        buf.clear();
+        buf.extend(
+            self.window
+                .window(samples)
+                .iter()
+                .map(|&i| Complex::new(i, 0.)),
+        );
+
+        // Run the FFT
+        fft.process_with_scratch(buf, scratch);
+
+        // Slice the buffer to the display range, then normalize the complex numbers (r^2 + i^2)
+        // Dividing by the window's coherent gain keeps magnitudes comparable between windows
+        out.extend(buf[bins.clone()].iter().map(|x| x.norm() / *coherent_gain));
+    }
+
+    /// Exponentially averages a new frame of magnitudes with the previous frames.
+    /// Each new frame has a weight of `1 / average`, so an average of 1 just returns the frame.
+    /// The average is reset if the frame size changes, as the bins would no longer line up.
+    fn average(&self, frame: Vec<f32>) -> Vec<f32> {
+        if self.average <= 1 {
+            return frame;
+        }
+
+        let mut averaged = self.averaged.lock();
+        if averaged.len() != frame.len() {
+            *averaged = frame;
+            return averaged.clone();
+        }
+
+        let weight = (self.average as f32).recip();
+        for (avg, new) in averaged.iter_mut().zip(frame) {
+            *avg += (new - *avg) * weight;
+        }
+
+        averaged.clone()
+    }
+
+    /// Converts a magnitude to decibels relative to a full scale sine wave.
+    fn magnitude_db(&self, mag: f32) -> f32 {
+        to_db(mag / (self.fft_size() as f32 / 2.0), DB_MIN)
+    }
+
+    /// Formats an RMS magnitude for the info displays, in decibels if using the decibel scale.
+    fn rms_label(&self, rms: f32) -> String {
+        match self.scale {
+            MagnitudeScale::Exponential => format!("{rms:.1}"),
+            MagnitudeScale::Decibel => format!("{:.1} dBFS", self.magnitude_db(rms)),
+        }
+    }
+
+    /// Maps a magnitude (with the gain already applied) to a value between 0 and 1 for the color scale.
+    /// The floor and ceiling are applied first, then the exponential curve if it is being used.
+    fn normalize(&self, mag: f32) -> f32 {
+        let levels = *self.levels.read();
+        match self.scale {
+            MagnitudeScale::Exponential => 1. - E.powf(-levels.map(mag).max(0.) * EXP_CEILING),
+            MagnitudeScale::Decibel => levels.map(self.magnitude_db(mag)).clamp(0., 1.),
+        }
+    }
+
+    /// Takes in an array of values and returns a color based on the average of the values.
+    /// A map function is also passed in to allow for picking different channels.
+    /// This is used in the print_row function to get the color of the previous row and then the current row.
+    fn get_color(&self, vals: &[(f32, f32)], map: impl Fn(&(f32, f32)) -> f32) -> Color {
+        let avg = vals.iter().map(map).sum::<f32>() / vals.len() as f32;
+        color(self.normalize(avg), self.color_scheme.colors())
+    }
+}
+
+impl FftState {
+    fn new(
+        planner: &mut FftPlanner<f32>,
+        window: &BoxedWindow,
+        display_range: &Range<usize>,
+        fft_size: usize,
+        sample_rate: u32,
+    ) -> Self {
+        let fft = planner.plan_fft_forward(fft_size);
+        let scratch = vec![Complex::default(); fft.get_inplace_scratch_len()];
+        let bins = display_bins(display_range, fft_size, sample_rate);
+
+        Self {
+            fft,
+            buffer: Vec::with_capacity(fft_size),
+            scratch,
+            coherent_gain: window.coherent_gain(fft_size),
+            row_len: bins.end() - bins.start() + 1,
+            bins,
+        }
+    }
+}
+
+impl Levels {
+    /// Maps a value to where it falls between the floor (0) and the ceiling (1).
+    /// Values outside of the levels aren't clamped.
+    fn map(&self, val: f32) -> f32 {
+        (val - self.floor) / (self.ceiling - self.floor)
+    }
+}
+
+impl ColorScheme {
+    /// Gets the colors that make up the gradient, from low to high magnitude.
+    fn colors(&self) -> &'static [Color] {
+        match self {
+            Self::Magma => MAGMA,
+            Self::Grayscale => GRAYSCALE,
+            Self::Viridis => VIRIDIS,
+            Self::Inferno => INFERNO,
+        }
     }
 }
 
@@ -138,49 +554,54 @@ impl Module for SpectrumAnalyzer {
 
     fn init(&self) {
         // Prints some info about the current state of the program
-        println!("[I] FFT size: {}", self.fft_size);
+        println!("[I] FFT size: {}", self.fft_size());
         println!("[I] Display range: {:?}", self.display_range);
-        println!("[I] Resolution: {}", nice_freq(self.resolution));
+        println!("[I] Resolution: {}", nice_freq(self.resolution()));
 
         self.renderer.init();
     }
 
     fn input(&self, input: &[f32]) {
+        // Apply a new FFT size if one was requested
+        let pending = self.pending_fft_size.swap(0, Ordering::Relaxed);
+        if pending != 0 {
+            self.set_fft_size(pending);
+        }
+
         // Add the buffer to the pass-through
         if let Some(i) = &self.passthrough {
             i.lock().add_samples(input);
         }
 
-        // Adds the samples to a buffer
-        let mut samples = self.samples.lock();
-        samples.reserve(input.len() / self.ctx.input.channels() as usize + 1);
-        samples.extend(to_mono(input, self.ctx.input.channels() as usize));
-
-        // If the buffer is big enough, it will process it
-        while samples.len() >= self.fft_size {
-            // Applies the windowing function and converts the samples to complex numbers
-            let samples = samples.drain(..self.fft_size);
-            let mut buf = Vec::with_capacity(self.fft_size);
-            for &i in self.window.window(samples.as_slice()).iter() {
-                buf.push(Complex::new(i, 0.));
-            }
-
-            // Run the FFT
-            let fft = self.planner.lock().plan_fft_forward(self.fft_size);
-            fft.process(&mut buf);
+        // Adds the samples of each analyzed channel to its buffer, the notches only apply to the analysis
+        let input = apply_notches(&self.notches, input);
+        let channels = self.ctx.input.channels() as usize;
+        let streams = match self.channel_mode {
+            ChannelMode::Mono => vec![to_mono(&input, channels)],
+            ChannelMode::Single(channel) => vec![extract_channel(&input, channels, channel)],
+            ChannelMode::Split => (0..channels)
+                .map(|channel| extract_channel(&input, channels, channel))
+                .collect(),
+        };
 
-            // Slice the buffer to the display range
-            let sample_rate = self.ctx.sample_rate().input as usize;
-            let start = self.display_range.start * self.fft_size / sample_rate;
-            let end = self.display_range.end * self.fft_size / sample_rate;
+        let mut samples = self.samples.lock();
+        for (buf, stream) in samples.iter_mut().zip(streams) {
+            buf.extend(stream);
+        }
 
-            // Normalize the complex numbers (r^2 + i^2)
-            let norm = buf[start.max(0)..=end.min(buf.len() / 2)]
-                .iter()
-                .map(|x| x.norm())
-                .collect::<Vec<_>>();
+        // If the buffers are big enough, they will be processed
+        // The spectrums of each channel are joined into one row
+        let fft_size = self.fft_size();
+        while samples.iter().all(|x| x.len() >= fft_size) {
+            let mut row = Vec::new();
+            for buf in samples.iter_mut() {
+                let window = buf.drain(..fft_size);
+                self.spectrum(window.as_slice(), &mut row);
+            }
 
-            self.renderer.render(norm);
+            let row = self.average(row);
+            self.write_data(&row);
+            self.renderer.render(row);
         }
     }
 
@@ -191,11 +612,55 @@ impl Module for SpectrumAnalyzer {
         }
     }
 
-    fn block(&self) -> ! {
+    fn block(&self) {
         self.renderer.block()
     }
 }
 
+/// Checks the args that depend on each other, so mistakes are reported like any other argument error.
+pub fn check_args(args: &ArgMatches) -> anyhow::Result<()> {
+    Levels::from_args(args).map(|_| ())
+}
+
+/// Parses a range of frequencies in the format of `low..high`.
+/// Used for the display range arg and config setting.
+pub fn parse_display_range(s: &str) -> anyhow::Result<Range<usize>> {
+    let (start, end) = s
+        .split_once("..")
+        .ok_or_else(|| anyhow::anyhow!("Must be in the format of `low..high`"))?;
+    Ok(start.parse()?..end.parse()?)
+}
+
+/// Parses the spacing of the console frequency labels, either a number of characters like `20`,
+/// or a frequency with a unit like `500hz` or `2khz`.
+pub fn parse_label_interval(s: &str) -> anyhow::Result<LabelInterval> {
+    let lower = s.to_ascii_lowercase();
+    let interval = match (lower.strip_suffix("khz"), lower.strip_suffix("hz")) {
+        (Some(khz), _) => LabelInterval::Hz(khz.parse::<f32>()? * 1000.0),
+        (None, Some(hz)) => LabelInterval::Hz(hz.parse()?),
+        (None, None) => LabelInterval::Chars(lower.parse()?),
+    };
+
+    match interval {
+        LabelInterval::Chars(0) => anyhow::bail!("Must be at least one character"),
+        LabelInterval::Hz(hz) if hz <= 0.0 => anyhow::bail!("Must be more than 0Hz"),
+        _ => Ok(interval),
+    }
+}
+
+/// Gets the FFT bins that cover the display range.
+/// The end is clamped to the Nyquist frequency and the start to the end, so the range is never empty or out of bounds.
+fn display_bins(
+    display_range: &Range<usize>,
+    fft_size: usize,
+    sample_rate: u32,
+) -> RangeInclusive<usize> {
+    let sample_rate = sample_rate as usize;
+    let end = (display_range.end * fft_size / sample_rate).min(fft_size / 2);
+    let start = (display_range.start * fft_size / sample_rate).min(end);
+    start..=end
+}
+
 /// Converts a frequency in Hz to a nice string with a unit.
 fn nice_freq(mut hz: f32) -> String {
     for i in FREQUENCY_UNITS {
@@ -209,26 +674,42 @@ fn nice_freq(mut hz: f32) -> String {
     format!("{hz:.1}{}", FREQUENCY_UNITS.last().unwrap())
 }
 
-/// Takes in a value between 0 and 1 and returns a color from the color scheme.
-fn color(val: f32) -> Color {
-    debug_assert!((0. ..=1.).contains(&val));
-    let sections = COLOR_SCHEME.len() - 2;
-    let section = (sections as f32 * val).floor() as usize;
+/// Estimates where the true peak is between bins, by fitting a parabola through the peak bin and its two neighbors.
+/// Returns the fractional bin index of the peak, or the peak index itself if it is at an edge.
+#[cfg(feature = "gui")]
+fn interpolate_peak(bins: &[f32], peak_idx: usize) -> f32 {
+    if peak_idx == 0 || peak_idx + 1 >= bins.len() {
+        return peak_idx as f32;
+    }
 
-    COLOR_SCHEME[section].lerp(
-        &COLOR_SCHEME[section + 1],
-        val * sections as f32 - section as f32,
-    )
+    let (a, b, c) = (bins[peak_idx - 1], bins[peak_idx], bins[peak_idx + 1]);
+    let denominator = a - 2.0 * b + c;
+    if denominator == 0.0 {
+        return peak_idx as f32;
+    }
+
+    peak_idx as f32 + (0.5 * (a - c) / denominator).clamp(-0.5, 0.5)
 }
 
-/// Takes in an array of values and returns a color based on the average of the values.
-/// A map function is also passed in to allow for picking different channels.
-/// This is used in the print_row function to get the color of the previous row and then the current row.
-fn get_color(vals: &[(f32, f32)], map: impl Fn(&(f32, f32)) -> f32) -> Color {
-    let avg = vals.iter().map(map).sum::<f32>() / vals.len() as f32;
-    let norm = 1. - E.powf(-avg);
+/// Picks a round spacing for gridlines, a 1, 2 or 5 times a power of ten, giving about `lines` lines over `span`.
+#[cfg(feature = "gui")]
+fn grid_step(span: f32, lines: f32) -> f32 {
+    let raw = span / lines.max(1.0);
+    let power = 10_f32.powf(raw.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|x| x * power)
+        .find(|&x| x >= raw)
+        .unwrap_or(10.0 * power)
+}
 
-    color(norm)
+/// Takes in a value between 0 and 1 and returns a color from the color scheme.
+fn color(val: f32, scheme: &[Color]) -> Color {
+    debug_assert!((0. ..=1.).contains(&val));
+    let sections = scheme.len() - 1;
+    let section = ((sections as f32 * val).floor() as usize).min(sections - 1);
+
+    scheme[section].lerp(&scheme[section + 1], val * sections as f32 - section as f32)
 }
 
 unsafe fn set_prio() {
@@ -296,7 +777,6 @@ impl Color {
         )
     }
 
-    #[cfg(feature = "gui")]
     fn to_slice(self) -> [u8; 4] {
         [self.r, self.g, self.b, 255]
     }
@@ -312,3 +792,84 @@ impl From<Color> for style::Color {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{display_bins, parse_display_range, parse_label_interval, LabelInterval, Levels};
+
+    #[test]
+    fn test_display_bins() {
+        assert_eq!(display_bins(&(15..14000), 2048, 48000), 0..=597);
+        assert_eq!(display_bins(&(1000..2000), 1024, 48000), 21..=42);
+
+        // Clamped to the Nyquist frequency
+        assert_eq!(display_bins(&(0..30000), 2048, 48000), 0..=1024);
+        assert_eq!(display_bins(&(25000..30000), 2048, 48000), 1024..=1024);
+    }
+
+    #[test]
+    fn test_parse_label_interval() {
+        assert_eq!(
+            parse_label_interval("20").unwrap(),
+            LabelInterval::Chars(20)
+        );
+        assert_eq!(
+            parse_label_interval("500Hz").unwrap(),
+            LabelInterval::Hz(500.0)
+        );
+        assert_eq!(
+            parse_label_interval("2khz").unwrap(),
+            LabelInterval::Hz(2000.0)
+        );
+        assert!(parse_label_interval("0").is_err());
+        assert!(parse_label_interval("-5hz").is_err());
+        assert!(parse_label_interval("wide").is_err());
+    }
+
+    #[test]
+    fn test_levels() {
+        let levels = Levels {
+            floor: -60.0,
+            ceiling: -20.0,
+        };
+        assert_eq!(levels.map(-60.0), 0.0);
+        assert_eq!(levels.map(-40.0), 0.5);
+        assert_eq!(levels.map(-20.0), 1.0);
+        assert_eq!(levels.map(0.0), 1.5);
+    }
+
+    #[test]
+    fn test_parse_display_range() {
+        assert_eq!(parse_display_range("15..14000").unwrap(), 15..14000);
+        assert!(parse_display_range("15-14000").is_err());
+        assert!(parse_display_range("15..").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "gui")]
+    fn test_interpolate_peak() {
+        use super::interpolate_peak;
+
+        // Samples of a parabola with its vertex at 10.3
+        let bins = (0..20)
+            .map(|x| 100.0 - (x as f32 - 10.3).powi(2))
+            .collect::<Vec<_>>();
+        assert!((interpolate_peak(&bins, 10) - 10.3).abs() < 1e-3);
+
+        // Symmetric peaks and edges are left alone
+        assert_eq!(interpolate_peak(&[1.0, 2.0, 1.0], 1), 1.0);
+        assert_eq!(interpolate_peak(&bins, 0), 0.0);
+        assert_eq!(interpolate_peak(&bins, 19), 19.0);
+    }
+
+    #[test]
+    #[cfg(feature = "gui")]
+    fn test_grid_step() {
+        use super::grid_step;
+
+        assert_eq!(grid_step(14000.0, 10.0), 2000.0);
+        assert_eq!(grid_step(10000.0, 10.0), 1000.0);
+        assert_eq!(grid_step(300.0, 10.0), 50.0);
+        assert_eq!(grid_step(30.0, 4.0), 10.0);
+    }
+}
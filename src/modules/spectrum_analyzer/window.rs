@@ -1,28 +1,57 @@
-use std::{collections::VecDeque, f32::consts::E, sync::Arc, time::Instant};
+use std::{
+    collections::VecDeque,
+    mem,
+    ops::{Range, RangeInclusive},
+    process,
+    sync::Arc,
+    thread,
+    time::Instant,
+};
 
 use bitflags::bitflags;
 use chrono::Local;
-use egui::{Align, Align2, Context, RichText, Slider, Ui};
+use egui::{
+    Align, Align2, Color32, ComboBox, Context, FontId, LayerId, Pos2, Rect, RichText, Shape,
+    Slider, Stroke, Ui,
+};
 use egui_extras::{Column, TableBuilder};
 use image::{ImageBuffer, Rgba};
 use indexmap::IndexMap;
 use parking_lot::Mutex;
 use pixels::{Pixels, SurfaceTexture};
 use winit::{
-    dpi::LogicalSize,
-    event::Event,
+    dpi::{LogicalSize, PhysicalPosition},
+    event::{Event, VirtualKeyCode},
     event_loop::{ControlFlow, EventLoop},
-    window::WindowBuilder,
+    window::{Icon, WindowBuilder},
 };
 use winit_input_helper::WinitInputHelper;
 
 use super::{
     egui::{Egui, Gui},
-    {color, nice_freq, Renderer, SpectrumAnalyzer},
+    window_state::WindowState,
+    {
+        color, grid_step, interpolate_peak, nice_freq, MagnitudeScale, Renderer, SpectrumAnalyzer,
+        DB_MIN, EXP_CEILING, RENDER_BACKLOG,
+    },
+};
+use crate::{
+    misc::ring_buffer::RingBuffer,
+    modules::{spectrum_analyzer::Color, stopped},
 };
-use crate::{misc::ring_buffer::RingBuffer, modules::spectrum_analyzer::Color};
 
+/// The size the window opens at the first time, and the smallest it can be resized to.
 const INIT_SIZE: (u32, u32) = (1302, 675);
+/// The icon of the window, as a PNG.
+const ICON: &[u8] = include_bytes!("../../../assets/icon.png");
+/// The powers of two that can be picked as the FFT size.
+const FFT_SIZES: RangeInclusive<u32> = 8..=16;
+/// How many rows of history each scroll wheel notch or arrow key press moves through.
+const SCROLL_ROWS: usize = 10;
+/// About how far apart the frequency gridlines are, in points.
+const GRID_SPACING: f32 = 120.0;
+/// The most rows put in an exported waterfall image, only the newest rows are kept past this.
+const MAX_EXPORT_ROWS: usize = 16384;
 
 pub struct WindowRenderer {
     window: Arc<Mutex<Window>>,
@@ -32,6 +61,14 @@ struct Window {
     analyzer: Arc<SpectrumAnalyzer>,
     /// New ffted data to be drawn
     new: VecDeque<Vec<f32>>,
+    /// The last row that was drawn, used to find peaks under the cursor
+    last_row: Vec<f32>,
+    /// The rows of the waterfall, oldest first, so it can be redrawn and scrolled back through
+    history: VecDeque<Vec<f32>>,
+    /// The most rows kept in the history
+    history_len: usize,
+    /// How many rows back from the newest the waterfall is scrolled, only while frozen
+    scroll: usize,
     /// Last time the frame was drawn
     last_frame: Instant,
     /// History of frame times
@@ -42,6 +79,19 @@ struct Window {
     frequency_indexes: IndexMap<usize, f32>,
     /// Mouse cursor position
     mouse: Option<(f32, f32)>,
+    /// If the mouse is over one of the egui windows, so clicks shouldn't start a zoom
+    pointer_over_gui: bool,
+    /// The x coordinate a zoom drag was started at
+    drag: Option<f32>,
+    /// The range of bins to show, if zoomed in.
+    /// This is separate from the analyzer's display range, which is what the FFT is sliced to.
+    zoom: Option<Range<usize>>,
+    /// The frequencies of the markers placed by clicking on the waterfall
+    markers: Vec<f32>,
+    /// The highest color scale value seen in each frequency bin, for the peak hold line
+    peak: Vec<f32>,
+    /// How much of the peak is kept each row
+    peak_decay: f32,
 
     /// Flags
     flags: Flags,
@@ -49,11 +99,16 @@ struct Window {
 
 bitflags! {
     #[derive(Clone, Copy)]
-    struct Flags: u8 {
-        const RESIZE      = 0b00000001;
-        const RECALC_FREQ = 0b00000010;
-        const CAPTURE     = 0b00000100;
-        const SHOW_INFO   = 0b00001000;
+    struct Flags: u16 {
+        const CLEAR       = 0b000000001;
+        const RECALC_FREQ = 0b000000010;
+        const CAPTURE     = 0b000000100;
+        const SHOW_INFO   = 0b000001000;
+        const PEAK_HOLD   = 0b000010000;
+        const FROZEN      = 0b000100000;
+        const REDRAW      = 0b001000000;
+        const EXPORT      = 0b010000000;
+        const GRID        = 0b100000000;
     }
 }
 
@@ -66,24 +121,53 @@ impl Flags {
 
 impl Renderer for WindowRenderer {
     fn render(&self, data: Vec<f32>) {
-        self.window.lock().new.push_back(data);
+        // Drop the oldest frame if the window isn't keeping up, like when it's minimized
+        let mut window = self.window.lock();
+        if window.new.len() >= RENDER_BACKLOG {
+            window.new.pop_front();
+            window.analyzer.drop_frame();
+        }
+        window.new.push_back(data);
     }
 
-    fn block(&self) -> ! {
+    fn block(&self) {
         let event_loop = EventLoop::new();
         let mut input = WinitInputHelper::new();
-        let size = LogicalSize::new(INIT_SIZE.0 as f64, INIT_SIZE.1 as f64);
-        let window = WindowBuilder::new()
-            .with_title("Radio Data - Spectrum Analyzer")
-            .with_inner_size(size)
-            .with_min_inner_size(size)
-            .build(&event_loop)
-            .unwrap();
+
+        // Open the window where it was last closed, if that was saved
+        let state = WindowState::load();
+        let init_size = state
+            .map(|x| (x.size.0.max(INIT_SIZE.0), x.size.1.max(INIT_SIZE.1)))
+            .unwrap_or(INIT_SIZE);
+        let min_size = LogicalSize::new(INIT_SIZE.0 as f64, INIT_SIZE.1 as f64);
+        let mut builder = WindowBuilder::new()
+            .with_inner_size(LogicalSize::new(init_size.0 as f64, init_size.1 as f64))
+            .with_min_inner_size(min_size)
+            .with_window_icon(load_icon());
+        if let Some((x, y)) = state.and_then(|x| x.position) {
+            builder = builder.with_position(PhysicalPosition::new(x, y));
+        }
+        let analyzer = self.window.lock().analyzer.clone();
+        let mut title = window_title(&analyzer);
+        let window = match builder.with_title(&title).build(&event_loop) {
+            Ok(window) => window,
+            Err(err) => {
+                println!("[E] Failed to open the window: {err}");
+                process::exit(1);
+            }
+        };
 
         let window_size = window.inner_size();
         let scale_factor = window.scale_factor() as f32;
         let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
-        let mut pixels = Pixels::new(INIT_SIZE.0, INIT_SIZE.1, surface_texture).unwrap();
+        let mut pixels = match Pixels::new(init_size.0, init_size.1, surface_texture) {
+            Ok(pixels) => pixels,
+            Err(err) => {
+                println!("[E] Failed to create the pixel buffer: {err}");
+                process::exit(1);
+            }
+        };
+        self.window.lock().size = init_size;
         let mut framework = Egui::new(
             &event_loop,
             window_size.width,
@@ -96,7 +180,19 @@ impl Renderer for WindowRenderer {
         let win = self.window.clone();
         event_loop.run(move |event, _, control_flow| {
             if input.update(&event) {
-                if input.quit() {
+                // Close the window on Ctrl-C too, as the event loop never returns to the main function
+                if input.quit() || stopped() {
+                    let state = WindowState {
+                        size: window
+                            .inner_size()
+                            .to_logical::<u32>(window.scale_factor())
+                            .into(),
+                        position: window.outer_position().ok().map(|x| x.into()),
+                    };
+                    if let Err(err) = state.save() {
+                        println!("[-] Failed to save the window state: {err:#}");
+                    }
+
                     *control_flow = ControlFlow::Exit;
                     return;
                 }
@@ -105,20 +201,32 @@ impl Renderer for WindowRenderer {
                     framework.scale_factor(scale_factor);
                 }
 
-                if let Some(size) = input.window_resized() {
-                    // todo: dont panic on these errors, just print them
-                    // also todo: https://crates.io/crates/winres   - add an icon
-                    let _ = pixels.resize_buffer(size.width, size.height);
-                    let _ = pixels.resize_surface(size.width, size.height);
-                    framework.resize(size.width, size.height);
-                    let mut win = win.lock();
-                    let resize = win.size.0 != size.width;
-                    win.size = (size.width, size.height);
-                    win.flags.set(Flags::RECALC_FREQ, true);
-                    win.flags.set_or(Flags::RESIZE, resize);
+                // Nothing is resized while minimized, as a zero sized buffer can't be made
+                match input.window_resized() {
+                    Some(size) if size.width > 0 && size.height > 0 => {
+                        // If the buffer can't be resized the old one is kept, as it matches the size drawn at
+                        match pixels.resize_buffer(size.width, size.height) {
+                            Ok(()) => {
+                                let mut win = win.lock();
+                                win.size = (size.width, size.height);
+                                win.flags.insert(Flags::RECALC_FREQ | Flags::REDRAW);
+                            }
+                            Err(err) => println!("[E] Failed to resize the pixel buffer: {err}"),
+                        }
+
+                        if let Err(err) = pixels.resize_surface(size.width, size.height) {
+                            println!("[E] Failed to resize the window surface: {err}");
+                        }
+                        framework.resize(size.width, size.height);
+                    }
+                    _ => {}
                 }
 
-                win.lock().mouse = input.mouse();
+                let mut win = win.lock();
+                win.mouse = input.mouse();
+                win.handle_mouse(&input);
+                win.handle_scroll(&input);
+                drop(win);
                 window.request_redraw();
             }
 
@@ -127,6 +235,13 @@ impl Renderer for WindowRenderer {
                     framework.handle_event(&event);
                 }
                 Event::RedrawRequested(_) => {
+                    // The FFT size can be changed from the GUI
+                    let new_title = window_title(&analyzer);
+                    if new_title != title {
+                        window.set_title(&new_title);
+                        title = new_title;
+                    }
+
                     let mut win = win.lock();
                     let delta = win.last_frame.elapsed().as_secs_f32();
                     win.last_frame = Instant::now();
@@ -137,13 +252,20 @@ impl Renderer for WindowRenderer {
 
                     framework.prepare(&window);
 
-                    pixels
-                        .render_with(|encoder, render_target, context| {
-                            context.scaling_renderer.render(encoder, render_target);
-                            framework.render(encoder, render_target, context);
-                            Ok(())
-                        })
-                        .unwrap();
+                    let rendered = pixels.render_with(|encoder, render_target, context| {
+                        context.scaling_renderer.render(encoder, render_target);
+                        framework.render(encoder, render_target, context);
+                        Ok(())
+                    });
+
+                    // The frame is skipped, and the surface is reconfigured in case it was lost
+                    if let Err(err) = rendered {
+                        println!("[E] Failed to render the frame: {err}");
+                        let size = window.inner_size();
+                        if let Err(err) = pixels.resize_surface(size.width, size.height) {
+                            println!("[E] Failed to reconfigure the window surface: {err}");
+                        }
+                    }
                 }
                 _ => (),
             }
@@ -153,14 +275,25 @@ impl Renderer for WindowRenderer {
 
 impl WindowRenderer {
     pub fn new(analyzer: Arc<SpectrumAnalyzer>) -> Self {
+        let history_len = *analyzer.ctx.args.get_one::<u32>("history").unwrap() as usize;
         Self {
             window: Arc::new(Mutex::new(Window {
                 analyzer,
                 new: VecDeque::new(),
+                last_row: Vec::new(),
+                history: VecDeque::with_capacity(history_len),
+                history_len,
+                scroll: 0,
                 last_frame: Instant::now(),
                 frame_history: RingBuffer::new(),
                 frequency_indexes: IndexMap::new(),
                 mouse: None,
+                pointer_over_gui: false,
+                drag: None,
+                zoom: None,
+                markers: Vec::new(),
+                peak: Vec::new(),
+                peak_decay: 0.99,
                 size: INIT_SIZE,
 
                 flags: Flags::RECALC_FREQ | Flags::SHOW_INFO,
@@ -169,6 +302,35 @@ impl WindowRenderer {
     }
 }
 
+/// Decodes the window icon, the window just goes without one if that fails.
+fn load_icon() -> Option<Icon> {
+    let icon = image::load_from_memory(ICON)
+        .map_err(anyhow::Error::from)
+        .and_then(|x| {
+            let image = x.to_rgba8();
+            let (width, height) = image.dimensions();
+            Ok(Icon::from_rgba(image.into_raw(), width, height)?)
+        });
+
+    match icon {
+        Ok(icon) => Some(icon),
+        Err(err) => {
+            println!("[-] Failed to load the window icon: {err}");
+            None
+        }
+    }
+}
+
+/// Gets the title of the window, with the input and FFT size so multiple instances can be told apart.
+fn window_title(analyzer: &SpectrumAnalyzer) -> String {
+    format!(
+        "radio-data — {} @ {} Hz — FFT {}",
+        analyzer.ctx.input_name,
+        analyzer.ctx.input.sample_rate().0,
+        analyzer.fft_size()
+    )
+}
+
 impl Window {
     fn draw(&mut self, image: &mut [u8]) {
         let (width, height) = (self.size.0 as usize, self.size.1 as usize);
@@ -177,6 +339,11 @@ impl Window {
             self.frequency_indexes.clear();
         }
 
+        let peak_hold = self.flags.contains(Flags::PEAK_HOLD);
+        if !peak_hold || self.flags.intersects(Flags::CLEAR | Flags::RECALC_FREQ) {
+            self.peak.clear();
+        }
+
         if self.flags.contains(Flags::CAPTURE) {
             self.flags.set(Flags::CAPTURE, false);
             let buf =
@@ -188,67 +355,270 @@ impl Window {
             println!("[*] Saving capture to `{}`", name);
         }
 
-        if self.flags.contains(Flags::RESIZE) {
-            self.flags.set(Flags::RESIZE, false);
+        if self.flags.contains(Flags::EXPORT) {
+            self.flags.set(Flags::EXPORT, false);
+            self.export(gain);
+        }
+
+        if self.flags.contains(Flags::CLEAR) {
+            self.flags.set(Flags::CLEAR, false);
+            self.history.clear();
+            self.scroll = 0;
             image.iter_mut().for_each(|x| *x = 0);
         }
 
+        // Drop new rows while frozen, so the waterfall can be inspected
+        if self.flags.contains(Flags::FROZEN) {
+            self.new.clear();
+        }
+
+        while let Some(full_row) = self.new.pop_front() {
+            // If the FFT size changed, the old rows, zoom and peaks no longer line up
+            if !self.last_row.is_empty() && full_row.len() != self.last_row.len() {
+                self.zoom = None;
+                self.peak.clear();
+                self.history.clear();
+                self.frequency_indexes.clear();
+                self.flags.insert(Flags::RECALC_FREQ | Flags::REDRAW);
+            }
+
+            // Update the peak of each bin, letting old peaks decay
+            if peak_hold {
+                let row = &full_row[self.visible(&full_row)];
+                self.peak.resize(row.len(), 0.0);
+                for (peak, &x) in self.peak.iter_mut().zip(row.iter()) {
+                    *peak = (*peak * self.peak_decay).max(self.analyzer.normalize(x * gain));
+                }
+            }
+
+            // Scroll everything up one line and draw the new row at the bottom.
+            // Not needed if the whole waterfall is going to be redrawn anyway.
+            if !self.flags.contains(Flags::REDRAW) {
+                let prev = image[(width * 4)..(width * height * 4)].to_owned();
+                image[0..(width * (height - 1) * 4)].copy_from_slice(&prev);
+                self.draw_row(image, height - 1, &full_row, gain);
+            }
+
+            if self.history.len() >= self.history_len {
+                self.history.pop_front();
+            }
+            self.history.push_back(full_row.clone());
+            self.last_row = full_row;
+        }
+
+        if self.flags.contains(Flags::REDRAW) {
+            self.redraw(image, gain);
+        }
+    }
+
+    /// Gets the range of bins to draw from a row.
+    /// This is just the zoomed in bins, falling back to the full row if the zoom doesn't fit.
+    fn visible(&self, row: &[f32]) -> Range<usize> {
+        match &self.zoom {
+            Some(i) if i.end <= row.len() => i.clone(),
+            _ => 0..row.len(),
+        }
+    }
+
+    /// Draws a row of the waterfall at the y coordinate `y`, averaging the bins that fall on each pixel.
+    fn draw_row(&mut self, image: &mut [u8], y: usize, full_row: &[f32], gain: f32) {
+        let width = self.size.0 as usize;
+        let range = self.visible(full_row);
+        let row = &full_row[range.clone()];
+        let points_per_px = row.len() as f32 / width as f32;
+        let pxs_per_point = points_per_px.recip();
+
         let mut point_error = 0.0;
         let mut pixel_error = 0.0;
         let mut points = Vec::new();
         let mut xi = 0;
 
-        while let Some(row) = self.new.pop_front() {
-            let points_per_px = row.len() as f32 / width as f32;
-            let pxs_per_point = points_per_px.recip();
+        for (i, &x) in row.iter().enumerate() {
+            points.push(x * gain);
+            point_error += 1.0;
 
-            // scroll everything up one line
-            let prev = image[(width * 4)..(width * height * 4)].to_owned();
-            image[0..(width * (height - 1) * 4)].copy_from_slice(&prev);
+            if point_error >= points_per_px {
+                point_error -= 1.0;
 
-            // Draw new row
-            for (i, &x) in row.iter().enumerate() {
-                points.push(x * gain);
-                point_error += 1.0;
+                let avg = points.iter().copied().sum::<f32>() / points.len() as f32;
+                let color = color(
+                    self.analyzer.normalize(avg),
+                    self.analyzer.color_scheme.colors(),
+                );
 
-                if point_error >= points_per_px {
-                    point_error -= 1.0;
+                pixel_error += pxs_per_point;
+                while pixel_error >= pxs_per_point {
+                    set_pixel(image, width, (xi, y), color);
+                    pixel_error -= 1.0;
+                    xi += 1;
+                }
 
-                    let avg = points.iter().copied().sum::<f32>() / points.len() as f32;
-                    let val = 1.0 - E.powf(-avg);
-                    let color = color(val);
+                if self.flags.contains(Flags::RECALC_FREQ) {
+                    self.frequency_indexes
+                        .insert(xi, self.analyzer.index_to_freq(i + range.start));
+                }
 
-                    pixel_error += pxs_per_point;
-                    while pixel_error >= pxs_per_point {
-                        set_pixel(image, width, (xi, height - 1), color);
-                        pixel_error -= 1.0;
-                        xi += 1;
-                    }
+                points.clear();
+            }
+        }
 
-                    if self.flags.contains(Flags::RECALC_FREQ) {
-                        self.frequency_indexes
-                            .insert(xi, self.analyzer.index_to_freq(i));
-                    }
+        // The frequencies are the same for every row, so they only need to be found once
+        self.flags.set(Flags::RECALC_FREQ, false);
+    }
+
+    /// Redraws the whole waterfall from the history, with the scrolled to row at the bottom.
+    /// Used when the rows no longer line up with what was drawn, like after zooming or scrolling.
+    fn redraw(&mut self, image: &mut [u8], gain: f32) {
+        self.flags.set(Flags::REDRAW, false);
+        image.iter_mut().for_each(|x| *x = 0);
+
+        // The history is taken out while drawing, as drawing a row needs to update the frequency indexes
+        let history = mem::take(&mut self.history);
+        let end = history.len().saturating_sub(self.scroll);
+        let height = self.size.1 as usize;
+        for (y, row) in (0..height).rev().zip(history.range(..end).rev()) {
+            self.draw_row(image, y, row, gain);
+        }
+        self.history = history;
+    }
+
+    /// Saves the whole history as one tall image, not just the part that fits in the window.
+    /// Very long histories are cut down to the newest [`MAX_EXPORT_ROWS`] rows.
+    /// The rows are drawn here, but encoding the PNG can take a while so it is done on another thread.
+    fn export(&mut self, gain: f32) {
+        if self.history.is_empty() {
+            println!("[-] No waterfall history to export");
+            return;
+        }
+
+        let width = self.size.0 as usize;
+        let rows = self.history.len().min(MAX_EXPORT_ROWS);
+        if rows < self.history.len() {
+            println!("[-] Only exporting the newest {rows} rows of the waterfall");
+        }
+
+        let mut image = vec![0; width * rows * 4];
+        let history = mem::take(&mut self.history);
+        for (y, row) in history.range(history.len() - rows..).enumerate() {
+            self.draw_row(&mut image, y, row, gain);
+        }
+        self.history = history;
+
+        let buf = ImageBuffer::<Rgba<u8>, _>::from_raw(width as u32, rows as u32, image).unwrap();
+        let name = format!("waterfall-{}.png", Local::now().format("%Y-%m-%d-%H-%M-%S"));
+        thread::spawn(move || match buf.save(&name) {
+            Ok(_) => println!("[*] Saving waterfall to `{}`", name),
+            Err(e) => println!("[E] Failed to save waterfall to `{}`: {e}", name),
+        });
+    }
+
+    /// Scrolls back through the history with the mouse wheel or arrow keys while frozen.
+    fn handle_scroll(&mut self, input: &WinitInputHelper) {
+        if !self.flags.contains(Flags::FROZEN) || self.pointer_over_gui {
+            return;
+        }
+
+        let mut rows = (input.scroll_diff().round() as isize) * SCROLL_ROWS as isize;
+        if input.key_pressed(VirtualKeyCode::Up) {
+            rows += SCROLL_ROWS as isize;
+        }
+        if input.key_pressed(VirtualKeyCode::Down) {
+            rows -= SCROLL_ROWS as isize;
+        }
+
+        // Stop once the oldest row reaches the top of the window
+        let max = self.history.len().saturating_sub(self.size.1 as usize);
+        let scroll = self.scroll.saturating_add_signed(rows).min(max);
+        if scroll != self.scroll {
+            self.scroll = scroll;
+            self.flags.insert(Flags::REDRAW);
+        }
+    }
+
+    /// Starts a zoom when the left mouse button is pressed, and applies it when released.
+    /// Clicking without dragging places a marker instead, and right clicking resets the zoom.
+    fn handle_mouse(&mut self, input: &WinitInputHelper) {
+        let x = match self.mouse {
+            Some((x, _)) => x,
+            None => return,
+        };
+
+        if input.mouse_pressed(1) && !self.pointer_over_gui {
+            self.set_zoom(None);
+        }
+
+        if input.mouse_pressed(0) && !self.pointer_over_gui {
+            self.drag = Some(x);
+        }
 
-                    points.clear();
+        if !input.mouse_released(0) {
+            return;
+        }
+
+        // Drags that are too small to zoom into are clicks, which place a marker
+        let start = match self.drag.take() {
+            Some(i) if (i - x).abs() >= 5.0 => i,
+            Some(_) => {
+                if let Some(freq) = self.freq_at(x) {
+                    self.markers.push(freq);
                 }
+                return;
+            }
+            None => return,
+        };
+
+        let bins = (self.freq_at(start.min(x)), self.freq_at(start.max(x)));
+        if let (Some(start), Some(end)) = bins {
+            let start = self.analyzer.freq_to_index(start).round() as usize;
+            let end =
+                (self.analyzer.freq_to_index(end).round() as usize + 1).min(self.last_row.len());
+            if end >= start + 2 {
+                self.set_zoom(Some(start..end));
             }
-
-            xi = 0;
-            point_error = 0.0;
-            pixel_error = 0.0;
         }
+    }
 
-        self.flags.set(Flags::RECALC_FREQ, false);
+    /// Sets the zoom range, redrawing the waterfall as the old rows no longer line up.
+    fn set_zoom(&mut self, zoom: Option<Range<usize>>) {
+        self.zoom = zoom;
+        self.flags.insert(Flags::REDRAW | Flags::RECALC_FREQ);
+    }
+
+    /// Gets the frequency drawn at an x coordinate.
+    fn freq_at(&self, x: f32) -> Option<f32> {
+        self.frequency_indexes
+            .iter()
+            .filter(|i| *i.0 as f32 <= x)
+            .last()
+            .map(|x| *x.1)
+    }
+
+    /// Gets the x coordinates a frequency is drawn at, between the pixels on either side of it.
+    /// A frequency can be on screen more than once, like in each channel of a split spectrum.
+    fn x_at(&self, freq: f32) -> Vec<f32> {
+        let points = self.frequency_indexes.iter().collect::<Vec<_>>();
+        points
+            .windows(2)
+            .filter_map(|pair| {
+                let ((&x1, &f1), (&x2, &f2)) = (pair[0], pair[1]);
+                (f1 <= freq && freq < f2)
+                    .then(|| x1 as f32 + (freq - f1) / (f2 - f1) * (x2 - x1) as f32)
+            })
+            .collect()
     }
 
     fn top_line(&mut self, ui: &mut Ui) {
         // Main info table
-        // todo: maybe RMS and FFT resolution
         let analyzer = &self.analyzer;
+        // The jitter of the frame times is converted to FPS, to show how steady the frame rate is
+        let frame_time = self.frame_history.avg();
+        let fps_jitter = self.frame_history.std_dev() / (frame_time * frame_time);
         let mut info = [
-            ("FPS", format!("{:.2}", self.frame_history.avg().recip())),
-            ("FFT size", analyzer.fft_size.to_string()),
+            (
+                "FPS",
+                format!("{:.2} ± {:.2}", frame_time.recip(), fps_jitter),
+            ),
+            ("FFT size", analyzer.fft_size().to_string()),
             (
                 "Sample Rate",
                 analyzer.ctx.input.sample_rate().0.to_string(),
@@ -265,26 +635,73 @@ impl Window {
         ]
         .to_vec();
 
-        if let Some((x, _)) = self.mouse {
-            let freq = self
-                .frequency_indexes
-                .iter()
-                .filter(|i| *i.0 as f32 <= x)
-                .last();
+        // The resolution is how much of the spectrum each pixel covers, which can't be finer than one bin
+        let visible_bins = match &self.zoom {
+            Some(zoom) => zoom.len(),
+            None => self.last_row.len(),
+        };
+        let points_per_px = visible_bins as f32 / self.size.0 as f32;
+        info.push((
+            "Resolution",
+            nice_freq(analyzer.resolution() * points_per_px.max(1.0)),
+        ));
+
+        if !self.last_row.is_empty() {
+            let rms = self.last_row.iter().map(|x| x * x).sum::<f32>() / self.last_row.len() as f32;
+            info.push(("RMS", analyzer.rms_label(rms.sqrt())));
+        }
 
-            if let Some(i) = freq {
-                info.push(("Frequency", nice_freq(*i.1)));
-            }
+        if let Some(zoom) = &self.zoom {
+            info.push((
+                "Zoom",
+                format!(
+                    "{}..{}",
+                    nice_freq(analyzer.index_to_freq(zoom.start)),
+                    nice_freq(analyzer.index_to_freq(zoom.end)),
+                ),
+            ));
+        }
+
+        if self.scroll != 0 {
+            info.push(("Scrollback", format!("{} rows", self.scroll)));
+        }
+
+        if let Some(underruns) = analyzer.passthrough_underruns() {
+            info.push(("Underruns", underruns.to_string()));
+        }
+
+        match analyzer.dropped_frames() {
+            0 => {}
+            i => info.push(("Dropped Frames", i.to_string())),
+        }
+
+        if let Some(freq) = self.mouse.and_then(|(x, _)| self.freq_at(x)) {
+            info.push(("Frequency", nice_freq(self.peak_near(freq))));
+        }
+
+        // Each marker, then the distance between each marker and the one placed after it
+        let mut markers = Vec::new();
+        for (i, freq) in self.markers.iter().enumerate() {
+            markers.push((format!("Marker {}", i + 1), nice_freq(*freq)));
+        }
+        for (i, pair) in self.markers.windows(2).enumerate() {
+            markers.push((
+                format!("Δ {}-{}", i + 1, i + 2),
+                nice_freq((pair[1] - pair[0]).abs()),
+            ));
         }
 
         TableBuilder::new(ui)
             .column(Column::auto())
             .column(Column::remainder())
             .body(|mut body| {
-                for i in info {
+                let info = info
+                    .into_iter()
+                    .map(|(name, value)| (name.to_owned(), value));
+                for i in info.chain(markers) {
                     body.row(15.0, |mut row| {
                         row.col(|col| {
-                            col.label(i.0.to_owned() + ":");
+                            col.label(i.0 + ":");
                         });
                         row.col(|col| {
                             col.label(i.1);
@@ -298,21 +715,218 @@ impl Window {
         let mut gain = *self.analyzer.gain.read();
         ui.add(Slider::new(&mut gain, 0.0..=1.0).text("Gain"));
         *self.analyzer.gain.write() = gain;
+
+        // Color calibration, in the units of the magnitude scale
+        let range = match self.analyzer.scale {
+            MagnitudeScale::Exponential => 0.0..=EXP_CEILING * 4.0,
+            MagnitudeScale::Decibel => DB_MIN..=0.0,
+        };
+        let mut levels = *self.analyzer.levels.read();
+        ui.add(
+            Slider::new(&mut levels.floor, range.clone())
+                .clamp_to_range(false)
+                .text("Floor"),
+        );
+        ui.add(
+            Slider::new(&mut levels.ceiling, range)
+                .clamp_to_range(false)
+                .text("Ceiling"),
+        );
+        if levels.floor < levels.ceiling {
+            *self.analyzer.levels.write() = levels;
+        }
+
+        // FFT size, which is applied by the analyzer before the next buffer is processed
+        let fft_size = self.analyzer.fft_size();
+        let mut new_size = fft_size;
+        ComboBox::from_label("FFT Size")
+            .selected_text(fft_size.to_string())
+            .show_ui(ui, |ui| {
+                for size in FFT_SIZES.map(|x| 1_usize << x) {
+                    ui.selectable_value(&mut new_size, size, size.to_string());
+                }
+            });
+        if new_size != fft_size {
+            self.analyzer.request_fft_size(new_size);
+        }
         ui.separator();
 
         // Buttons
         ui.horizontal(|ui| {
             self.flags
-                .set_or(Flags::RESIZE, ui.button("Clear").clicked());
+                .set_or(Flags::CLEAR, ui.button("Clear").clicked());
             self.flags
                 .set_or(Flags::CAPTURE, ui.button("Capture").clicked());
+            self.flags
+                .set_or(Flags::EXPORT, ui.button("Export Waterfall").clicked());
+            if ui.button("Reset Zoom").clicked() {
+                self.set_zoom(None);
+            }
+            if ui.button("Clear Markers").clicked() {
+                self.markers.clear();
+            }
+
+            let mut peak_hold = self.flags.contains(Flags::PEAK_HOLD);
+            ui.checkbox(&mut peak_hold, "Peak Hold");
+            self.flags.set(Flags::PEAK_HOLD, peak_hold);
+
+            let mut grid = self.flags.contains(Flags::GRID);
+            ui.checkbox(&mut grid, "Grid");
+            self.flags.set(Flags::GRID, grid);
+
+            let mut frozen = self.flags.contains(Flags::FROZEN);
+            ui.checkbox(&mut frozen, "Freeze");
+            self.flags.set(Flags::FROZEN, frozen);
+
+            // Jump back to the newest rows when unfrozen
+            if !frozen && self.scroll != 0 {
+                self.scroll = 0;
+                self.flags.insert(Flags::REDRAW);
+            }
         });
+
+        if self.flags.contains(Flags::PEAK_HOLD) {
+            ui.add(Slider::new(&mut self.peak_decay, 0.9..=1.0).text("Peak Decay"));
+        }
+    }
+
+    /// Finds the strongest bin of the last row near a frequency, and interpolates its true frequency.
+    /// This looks at the bins covered by a few pixels around the cursor, so the readout snaps to nearby peaks.
+    fn peak_near(&self, freq: f32) -> f32 {
+        let bin = self.analyzer.freq_to_index(freq).round() as usize;
+        if bin >= self.last_row.len() {
+            return freq;
+        }
+
+        let radius = (self.last_row.len() / self.size.0 as usize).max(2);
+        let range = bin.saturating_sub(radius)..(bin + radius + 1).min(self.last_row.len());
+        let peak = self.last_row[range.clone()]
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|x| x.0 + range.start)
+            .unwrap_or(bin);
+
+        let offset = interpolate_peak(&self.last_row, peak) - peak as f32;
+        self.analyzer.index_to_freq(peak) + offset * self.analyzer.resolution()
+    }
+
+    /// Highlights the frequency range being selected while dragging to zoom.
+    fn zoom_selection(&self, ctx: &Context) {
+        let (start, x) = match (self.drag, self.mouse) {
+            (Some(start), Some((x, _))) => (start, x),
+            _ => return,
+        };
+
+        let scale = ctx.pixels_per_point();
+        let rect = Rect::from_two_pos(
+            Pos2::new(start / scale, 0.0),
+            Pos2::new(x / scale, self.size.1 as f32 / scale),
+        );
+        ctx.layer_painter(LayerId::background()).rect_filled(
+            rect,
+            0.0,
+            Color32::from_white_alpha(40),
+        );
+    }
+
+    /// Draws faint vertical lines at round frequencies over the waterfall, labeled at the top.
+    /// The spacing is picked from the frequencies on screen, so it follows the display range and zoom.
+    fn grid(&self, ctx: &Context) {
+        let (first, last) = match (
+            self.frequency_indexes.first(),
+            self.frequency_indexes.last(),
+        ) {
+            (Some(first), Some(last)) if last.1 > first.1 => (*first.1, *last.1),
+            _ => return,
+        };
+
+        let scale = ctx.pixels_per_point();
+        let height = self.size.1 as f32 / scale;
+        let step = grid_step(last - first, self.size.0 as f32 / scale / GRID_SPACING);
+        let painter = ctx.layer_painter(LayerId::background());
+
+        // Each line is drawn everywhere its frequency is on screen, like in both channels of a split spectrum
+        let mut line = (first / step).ceil() * step;
+        while line < last {
+            for x in self.x_at(line) {
+                let x = x / scale;
+                painter.line_segment(
+                    [Pos2::new(x, 0.0), Pos2::new(x, height)],
+                    Stroke::new(1.0, Color32::from_white_alpha(25)),
+                );
+                painter.text(
+                    Pos2::new(x + 2.0, 2.0),
+                    Align2::LEFT_TOP,
+                    nice_freq(line),
+                    FontId::proportional(10.0),
+                    Color32::from_white_alpha(120),
+                );
+            }
+            line += step;
+        }
+    }
+
+    /// Draws a line for each marker over the waterfall, labeled with its number and frequency.
+    /// Markers outside of the frequencies on screen, like when zoomed in, aren't drawn.
+    fn marker_lines(&self, ctx: &Context) {
+        let scale = ctx.pixels_per_point();
+        let height = self.size.1 as f32 / scale;
+        let painter = ctx.layer_painter(LayerId::background());
+
+        for (i, &freq) in self.markers.iter().enumerate() {
+            for x in self.x_at(freq) {
+                let x = x / scale;
+                painter.line_segment(
+                    [Pos2::new(x, 0.0), Pos2::new(x, height)],
+                    Stroke::new(1.0, Color32::YELLOW),
+                );
+                painter.text(
+                    Pos2::new(x + 2.0, height - 2.0),
+                    Align2::LEFT_BOTTOM,
+                    format!("{}: {}", i + 1, nice_freq(freq)),
+                    FontId::proportional(12.0),
+                    Color32::YELLOW,
+                );
+            }
+        }
+    }
+
+    /// Draws the peak hold line over the waterfall.
+    /// The peaks are drawn as a line from the bottom of the window, with a full scale peak reaching the top.
+    fn peak_line(&self, ctx: &Context) {
+        if self.peak.len() < 2 {
+            return;
+        }
+
+        let scale = ctx.pixels_per_point();
+        let (width, height) = (self.size.0 as f32 / scale, self.size.1 as f32 / scale);
+        let step = width / (self.peak.len() - 1) as f32;
+        let points = self
+            .peak
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| Pos2::new(i as f32 * step, height * (1.0 - x)))
+            .collect::<Vec<_>>();
+
+        ctx.layer_painter(LayerId::background())
+            .add(Shape::line(points, Stroke::new(1.0, Color32::WHITE)));
     }
 }
 
 impl Gui for Arc<Mutex<Window>> {
     fn ui(&self, ctx: &Context) {
         let mut this = self.lock();
+        if this.flags.contains(Flags::GRID) {
+            this.grid(ctx);
+        }
+        if this.flags.contains(Flags::PEAK_HOLD) {
+            this.peak_line(ctx);
+        }
+        this.marker_lines(ctx);
+        this.zoom_selection(ctx);
+        this.pointer_over_gui = ctx.is_pointer_over_area();
+
         egui::TopBottomPanel::top("menubar_container").show(ctx, |ui| {
             egui::menu::bar(ui, |ui| {
                 ui.label(RichText::new("[RADIO-DATA SPECTRUM ANALYZER]").monospace());
@@ -0,0 +1,79 @@
+//! Remembers the size and position of the spectrum analyzer window between runs.
+//! The state is kept in a small TOML file in the platform's config directory.
+
+use std::{fs, path::PathBuf};
+
+use anyhow::Context;
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+
+/// The name of the state file, in the config directory.
+const STATE_FILE: &str = "window.toml";
+
+/// The size and position of the window when it was last closed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WindowState {
+    /// The logical inner size of the window.
+    pub size: (u32, u32),
+    /// The physical position of the window's outer corner, not every platform has one.
+    pub position: Option<(i32, i32)>,
+}
+
+impl WindowState {
+    /// Loads the state saved by the last run.
+    /// Returns None if there isn't one, or if it can't be read.
+    pub fn load() -> Option<Self> {
+        let path = state_path()?;
+        let raw = fs::read_to_string(&path).ok()?;
+        match toml::from_str(&raw) {
+            Ok(state) => Some(state),
+            Err(err) => {
+                println!(
+                    "[-] Ignoring invalid window state `{}`: {err}",
+                    path.display()
+                );
+                None
+            }
+        }
+    }
+
+    /// Saves the state for the next run.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let path = state_path().context("No config directory found")?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create `{}`", parent.display()))?;
+        }
+
+        fs::write(&path, toml::to_string(self)?)
+            .with_context(|| format!("Failed to write window state `{}`", path.display()))
+    }
+}
+
+/// Gets the path of the state file, if the platform has a config directory.
+fn state_path() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "radio-data").map(|x| x.config_dir().join(STATE_FILE))
+}
+
+#[cfg(test)]
+mod test {
+    use super::WindowState;
+
+    #[test]
+    fn test_round_trip() {
+        let state = WindowState {
+            size: (1600, 900),
+            position: Some((-20, 40)),
+        };
+        let raw = toml::to_string(&state).unwrap();
+        assert_eq!(toml::from_str::<WindowState>(&raw).unwrap(), state);
+
+        let state = WindowState {
+            size: (1600, 900),
+            position: None,
+        };
+        let raw = toml::to_string(&state).unwrap();
+        assert_eq!(toml::from_str::<WindowState>(&raw).unwrap(), state);
+        assert!(toml::from_str::<WindowState>("size = 5").is_err());
+    }
+}
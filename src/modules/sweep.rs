@@ -0,0 +1,64 @@
+//! Plays a linear frequency sweep (chirp) once, then exits.
+//! Useful for measuring a radio's frequency response with the spectrum analyzer on the receiving end.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use parking_lot::Mutex;
+
+use super::{InitContext, Module};
+use crate::audio::tone::Chirp;
+
+pub struct Sweep {
+    ctx: InitContext,
+    chirp: Mutex<Chirp>,
+    finished: AtomicBool,
+}
+
+impl Sweep {
+    pub fn new(ctx: InitContext) -> Arc<Self> {
+        // Load command line arguments
+        let start = *ctx.args.get_one::<f32>("start").unwrap();
+        let end = *ctx.args.get_one::<f32>("end").unwrap();
+        let duration = *ctx.args.get_one::<f32>("duration").unwrap();
+
+        println!("[*] Sweeping from {start}Hz to {end}Hz over {duration}s");
+        let chirp = Chirp::new(start, end, ctx.sample_rate(), duration);
+
+        Arc::new(Self {
+            ctx,
+            chirp: Mutex::new(chirp),
+            finished: AtomicBool::new(false),
+        })
+    }
+}
+
+impl Module for Sweep {
+    fn name(&self) -> &'static str {
+        "sweep"
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+
+    fn output(&self, output: &mut [f32]) {
+        // Pass the chirp to the output of each channel, exiting once it has finished
+        let mut chirp = self.chirp.lock();
+        let mut last = 0.0;
+
+        for (i, e) in output.iter_mut().enumerate() {
+            if i % self.ctx.output.channels() as usize == 0 {
+                let val = chirp.next();
+                if val.is_none() {
+                    self.finished.store(true, Ordering::Relaxed);
+                }
+                last = val.unwrap_or(0.0);
+            }
+
+            *e = last;
+        }
+    }
+}
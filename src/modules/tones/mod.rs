@@ -0,0 +1 @@
+pub mod tones_detect;
@@ -0,0 +1,187 @@
+//! Shows the live magnitude of a set of tones as labeled bars in the terminal.
+//! Useful for monitoring CTCSS, pilot tones or custom signaling, without any decoding on top.
+//!
+//! All the tones are measured together with [`goertzel_mag_multi`] over a sliding window, whose length is set by the bandwidth.
+
+use std::{
+    io::{stdout, Write},
+    sync::Arc,
+    time::Duration,
+};
+
+use crossterm::{cursor, queue, style, terminal};
+use parking_lot::Mutex;
+
+use crate::{
+    audio::algorithms::{goertzel_mag_multi, to_db, to_mono, SlidingWindow, DB_MIN},
+    modules::{meter::bar_position, InitContext, Module},
+};
+
+/// How often the magnitudes are measured and the bars redrawn.
+const UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A tone to look for, from the `--freqs` argument.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DetectTone {
+    pub freq: f32,
+    /// The magnitude the tone counts as present at, overriding `--threshold`.
+    pub threshold: Option<f32>,
+}
+
+pub struct TonesDetect {
+    ctx: InitContext,
+
+    // == Settings ==
+    freqs: Vec<f32>,
+    thresholds: Vec<f32>,
+
+    // == Internal ==
+    window: Mutex<SlidingWindow>,
+}
+
+impl TonesDetect {
+    pub fn new(ctx: InitContext) -> Arc<Self> {
+        let args = ctx.args.subcommand().unwrap().1;
+        let tones = args.get_one::<Vec<DetectTone>>("freqs").unwrap();
+        let threshold = *args.get_one::<f32>("threshold").unwrap();
+        let bandwidth = *args.get_one::<f32>("bandwidth").unwrap();
+
+        let freqs = tones.iter().map(|x| x.freq).collect();
+        let thresholds = tones
+            .iter()
+            .map(|x| x.threshold.unwrap_or(threshold))
+            .collect();
+
+        let sample_rate = ctx.sample_rate().input as f32;
+        let window = ((sample_rate / bandwidth) as usize).max(1);
+        let hop = (sample_rate * UPDATE_INTERVAL.as_secs_f32()) as usize;
+
+        Arc::new(Self {
+            ctx,
+            freqs,
+            thresholds,
+            window: Mutex::new(SlidingWindow::new(window, hop)),
+        })
+    }
+
+    /// Redraws the bars in place, one line per tone.
+    fn render(&self, magnitudes: &[f32]) {
+        let width = terminal::size().map(|x| x.0 as usize).unwrap_or(80);
+        let bar_width = width.saturating_sub(35).max(10);
+
+        let mut stdout = stdout();
+        queue!(stdout, cursor::MoveToPreviousLine(self.freqs.len() as u16)).unwrap();
+
+        for ((freq, threshold), magnitude) in
+            self.freqs.iter().zip(&self.thresholds).zip(magnitudes)
+        {
+            let detected = magnitude >= threshold;
+            let fill = bar_position(*magnitude, DB_MIN, bar_width);
+            let marker = bar_position(*threshold, DB_MIN, bar_width);
+            let bar = (0..bar_width)
+                .map(|i| match i {
+                    _ if i < fill => '█',
+                    _ if i + 1 == marker => '|',
+                    _ => ' ',
+                })
+                .collect::<String>();
+
+            queue!(
+                stdout,
+                terminal::Clear(terminal::ClearType::CurrentLine),
+                style::Print(format!("{freq:>8.1}Hz [")),
+                style::SetForegroundColor(match detected {
+                    true => style::Color::Green,
+                    false => style::Color::DarkGrey,
+                }),
+                style::Print(bar),
+                style::ResetColor,
+                style::Print(format!("] {:>6.1} dBFS\n", to_db(*magnitude, DB_MIN))),
+            )
+            .unwrap();
+        }
+
+        stdout.flush().unwrap();
+    }
+}
+
+impl Module for TonesDetect {
+    fn name(&self) -> &'static str {
+        "tones-detect"
+    }
+
+    fn init(&self) {
+        let window = self.window.lock().size();
+        println!(
+            "[I] Watching {} tones, window: {window} samples ({:.2}Hz bandwidth)",
+            self.freqs.len(),
+            self.ctx.sample_rate().input as f32 / window as f32
+        );
+
+        // Make room for the bars, as they are redrawn over the previous lines
+        print!("{}", "\n".repeat(self.freqs.len()));
+    }
+
+    fn input(&self, input: &[f32]) {
+        let mut window = self.window.lock();
+        let mono = to_mono(input, self.ctx.input.channels() as usize);
+        let samples = match window.push(&mono) {
+            Some(samples) => samples,
+            None => return,
+        };
+
+        let magnitudes = goertzel_mag_multi(&self.freqs, samples, self.ctx.sample_rate().input);
+        self.render(&magnitudes);
+    }
+}
+
+/// Parses a comma-separated list of tones, like `88.5,100:0.05,123`.
+/// Each frequency can have its own threshold after a colon.
+pub fn parse_tones(s: &str) -> anyhow::Result<Vec<DetectTone>> {
+    let tones = s
+        .split(',')
+        .map(|x| -> anyhow::Result<DetectTone> {
+            let (freq, threshold) = match x.split_once(':') {
+                Some((freq, threshold)) => (freq, Some(threshold.trim().parse::<f32>()?)),
+                None => (x, None),
+            };
+            let freq = freq.trim().parse::<f32>()?;
+            anyhow::ensure!(freq > 0.0, "Frequencies must be positive, got {freq}");
+            Ok(DetectTone { freq, threshold })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    anyhow::ensure!(!tones.is_empty(), "Expected at least one frequency");
+    Ok(tones)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_tones, DetectTone};
+
+    #[test]
+    fn test_parse_tones() {
+        assert_eq!(
+            parse_tones("88.5, 100:0.05,123").unwrap(),
+            [
+                DetectTone {
+                    freq: 88.5,
+                    threshold: None
+                },
+                DetectTone {
+                    freq: 100.0,
+                    threshold: Some(0.05)
+                },
+                DetectTone {
+                    freq: 123.0,
+                    threshold: None
+                },
+            ]
+        );
+
+        assert!(parse_tones("").is_err());
+        assert!(parse_tones("100,abc").is_err());
+        assert!(parse_tones("100:high").is_err());
+        assert!(parse_tones("-100").is_err());
+    }
+}
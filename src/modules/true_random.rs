@@ -2,38 +2,72 @@
 //! It hosts a web server (with afire) to allow other applications to get random numbers.
 
 use std::{
+    collections::VecDeque,
+    fs,
+    net::IpAddr,
+    path::PathBuf,
     sync::{
-        atomic::{AtomicUsize, Ordering},
+        atomic::{AtomicBool, AtomicUsize, Ordering},
         Arc,
     },
     thread,
+    time::{Duration, Instant},
 };
 
 use afire::{
+    middleware::Middleware,
     trace::{self, Formatter, Level},
     Server,
 };
-use bitvec::{order::Lsb0, vec::BitVec, view::BitView};
-use parking_lot::Mutex;
+use bitvec::{order::Lsb0, vec::BitVec};
+use clap::ValueEnum;
+use hashbrown::HashMap;
+use parking_lot::{Condvar, Mutex};
+use serde::Serialize;
 
-use crate::misc::ring_buffer::RingBuffer;
+use crate::{audio::algorithms::to_mono, misc::ring_buffer::RingBuffer};
 
-use super::{InitContext, Module};
+use super::{wait_until, InitContext, Module};
 
 const RANGE_HISTORY: usize = 1000;
+/// The number of samples used to check if the input is stuck.
+const VARIANCE_HISTORY: usize = 4800;
+/// The input variance below which the entropy source is considered stuck or silent.
+const MIN_VARIANCE: f32 = 1e-8;
+/// The p-value below which a health test fails.
+const HEALTH_SIGNIFICANCE: f64 = 0.01;
+/// The false positive rate for the repetition count test.
+const REPETITION_ALPHA: f64 = 1.0 / (1u32 << 20) as f64;
+/// The minimum entropy (in bits) expected of each byte, for the repetition count test.
+const ASSUMED_ENTROPY: f64 = 4.0;
+/// The most dice or coins that can be drawn in one request.
+const MAX_DRAWS: usize = 1000;
+/// How far back the rate the buffer is filling at is measured over.
+const RATE_WINDOW: Duration = Duration::from_secs(10);
 
 pub struct TrueRandom {
     ctx: InitContext,
     args: Args,
     buffer: Buffer,
+    /// Recent input samples, used to detect a stuck or silent input.
+    history: Mutex<RingBuffer<f32, VARIANCE_HISTORY>>,
+    /// If the entropy source seems to be working.
+    source_ok: AtomicBool,
+    /// Limits how quickly each client can take data, if `--rate-limit` is set.
+    limiter: Option<RateLimiter>,
 }
 
 /// Buffer of random data
 struct Buffer {
     target: usize,
+    debias: Debias,
     range: Mutex<RingBuffer<f32, RANGE_HISTORY>>,
     data: Mutex<Vec<u8>>,
     size: AtomicUsize,
+    /// Notified whenever new data is added to the buffer.
+    filled: Condvar,
+    /// When data was added to the buffer, and how many bytes, over the last [`RATE_WINDOW`].
+    fills: Mutex<VecDeque<(Instant, usize)>>,
 }
 
 // Arguments for this module
@@ -42,6 +76,38 @@ struct Args {
     port: u16,
     threads: usize,
     buffer_size: usize,
+    save: Option<PathBuf>,
+    load: Option<PathBuf>,
+    debias: Debias,
+    /// The secret clients must send in the `Authorization` header to use the data routes.
+    token: Option<String>,
+}
+
+/// Limits how many bytes each client IP can take per second, with a token bucket for each.
+/// Requests bigger than a client's allowance are refused before any data is taken.
+/// Rejection sampling can still use a few more bytes than were checked for, which puts the client in debt until it is paid off.
+struct RateLimiter {
+    bytes_per_sec: f32,
+    clients: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+struct Bucket {
+    /// The number of bytes the client can still take, negative if it is in debt.
+    allowance: f32,
+    updated: Instant,
+}
+
+/// The method used to remove bias from the raw bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Debias {
+    /// Go through the bits in pairs, discarding pairs where both bits are the same and keeping the first bit of the others.
+    /// This discards a lot but its important for getting rid of bias.
+    VonNeumann,
+    /// Use the bits as-is.
+    None,
+    /// XOR each pair of bits together.
+    /// This reduces bias without throwing away as much data as Von Neumann.
+    XorFold,
 }
 
 impl TrueRandom {
@@ -56,25 +122,102 @@ impl TrueRandom {
             port: *ctx.args.get_one("port").unwrap(),
             threads: *ctx.args.get_one("threads").unwrap(),
             buffer_size: *ctx.args.get_one("buffer-size").unwrap(),
+            save: ctx.args.get_one::<PathBuf>("save").cloned(),
+            load: ctx.args.get_one::<PathBuf>("load").cloned(),
+            debias: *ctx.args.get_one("debias").unwrap(),
+            token: ctx.args.get_one::<String>("token").cloned(),
         };
+        let limiter = ctx
+            .args
+            .get_one::<f32>("rate-limit")
+            .map(|&x| RateLimiter::new(x));
 
         let this = Self {
             ctx,
-            buffer: Buffer::new(args.buffer_size),
+            buffer: Buffer::new(args.buffer_size, args.debias),
+            history: Mutex::new(RingBuffer::new()),
+            source_ok: AtomicBool::new(true),
+            limiter,
             args,
         };
 
+        // Restore the buffer saved from a previous run
+        if let Some(path) = &this.args.load {
+            match fs::read(path) {
+                Ok(data) => {
+                    this.buffer.load(&data);
+                    println!(
+                        "[*] Loaded {} bytes from {}",
+                        this.buffer.size(),
+                        path.display()
+                    );
+                }
+                Err(e) => println!("[-] Failed to load buffer from {}: {e}", path.display()),
+            }
+        }
+
         // Create a new web server
         let mut server = Server::<Self>::new(&this.args.host, this.args.port).state(this);
         routes::attach(&mut server);
+        let app = server.app();
+        access::Access::new(app.clone()).attach(&mut server);
 
         // Start the server in a new thread
-        let app = server.app();
         let threads = app.args.threads;
         thread::spawn(move || server.start_threaded(threads).unwrap());
 
         app
     }
+
+    /// Writes the current contents of the buffer to the save path, if there is one.
+    fn save(&self) {
+        let path = match &self.args.save {
+            Some(i) => i,
+            None => return,
+        };
+
+        let data = self.buffer.data.lock();
+        match fs::write(path, &*data) {
+            Ok(_) => println!("[*] Saved {} bytes to {}", data.len(), path.display()),
+            Err(e) => println!("[E] Failed to save buffer to {}: {e}", path.display()),
+        }
+    }
+
+    /// Takes bytes given to a client from its rate limit allowance.
+    fn charge(&self, ip: IpAddr, bytes: usize) {
+        if let Some(limiter) = &self.limiter {
+            limiter.charge(ip, bytes, Instant::now());
+        }
+    }
+
+    /// Checks the variance of the recent input samples to detect a stuck or silent input.
+    /// Logs a message when the state of the source changes, and returns if the source is ok.
+    fn check_source(&self, samples: &[f32]) -> bool {
+        let mut history = self.history.lock();
+        for &sample in samples {
+            history.push(sample);
+        }
+
+        if !history.filled {
+            return self.source_ok.load(Ordering::Relaxed);
+        }
+
+        let mean = history.avg();
+        let variance =
+            history.data.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / VARIANCE_HISTORY as f32;
+
+        let ok = variance >= MIN_VARIANCE;
+        if self.source_ok.swap(ok, Ordering::Relaxed) != ok {
+            match ok {
+                true => println!("[*] Input variance has recovered, resuming collection"),
+                false => println!(
+                    "[-] Input variance is very low ({variance:e}), the input may be muted or disconnected"
+                ),
+            }
+        }
+
+        ok
+    }
 }
 
 impl Module for TrueRandom {
@@ -82,25 +225,26 @@ impl Module for TrueRandom {
         "true-random"
     }
 
+    /// Saves the buffer once the program is stopped.
+    fn block(&self) {
+        wait_until(|| self.is_finished());
+        self.save();
+    }
+
     fn input(&self, input: &[f32]) {
-        // If the buffer is full, don't add any more data
-        if self.buffer.size() >= self.args.buffer_size {
+        // Add the data to the buffer.
+        // If you have more than one channel, the data will be averaged.
+        let buffer = to_mono(input, self.ctx.input.channels() as usize);
+
+        // Don't use the data if the input seems to be muted or disconnected
+        if !self.check_source(&buffer) {
             return;
         }
 
-        // Add the data to the buffer.
-        // If you have more than one channel, the data will be averaged.
-        let mut buffer = Vec::with_capacity(input.len() / self.ctx.input.channels() as usize + 1);
-        let mut working = 0.0;
-        for (i, e) in input.iter().enumerate() {
-            working += e;
-
-            if i != 0 && i % self.ctx.input.channels() as usize == 0 {
-                buffer.push(working / self.ctx.input.channels() as f32);
-                working = 0.0;
-            }
+        // If the buffer is full, don't add any more data
+        if self.buffer.size() >= self.args.buffer_size {
+            return;
         }
-        buffer.push(working / self.ctx.input.channels() as f32);
 
         // Convert data from a float in the range [-1, 1] to an i32 in the range [-2^31, 2^31)
         self.buffer.fill_buffer(&buffer);
@@ -109,12 +253,15 @@ impl Module for TrueRandom {
 
 impl Buffer {
     /// Create a new buffer with the given size.
-    pub fn new(size: usize) -> Self {
+    pub fn new(size: usize, debias: Debias) -> Self {
         Self {
             target: size,
+            debias,
             data: Mutex::new(Vec::with_capacity(size)),
             size: AtomicUsize::new(0),
             range: Mutex::new(RingBuffer::new()),
+            filled: Condvar::new(),
+            fills: Mutex::new(VecDeque::new()),
         }
     }
 
@@ -132,10 +279,7 @@ impl Buffer {
             return;
         }
 
-        // This is for correcting for any bias in the data.
-        // It does this by going through the data in chunks of two bits, if both bits are the same, it discards them.
-        // Then it takes the first bit of each chunk and adds it to the buffer.
-        // This discards a lot but its important for getting rid of bias.
+        // The bits of each sample are run through the debiasing algorithm to correct for any bias in the data.
         let mut new_data = BitVec::<u8, Lsb0>::new();
         let mut range = self.range.lock();
         for &sample in data {
@@ -149,13 +293,7 @@ impl Buffer {
             let max = range.max() as f64;
             let val = ((sample as f64 - min) / (max - min) * (u32::MAX as f64)) as u32;
 
-            new_data.extend(
-                val.to_ne_bytes()
-                    .view_bits::<Lsb0>()
-                    .chunks(2)
-                    .filter(|x| x[0] != x[1])
-                    .map(|x| x[0]),
-            );
+            new_data.extend(self.debias.apply(&to_bits(&val.to_ne_bytes())));
 
             // If we have enough data, stop
             if new_data.len() >= needed {
@@ -165,9 +303,64 @@ impl Buffer {
 
         // Convert the data to a vector of bytes and add it to the buffer
         let new_data = new_data.into_vec();
+        let added = needed.min(new_data.len());
+        let mut data = self.data.lock();
+        data.extend(new_data[..added].iter());
+        self.size.store(data.len(), Ordering::Release);
+        self.filled.notify_all();
+        drop(data);
+
+        self.record_fill(Instant::now(), added);
+    }
+
+    /// Records that `bytes` were added to the buffer at `time`, forgetting fills older than [`RATE_WINDOW`].
+    /// Fills that add nothing are still recorded, as the time since the last fill is part of the rate.
+    fn record_fill(&self, time: Instant, bytes: usize) {
+        let mut fills = self.fills.lock();
+        fills.push_back((time, bytes));
+        while let Some(&(oldest, _)) = fills.front() {
+            if time.duration_since(oldest) <= RATE_WINDOW {
+                break;
+            }
+            fills.pop_front();
+        }
+    }
+
+    /// Gets how many bytes per second were being added, over the last [`RATE_WINDOW`] of filling.
+    /// Nothing is added while the buffer is full, so the rate from before it filled up is kept.
+    pub fn fill_rate(&self) -> f32 {
+        let fills = self.fills.lock();
+        let span = match (fills.front(), fills.back()) {
+            (Some(first), Some(last)) => last.0.duration_since(first.0).as_secs_f32(),
+            _ => return 0.0,
+        };
+        if span == 0.0 {
+            return 0.0;
+        }
+
+        // The bytes of the first fill were generated before the span starts
+        fills.iter().skip(1).map(|x| x.1).sum::<usize>() as f32 / span
+    }
+
+    /// Estimates how many seconds until the buffer is full at the current [`Buffer::fill_rate`].
+    /// Returns None if it isn't filling at all.
+    pub fn time_to_full(&self) -> Option<f32> {
+        let remaining = self.target.saturating_sub(self.size());
+        let rate = self.fill_rate();
+        match remaining {
+            0 => Some(0.0),
+            _ if rate > 0.0 => Some(remaining as f32 / rate),
+            _ => None,
+        }
+    }
+
+    /// Adds already processed data directly to the buffer, up to its target size.
+    pub fn load(&self, new: &[u8]) {
         let mut data = self.data.lock();
-        data.extend(new_data[..needed.min(new_data.len())].iter());
+        let needed = self.target.saturating_sub(data.len());
+        data.extend_from_slice(&new[..needed.min(new.len())]);
         self.size.store(data.len(), Ordering::Release);
+        self.filled.notify_all();
     }
 
     // Get the specified number of bytes from the buffer.
@@ -182,6 +375,141 @@ impl Buffer {
         self.size.store(data.len(), Ordering::Release);
         Some(out)
     }
+
+    /// Get up to `max` bytes from the buffer.
+    /// If the buffer is empty, this blocks until more data is added.
+    pub fn wait_raw(&self, max: usize) -> Vec<u8> {
+        let mut data = self.data.lock();
+        while data.is_empty() {
+            self.filled.wait(&mut data);
+        }
+
+        let out = data.drain(..max.min(data.len())).collect();
+        self.size.store(data.len(), Ordering::Release);
+        out
+    }
+
+    /// Get a uniformly distributed random number in the range [0, n).
+    /// Uses rejection sampling to avoid modulo bias, so the number of bytes used can vary.
+    /// The bytes consumed are added to `used`, returns None if the buffer runs out.
+    pub fn uniform(&self, n: u32, used: &mut Vec<u8>) -> Option<u32> {
+        let bytes = uniform_bytes(n);
+        let range = 1u64 << (bytes * 8);
+        let zone = range - range % n as u64;
+
+        loop {
+            let data = self.get_raw(bytes)?;
+            used.extend_from_slice(&data);
+
+            let val = data
+                .iter()
+                .rev()
+                .fold(0u64, |acc, &x| (acc << 8) | x as u64);
+            if val < zone {
+                return Some((val % n as u64) as u32);
+            }
+        }
+    }
+}
+
+impl RateLimiter {
+    fn new(bytes_per_sec: f32) -> Self {
+        Self {
+            bytes_per_sec,
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Gets the number of bytes a client can take right now, which is a full bucket for new clients.
+    fn allowance(&self, ip: IpAddr, now: Instant) -> f32 {
+        let mut clients = self.clients.lock();
+        match clients.get_mut(&ip) {
+            Some(bucket) => {
+                bucket.refill(self.bytes_per_sec, now);
+                bucket.allowance
+            }
+            None => self.bytes_per_sec,
+        }
+    }
+
+    /// Checks if a client can take more data.
+    /// If it is in debt, returns how long until it is paid off.
+    fn check(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        let allowance = self.allowance(ip, now);
+        match allowance < 0.0 {
+            true => Err(Duration::from_secs_f32(-allowance / self.bytes_per_sec)),
+            false => Ok(()),
+        }
+    }
+
+    /// Checks if a client can take `bytes` without going over its allowance.
+    /// If not, returns how long until it can, or None if the request is more than a full bucket and never will.
+    fn check_take(&self, ip: IpAddr, bytes: usize, now: Instant) -> Result<(), Option<Duration>> {
+        let bytes = bytes as f32;
+        if bytes > self.bytes_per_sec {
+            return Err(None);
+        }
+
+        let allowance = self.allowance(ip, now);
+        match allowance < bytes {
+            true => Err(Some(Duration::from_secs_f32(
+                (bytes - allowance) / self.bytes_per_sec,
+            ))),
+            false => Ok(()),
+        }
+    }
+
+    /// Takes bytes from a client's allowance, which may put it in debt.
+    fn charge(&self, ip: IpAddr, bytes: usize, now: Instant) {
+        let mut clients = self.clients.lock();
+
+        // Forget clients that have been idle long enough to have a full bucket again
+        let rate = self.bytes_per_sec;
+        clients
+            .retain(|_, x| x.allowance + now.duration_since(x.updated).as_secs_f32() * rate < rate);
+
+        let bucket = clients.entry(ip).or_insert(Bucket {
+            allowance: rate,
+            updated: now,
+        });
+        bucket.refill(rate, now);
+        bucket.allowance -= bytes as f32;
+    }
+}
+
+impl Bucket {
+    /// Adds the allowance earned since the last update, up to one second worth of bytes.
+    fn refill(&mut self, bytes_per_sec: f32, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f32();
+        self.allowance = (self.allowance + elapsed * bytes_per_sec).min(bytes_per_sec);
+        self.updated = now;
+    }
+}
+
+/// Gets the number of bytes each draw of [`Buffer::uniform`] takes from the buffer, not counting rejected draws.
+fn uniform_bytes(n: u32) -> usize {
+    (32 - n.saturating_sub(1).leading_zeros() as usize)
+        .div_ceil(8)
+        .max(1)
+}
+
+impl Debias {
+    /// Removes bias from the given bits.
+    fn apply(&self, bits: &[bool]) -> Vec<bool> {
+        match self {
+            Debias::VonNeumann => bits
+                .chunks(2)
+                .filter(|x| x.len() == 2 && x[0] != x[1])
+                .map(|x| x[0])
+                .collect(),
+            Debias::None => bits.to_vec(),
+            Debias::XorFold => bits
+                .chunks(2)
+                .filter(|x| x.len() == 2)
+                .map(|x| x[0] ^ x[1])
+                .collect(),
+        }
+    }
 }
 
 /// Calculate the entropy of the given data.
@@ -204,6 +532,113 @@ fn entropy(data: &[u8]) -> f32 {
     entropy // (data.len() as f32).log2()
 }
 
+/// The result of a randomness health test.
+#[derive(Serialize)]
+struct HealthTest {
+    /// The p-value of the test, or the longest run for the repetition count test
+    statistic: f64,
+    passed: bool,
+}
+
+/// Converts bytes into a list of bits, least significant bit first.
+fn to_bits(data: &[u8]) -> Vec<bool> {
+    data.iter()
+        .flat_map(|x| (0..8).map(move |i| x >> i & 1 == 1))
+        .collect()
+}
+
+/// The NIST SP 800-22 frequency (monobit) test.
+/// Checks that the number of ones and zeros are about the same.
+fn monobit_test(bits: &[bool]) -> HealthTest {
+    if bits.is_empty() {
+        return HealthTest::failed();
+    }
+
+    let sum = bits.iter().map(|&x| if x { 1 } else { -1 }).sum::<i64>();
+    let s_obs = sum.unsigned_abs() as f64 / (bits.len() as f64).sqrt();
+    HealthTest::from_p(erfc(s_obs / 2f64.sqrt()))
+}
+
+/// The NIST SP 800-22 runs test.
+/// Checks that the number of runs of identical bits is what would be expected from random data.
+fn runs_test(bits: &[bool]) -> HealthTest {
+    if bits.is_empty() {
+        return HealthTest::failed();
+    }
+
+    // The runs test is only valid if the data passes a basic frequency check
+    let n = bits.len() as f64;
+    let pi = bits.iter().filter(|&&x| x).count() as f64 / n;
+    if (pi - 0.5).abs() >= 2.0 / n.sqrt() {
+        return HealthTest::failed();
+    }
+
+    let runs = 1 + bits.windows(2).filter(|x| x[0] != x[1]).count();
+    let expected = 2.0 * n * pi * (1.0 - pi);
+    let p = erfc((runs as f64 - expected).abs() / (2.0 * (2.0 * n).sqrt() * pi * (1.0 - pi)));
+    HealthTest::from_p(p)
+}
+
+/// The NIST SP 800-90B repetition count test.
+/// Fails if any byte is repeated more times in a row than would be expected from a working source.
+fn repetition_count_test(data: &[u8]) -> HealthTest {
+    let cutoff = 1.0 + (-REPETITION_ALPHA.log2() / ASSUMED_ENTROPY).ceil();
+
+    let mut longest = 0;
+    let mut run = 0;
+    for (i, e) in data.iter().enumerate() {
+        run = match i > 0 && data[i - 1] == *e {
+            true => run + 1,
+            false => 1,
+        };
+        longest = longest.max(run);
+    }
+
+    HealthTest {
+        statistic: longest as f64,
+        passed: !data.is_empty() && (longest as f64) < cutoff,
+    }
+}
+
+/// Complementary error function.
+/// Uses the approximation from Numerical Recipes, which has a fractional error of less than 1.2e-7.
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807
+                            + t * (-1.13520398
+                                + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277))))))));
+    let out = t * poly.exp();
+
+    match x >= 0.0 {
+        true => out,
+        false => 2.0 - out,
+    }
+}
+
+impl HealthTest {
+    /// Creates a test result from a p-value.
+    fn from_p(p: f64) -> Self {
+        Self {
+            statistic: p,
+            passed: p >= HEALTH_SIGNIFICANCE,
+        }
+    }
+
+    /// A failed test, for when there is no data to test.
+    fn failed() -> Self {
+        Self {
+            statistic: 0.0,
+            passed: false,
+        }
+    }
+}
+
 /// Custom logger for afire
 struct Logger;
 
@@ -219,10 +654,53 @@ impl Formatter for Logger {
 
 /// Define the routes for the server
 mod routes {
+    use std::{
+        io::{self, Read},
+        net::IpAddr,
+        sync::{atomic::Ordering, Arc},
+        thread,
+        time::Instant,
+    };
+
     use afire::{Content, Method, Response, Server};
     use serde::Serialize;
 
-    use super::{entropy, TrueRandom};
+    use super::{
+        entropy, monobit_test, repetition_count_test, runs_test, to_bits, uniform_bytes,
+        HealthTest, TrueRandom, MAX_DRAWS,
+    };
+
+    /// Streams random bytes from the buffer as they are generated.
+    struct RandomStream {
+        app: Arc<TrueRandom>,
+        /// The client the bytes are sent to, for the rate limit.
+        ip: IpAddr,
+        remaining: usize,
+    }
+
+    impl Read for RandomStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.remaining == 0 || buf.is_empty() {
+                return Ok(0);
+            }
+
+            // Only send as much as the client's allowance covers, waiting for at least a byte of it.
+            // The stream could otherwise take as much as it wants once started.
+            let mut len = self.remaining.min(buf.len());
+            if let Some(limiter) = &self.app.limiter {
+                while let Err(Some(wait)) = limiter.check_take(self.ip, 1, Instant::now()) {
+                    thread::sleep(wait);
+                }
+                len = len.min(limiter.allowance(self.ip, Instant::now()) as usize);
+            }
+
+            let data = self.app.buffer.wait_raw(len);
+            self.app.charge(self.ip, data.len());
+            buf[..data.len()].copy_from_slice(&data);
+            self.remaining -= data.len();
+            Ok(data.len())
+        }
+    }
 
     #[derive(Serialize)]
     struct Status {
@@ -231,6 +709,58 @@ mod routes {
         percent_filled: f32,
         bit_ratio: f32,
         entropy: f32,
+        source_ok: bool,
+        /// How many bytes per second the buffer has recently been filling at.
+        bytes_per_sec: f32,
+        /// How long until the buffer is full, or null if it isn't filling.
+        estimated_full_seconds: Option<f32>,
+    }
+
+    #[derive(Serialize)]
+    struct Health {
+        monobit: HealthTest,
+        runs: HealthTest,
+        repetition_count: HealthTest,
+    }
+
+    /// Checks that `count` numbers in the range [0, n) can be drawn before taking any of them.
+    /// Otherwise a request could use up the buffer, then fail partway through and throw away what it took.
+    fn check_draws(app: &TrueRandom, count: usize, n: u32) -> Option<Response> {
+        if count > MAX_DRAWS {
+            return Some(
+                Response::new()
+                    .status(400)
+                    .text(format!("At most {MAX_DRAWS} can be drawn at once.")),
+            );
+        }
+
+        match count * uniform_bytes(n) > app.buffer.size() {
+            true => Some(
+                Response::new()
+                    .status(400)
+                    .text("Buffer not filled enough."),
+            ),
+            false => None,
+        }
+    }
+
+    /// Checks that taking `bytes` won't put the client over its rate limit allowance, before any of them are taken.
+    /// Otherwise a single big request could take far more than the limit allows.
+    fn check_limit(app: &TrueRandom, ip: IpAddr, bytes: usize) -> Option<Response> {
+        let limiter = app.limiter.as_ref()?;
+        match limiter.check_take(ip, bytes, Instant::now()) {
+            Ok(()) => None,
+            Err(None) => Some(Response::new().status(429).text(format!(
+                "At most {} bytes can be taken at once.",
+                limiter.bytes_per_sec as usize
+            ))),
+            Err(Some(wait)) => Some(
+                Response::new()
+                    .status(429)
+                    .text("Rate limit exceeded.")
+                    .header("Retry-After", wait.as_secs_f32().ceil().to_string()),
+            ),
+        }
     }
 
     pub fn attach(server: &mut Server<TrueRandom>) {
@@ -244,12 +774,20 @@ mod routes {
             }
             drop(buffer);
 
+            let source_ok = app.source_ok.load(Ordering::Relaxed);
             let status = Status {
                 buffer_filled: app.buffer.size(),
                 buffer_size: app.args.buffer_size,
                 percent_filled: app.buffer.size() as f32 / app.args.buffer_size as f32,
                 bit_ratio: bit_ones as f32 / bits as f32,
                 entropy: entropy(&app.buffer.data.lock()),
+                source_ok,
+                bytes_per_sec: app.buffer.fill_rate(),
+                // Nothing is collected while the source is down, so it won't fill at the last rate
+                estimated_full_seconds: match source_ok {
+                    true => app.buffer.time_to_full(),
+                    false => None,
+                },
             };
 
             Response::new()
@@ -257,6 +795,22 @@ mod routes {
                 .content(Content::JSON)
         });
 
+        // Runs some randomness health tests on the data currently in the buffer
+        server.stateful_route(Method::GET, "/health", |app, _req| {
+            let buffer = app.buffer.data.lock();
+            let bits = to_bits(&buffer);
+            let health = Health {
+                monobit: monobit_test(&bits),
+                runs: runs_test(&bits),
+                repetition_count: repetition_count_test(&buffer),
+            };
+            drop(buffer);
+
+            Response::new()
+                .text(serde_json::to_string(&health).unwrap())
+                .content(Content::JSON)
+        });
+
         // Get a specified number of bytes from the buffer
         server.stateful_route(Method::GET, "/raw/{len}", |app, req| {
             let len = req.param("len").unwrap().parse::<usize>().unwrap();
@@ -265,20 +819,39 @@ mod routes {
                     .status(400)
                     .text("Buffer not filled enough.");
             }
+            if let Some(res) = check_limit(&app, req.address.ip(), len) {
+                return res;
+            }
 
             let data = app.buffer.get_raw(len).unwrap();
+            app.charge(req.address.ip(), data.len());
             let entropy = entropy(&data);
             Response::new()
                 .bytes(&data)
                 .header("X-Entropy", entropy.to_string())
         });
 
+        // Streams {len} bytes, sending them as they are generated
+        server.stateful_route(Method::GET, "/stream/{len}", |app, req| {
+            let len = req.param("len").unwrap().parse::<usize>().unwrap();
+            Response::new().stream(RandomStream {
+                app,
+                ip: req.address.ip(),
+                remaining: len,
+            })
+        });
+
         // Gets a random float between {min} and {max}
         server.stateful_route(Method::GET, "/data/number/{min}/{max}", |app, req| {
             let min = req.param("min").unwrap().parse::<f64>().unwrap();
             let max = req.param("max").unwrap().parse::<f64>().unwrap();
 
+            if let Some(res) = check_limit(&app, req.address.ip(), 8) {
+                return res;
+            }
+
             let data = app.buffer.get_raw(8).unwrap();
+            app.charge(req.address.ip(), data.len());
             let entropy = entropy(&data);
             let number = u64::from_le_bytes([
                 data[0], data[1], data[2], data[3], data[4], data[5], data[6], data[7],
@@ -296,7 +869,12 @@ mod routes {
             let min = req.param("min").unwrap().parse::<u64>().unwrap() as f32;
             let max = req.param("max").unwrap().parse::<u64>().unwrap() as f32;
 
+            if let Some(res) = check_limit(&app, req.address.ip(), 8) {
+                return res;
+            }
+
             let data = app.buffer.get_raw(8).unwrap();
+            app.charge(req.address.ip(), data.len());
             let entropy = entropy(&data);
             let number = u64::from_le_bytes([
                 data[0], data[1], data[2], data[3], data[4], data[5], data[6], data[7],
@@ -308,5 +886,342 @@ mod routes {
                 .content(Content::JSON)
                 .header("X-Entropy", entropy.to_string())
         });
+
+        // Rolls {count} dice with {sides} sides each
+        server.stateful_route(Method::GET, "/data/dice/{sides}/{count}", |app, req| {
+            let sides = req.param("sides").unwrap().parse::<u32>().unwrap();
+            let count = req.param("count").unwrap().parse::<usize>().unwrap();
+            if sides == 0 {
+                return Response::new()
+                    .status(400)
+                    .text("Dice need at least one side.");
+            }
+            if let Some(res) = check_draws(&app, count, sides) {
+                return res;
+            }
+            if let Some(res) = check_limit(&app, req.address.ip(), count * uniform_bytes(sides)) {
+                return res;
+            }
+
+            let mut used = Vec::new();
+            let rolls = (0..count)
+                .map(|_| app.buffer.uniform(sides, &mut used).map(|x| x + 1))
+                .collect::<Option<Vec<_>>>();
+            app.charge(req.address.ip(), used.len());
+            let rolls = match rolls {
+                Some(i) => i,
+                None => {
+                    return Response::new()
+                        .status(400)
+                        .text("Buffer not filled enough.")
+                }
+            };
+
+            Response::new()
+                .text(serde_json::to_string(&rolls).unwrap())
+                .content(Content::JSON)
+                .header("X-Entropy", entropy(&used).to_string())
+        });
+
+        // Flips {count} coins
+        server.stateful_route(Method::GET, "/data/coin/{count}", |app, req| {
+            let count = req.param("count").unwrap().parse::<usize>().unwrap();
+            if let Some(res) = check_draws(&app, count, 2) {
+                return res;
+            }
+            if let Some(res) = check_limit(&app, req.address.ip(), count * uniform_bytes(2)) {
+                return res;
+            }
+
+            let mut used = Vec::new();
+            let flips = (0..count)
+                .map(|_| {
+                    app.buffer.uniform(2, &mut used).map(|x| match x {
+                        0 => "heads",
+                        _ => "tails",
+                    })
+                })
+                .collect::<Option<Vec<_>>>();
+            app.charge(req.address.ip(), used.len());
+            let flips = match flips {
+                Some(i) => i,
+                None => {
+                    return Response::new()
+                        .status(400)
+                        .text("Buffer not filled enough.")
+                }
+            };
+
+            Response::new()
+                .text(serde_json::to_string(&flips).unwrap())
+                .content(Content::JSON)
+                .header("X-Entropy", entropy(&used).to_string())
+        });
+
+        // Gets a random version 4 UUID
+        server.stateful_route(Method::GET, "/data/uuid", |app, req| {
+            if let Some(res) = check_limit(&app, req.address.ip(), 16) {
+                return res;
+            }
+
+            let mut data = match app.buffer.get_raw(16) {
+                Some(i) => i,
+                None => {
+                    return Response::new()
+                        .status(400)
+                        .text("Buffer not filled enough.")
+                }
+            };
+            app.charge(req.address.ip(), data.len());
+            let entropy = entropy(&data);
+
+            // Set the version (4) and variant (RFC 4122) bits
+            data[6] = (data[6] & 0x0F) | 0x40;
+            data[8] = (data[8] & 0x3F) | 0x80;
+
+            let hex = data.iter().map(|x| format!("{x:02x}")).collect::<String>();
+            let uuid = format!(
+                "{}-{}-{}-{}-{}",
+                &hex[0..8],
+                &hex[8..12],
+                &hex[12..16],
+                &hex[16..20],
+                &hex[20..32]
+            );
+
+            Response::new()
+                .text(uuid)
+                .header("X-Entropy", entropy.to_string())
+        });
+    }
+}
+
+/// Middleware that checks the token and rate limit before any of the data routes are run.
+mod access {
+    use std::{sync::Arc, time::Instant};
+
+    use afire::{
+        middleware::{MiddleResult, Middleware},
+        Request, Response,
+    };
+
+    use super::TrueRandom;
+
+    /// Routes that only report on the buffer, without giving out any of its data.
+    const OPEN_ROUTES: &[&str] = &["/status", "/health"];
+
+    pub struct Access {
+        app: Arc<TrueRandom>,
+    }
+
+    impl Access {
+        pub fn new(app: Arc<TrueRandom>) -> Self {
+            Self { app }
+        }
+    }
+
+    impl Middleware for Access {
+        fn pre(&self, req: &mut Request) -> MiddleResult {
+            if OPEN_ROUTES.contains(&req.path.as_str()) {
+                return MiddleResult::Continue;
+            }
+
+            // The token can be sent on its own, or as a bearer token
+            if let Some(token) = &self.app.args.token {
+                let given = req.headers.get("Authorization").unwrap_or_default();
+                let given = given.strip_prefix("Bearer ").unwrap_or(given);
+                if !constant_time_eq(given.as_bytes(), token.as_bytes()) {
+                    return MiddleResult::Send(
+                        Response::new()
+                            .status(401)
+                            .text("Missing or invalid token.")
+                            .header("WWW-Authenticate", "Bearer"),
+                    );
+                }
+            }
+
+            if let Some(limiter) = &self.app.limiter {
+                if let Err(wait) = limiter.check(req.address.ip(), Instant::now()) {
+                    return MiddleResult::Send(
+                        Response::new()
+                            .status(429)
+                            .text("Rate limit exceeded.")
+                            .header("Retry-After", wait.as_secs_f32().ceil().to_string()),
+                    );
+                }
+            }
+
+            MiddleResult::Continue
+        }
+    }
+
+    /// Compares two secrets in a time that only depends on their length, so a token can't be guessed a byte at a time.
+    pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        net::{IpAddr, Ipv4Addr},
+        time::{Duration, Instant},
+    };
+
+    use super::{
+        access::constant_time_eq, monobit_test, repetition_count_test, runs_test, to_bits,
+        uniform_bytes, Buffer, Debias, RateLimiter,
+    };
+
+    /// Converts a string of ones and zeros into bits.
+    fn bits(s: &str) -> Vec<bool> {
+        s.chars().map(|x| x == '1').collect()
+    }
+
+    #[test]
+    fn test_monobit() {
+        // Example from NIST SP 800-22 section 2.1.8
+        let test = monobit_test(&bits("1011010101"));
+        assert!((test.statistic - 0.527089).abs() < 1e-5);
+        assert!(test.passed);
+
+        assert!(!monobit_test(&to_bits(&[0; 128])).passed);
+    }
+
+    #[test]
+    fn test_runs() {
+        // Example from NIST SP 800-22 section 2.3.8
+        let test = runs_test(&bits("1001101011"));
+        assert!((test.statistic - 0.147232).abs() < 1e-5);
+        assert!(test.passed);
+
+        // Balanced, but alternates far too often
+        assert!(!runs_test(&to_bits(&[0x55; 128])).passed);
+    }
+
+    #[test]
+    fn test_repetition_count() {
+        assert!(repetition_count_test(&[1, 2, 3, 3, 4, 5]).passed);
+        assert!(!repetition_count_test(&[0; 64]).passed);
+        assert!(!repetition_count_test(&[]).passed);
+    }
+
+    #[test]
+    fn test_uniform_bytes() {
+        assert_eq!(uniform_bytes(2), 1);
+        assert_eq!(uniform_bytes(6), 1);
+        assert_eq!(uniform_bytes(256), 1);
+        assert_eq!(uniform_bytes(257), 2);
+        assert_eq!(uniform_bytes(u32::MAX), 4);
+    }
+
+    #[test]
+    fn test_fill_rate() {
+        let buffer = Buffer::new(1000, Debias::None);
+        assert_eq!(buffer.fill_rate(), 0.0);
+        assert_eq!(buffer.time_to_full(), None);
+
+        // 100 bytes a second, with the first fill only marking the start
+        let start = Instant::now();
+        for i in 0..5 {
+            buffer.record_fill(start + Duration::from_secs(i), 100);
+        }
+        assert_eq!(buffer.fill_rate(), 100.0);
+        assert_eq!(buffer.time_to_full(), Some(10.0));
+
+        // Fills older than the window are forgotten
+        buffer.record_fill(start + Duration::from_secs(20), 400);
+        buffer.record_fill(start + Duration::from_secs(22), 400);
+        assert_eq!(buffer.fill_rate(), 200.0);
+
+        buffer.load(&[0; 1000]);
+        assert_eq!(buffer.time_to_full(), Some(0.0));
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let limiter = RateLimiter::new(100.0);
+        let (a, b) = (
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+        );
+        let start = Instant::now();
+        assert!(limiter.check(a, start).is_ok());
+
+        // A big request puts the client in debt, without affecting anyone else
+        limiter.charge(a, 300, start);
+        assert_eq!(limiter.check(a, start), Err(Duration::from_secs(2)));
+        assert!(limiter.check(b, start).is_ok());
+
+        let later = start + Duration::from_secs(1);
+        assert_eq!(limiter.check(a, later), Err(Duration::from_secs(1)));
+        assert!(limiter.check(a, start + Duration::from_secs(2)).is_ok());
+
+        // The allowance doesn't build up past one second of bytes while idle
+        let idle = start + Duration::from_secs(60);
+        limiter.charge(a, 150, idle);
+        assert!(limiter.check(a, idle).is_err());
+    }
+
+    #[test]
+    fn test_rate_limit_request() {
+        let limiter = RateLimiter::new(100.0);
+        let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let start = Instant::now();
+
+        // A single request bigger than a full allowance is refused before anything is taken
+        assert_eq!(limiter.check_take(ip, 300, start), Err(None));
+        assert_eq!(limiter.allowance(ip, start), 100.0);
+
+        // Smaller requests are allowed until the allowance runs out
+        assert!(limiter.check_take(ip, 100, start).is_ok());
+        limiter.charge(ip, 50, start);
+        assert_eq!(limiter.allowance(ip, start), 50.0);
+        assert_eq!(
+            limiter.check_take(ip, 100, start),
+            Err(Some(Duration::from_millis(500)))
+        );
+        assert!(limiter
+            .check_take(ip, 100, start + Duration::from_millis(500))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+        assert!(!constant_time_eq(b"", b"secret"));
+    }
+
+    /// Generates bits where about 80% are ones, using a simple LCG.
+    fn biased_bits(len: usize) -> Vec<bool> {
+        let mut state = 0x2545F491u32;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                (state >> 8) % 10 < 8
+            })
+            .collect()
+    }
+
+    fn ratio(bits: &[bool]) -> f32 {
+        bits.iter().filter(|&&x| x).count() as f32 / bits.len() as f32
+    }
+
+    #[test]
+    fn test_debias() {
+        let bits = biased_bits(100_000);
+        let bias = (ratio(&bits) - 0.5).abs();
+        assert!(bias > 0.25);
+
+        assert_eq!(Debias::None.apply(&bits), bits);
+        for mode in [Debias::VonNeumann, Debias::XorFold] {
+            let out = mode.apply(&bits);
+            assert!((ratio(&out) - 0.5).abs() < bias, "{mode:?}");
+        }
+
+        let von_neumann = Debias::VonNeumann.apply(&bits);
+        assert!((ratio(&von_neumann) - 0.5).abs() < 0.01);
     }
 }
@@ -0,0 +1,170 @@
+//! Tuning module that shows the strength of a single frequency as a bar in the terminal.
+//! Useful for aligning a transmitter or finding a carrier, where the spectrum analyzer is too coarse.
+//!
+//! The magnitude is measured with the Goertzel algorithm over a sliding window, whose length is set by the bandwidth.
+//! The frequency isn't rounded to a DFT bin, so narrow windows still measure exactly the frequency being tuned to.
+//! It can optionally be played back as a tone that rises in pitch as the signal gets stronger.
+
+use std::{
+    f32::consts::TAU,
+    io::{stdout, Write},
+    sync::Arc,
+    time::Duration,
+};
+
+use crossterm::{cursor, queue, style, terminal};
+use parking_lot::Mutex;
+
+use super::meter::bar_position;
+use super::{InitContext, Module};
+use crate::audio::algorithms::{goertzel_mag_exact, to_db, to_mono, SlidingWindow, DB_MIN};
+
+/// How often the magnitude is measured and the bar redrawn.
+const UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+/// The pitch of the feedback tone with no signal.
+const SONIFY_LOW: f32 = 200.0;
+/// The pitch of the feedback tone with a full-scale signal.
+const SONIFY_HIGH: f32 = 1200.0;
+/// The amplitude of the feedback tone.
+const SONIFY_AMPLITUDE: f32 = 0.2;
+
+pub struct Tune {
+    ctx: InitContext,
+
+    // == Settings ==
+    /// The frequency being measured.
+    freq: f32,
+    sonify: bool,
+
+    // == Internal ==
+    window: Mutex<SlidingWindow>,
+    /// The last measured magnitude.
+    magnitude: Mutex<f32>,
+    /// The phase of the feedback tone, from 0 to 1.
+    phase: Mutex<f32>,
+}
+
+impl Tune {
+    pub fn new(ctx: InitContext) -> Arc<Self> {
+        let freq = *ctx
+            .args
+            .get_one::<f32>("freq")
+            .or_else(|| ctx.args.get_one::<f32>("freq-option"))
+            .unwrap();
+        let bandwidth = *ctx.args.get_one::<f32>("bandwidth").unwrap();
+        let sonify = ctx.args.get_flag("sonify");
+
+        let sample_rate = ctx.sample_rate().input as f32;
+        let window = ((sample_rate / bandwidth) as usize).max(1);
+        let hop = (sample_rate * UPDATE_INTERVAL.as_secs_f32()) as usize;
+
+        Arc::new(Self {
+            ctx,
+            freq,
+            sonify,
+            window: Mutex::new(SlidingWindow::new(window, hop)),
+            magnitude: Mutex::new(0.0),
+            phase: Mutex::new(0.0),
+        })
+    }
+
+    /// Redraws the bar in place.
+    fn render(&self, magnitude: f32) {
+        let width = terminal::size().map(|x| x.0 as usize).unwrap_or(80);
+        let bar_width = width.saturating_sub(30).max(10);
+        let fill = bar_position(magnitude, DB_MIN, bar_width);
+
+        let mut stdout = stdout();
+        queue!(
+            stdout,
+            cursor::MoveToPreviousLine(1),
+            terminal::Clear(terminal::ClearType::CurrentLine),
+            style::Print("["),
+            style::SetForegroundColor(style::Color::Green),
+            style::Print("█".repeat(fill)),
+            style::Print(" ".repeat(bar_width - fill)),
+            style::ResetColor,
+            style::Print(format!(
+                "] {:>6.1} dBFS ({magnitude:.4})\n",
+                to_db(magnitude, DB_MIN)
+            )),
+        )
+        .unwrap();
+        stdout.flush().unwrap();
+    }
+}
+
+impl Module for Tune {
+    fn name(&self) -> &'static str {
+        "tune"
+    }
+
+    fn init(&self) {
+        let window = self.window.lock().size();
+        println!(
+            "[I] Tuning to {}Hz, window: {window} samples ({:.2}Hz bandwidth)",
+            self.freq,
+            self.ctx.sample_rate().input as f32 / window as f32
+        );
+
+        // Make room for the bar, as it is redrawn over the previous line
+        println!();
+    }
+
+    fn input(&self, input: &[f32]) {
+        let mut window = self.window.lock();
+        let mono = to_mono(input, self.ctx.input.channels() as usize);
+        let samples = match window.push(&mono) {
+            Some(samples) => samples,
+            None => return,
+        };
+
+        let magnitude = goertzel_mag_exact(self.freq, samples, self.ctx.sample_rate().input);
+        *self.magnitude.lock() = magnitude;
+        self.render(magnitude);
+    }
+
+    fn output(&self, output: &mut [f32]) {
+        if !self.sonify {
+            return;
+        }
+
+        // Plays the feedback tone on each channel
+        let step = pitch(*self.magnitude.lock()) / self.ctx.sample_rate().output as f32;
+        let mut phase = self.phase.lock();
+        for frame in output.chunks_mut(self.ctx.output.channels() as usize) {
+            *phase = (*phase + step) % 1.0;
+            frame.fill((*phase * TAU).sin() * SONIFY_AMPLITUDE);
+        }
+    }
+}
+
+/// Gets how strong a magnitude is on the bar, from 0 for [`DB_MIN`] to 1 for full scale.
+fn strength(magnitude: f32) -> f32 {
+    ((to_db(magnitude, DB_MIN) - DB_MIN) / -DB_MIN).clamp(0.0, 1.0)
+}
+
+/// Gets the pitch of the feedback tone for a magnitude.
+fn pitch(magnitude: f32) -> f32 {
+    SONIFY_LOW + (SONIFY_HIGH - SONIFY_LOW) * strength(magnitude)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{pitch, strength, SONIFY_HIGH, SONIFY_LOW};
+
+    #[test]
+    fn test_strength() {
+        assert_eq!(strength(0.0), 0.0);
+        assert_eq!(strength(1.0), 1.0);
+        assert_eq!(strength(2.0), 1.0);
+        assert!((strength(0.01) - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_pitch() {
+        assert_eq!(pitch(0.0), SONIFY_LOW);
+        assert_eq!(pitch(1.0), SONIFY_HIGH);
+        assert!(pitch(0.1) > pitch(0.01));
+    }
+}